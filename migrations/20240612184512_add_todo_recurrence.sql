@@ -0,0 +1,4 @@
+-- Recurring todos carry an iCalendar RRULE (without the "RRULE:" prefix) and the
+-- due date of the current occurrence, which acts as the rule's DTSTART.
+ALTER TABLE todos ADD COLUMN due_at TIMESTAMP;
+ALTER TABLE todos ADD COLUMN rrule TEXT;
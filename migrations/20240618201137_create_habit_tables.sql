@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS habits (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id       INTEGER NOT NULL,
+    name          TEXT NOT NULL,
+    -- Target frequency, e.g. 3 times per 'week'
+    target_count  INTEGER NOT NULL DEFAULT 1,
+    target_period TEXT NOT NULL DEFAULT 'day',
+    created_at    TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS habit_checkins (
+    habit_id   INTEGER NOT NULL,
+    day        DATE NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (habit_id, day),
+    FOREIGN KEY (habit_id) REFERENCES habits (id) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS projects (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id    INTEGER NOT NULL,
+    name       TEXT NOT NULL,
+    archived   BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Deleting a project keeps its todos, they just no longer belong to a project
+ALTER TABLE todos ADD COLUMN project_id INTEGER REFERENCES projects (id) ON DELETE SET NULL;
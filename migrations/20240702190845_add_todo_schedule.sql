@@ -0,0 +1,7 @@
+-- IANA time zone name used to interpret and display dates
+ALTER TABLE users ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+
+-- Todo timestamps are stored in UTC
+ALTER TABLE todos ADD COLUMN start_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS todos_user_due_at ON todos (user_id, due_at);
@@ -0,0 +1,43 @@
+CREATE TABLE IF NOT EXISTS reminders (
+    id             INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id        INTEGER NOT NULL,
+    todo_id        INTEGER,
+    -- Either an absolute time, or a number of minutes before the todo is due
+    remind_at      TIMESTAMP,
+    offset_minutes INTEGER,
+    message        TEXT,
+    fired_at       TIMESTAMP,
+    -- Failed deliveries are retried with a backoff
+    attempts       INTEGER NOT NULL DEFAULT 0,
+    retry_at       TIMESTAMP,
+    created_at     TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (todo_id) REFERENCES todos (id) ON DELETE CASCADE,
+    CHECK (remind_at IS NOT NULL OR (todo_id IS NOT NULL AND offset_minutes IS NOT NULL))
+);
+
+CREATE INDEX IF NOT EXISTS reminders_pending ON reminders (fired_at);
+
+-- Sinks a reminder was delivered to, so retries only go to the ones which failed
+CREATE TABLE IF NOT EXISTS reminder_deliveries (
+    reminder_id  INTEGER NOT NULL,
+    sink         TEXT NOT NULL,
+    delivered_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (reminder_id, sink),
+    FOREIGN KEY (reminder_id) REFERENCES reminders (id) ON DELETE CASCADE
+);
+
+-- When each reminder fires. Relative reminders follow their todo's due date,
+-- and reminders of completed todos never fire.
+CREATE VIEW IF NOT EXISTS reminder_schedule AS
+SELECT
+    r.id,
+    r.user_id,
+    r.todo_id,
+    r.message,
+    r.fired_at,
+    t.title AS todo_title,
+    COALESCE(r.remind_at, datetime(t.due_at, printf('%+d minutes', -r.offset_minutes))) AS fire_at
+FROM reminders r
+LEFT JOIN todos t ON t.id = r.todo_id
+WHERE t.id IS NULL OR t.completed = false;
@@ -0,0 +1,15 @@
+-- Delivered by the in-app reminder sink
+CREATE TABLE IF NOT EXISTS notifications (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id    INTEGER NOT NULL,
+    todo_id    INTEGER,
+    title      TEXT NOT NULL,
+    body       TEXT,
+    read_at    TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (todo_id) REFERENCES todos (id) ON DELETE SET NULL
+);
+
+-- Unread counts are polled by the navbar
+CREATE INDEX IF NOT EXISTS notifications_user_read_at ON notifications (user_id, read_at);
@@ -0,0 +1,7 @@
+-- Subtasks are todos with a parent, ordered by position among their siblings
+ALTER TABLE todos ADD COLUMN parent_id INTEGER REFERENCES todos (id) ON DELETE CASCADE;
+ALTER TABLE todos ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+-- Complete the todo once all of its subtasks are done
+ALTER TABLE todos ADD COLUMN auto_complete BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS todos_parent_id ON todos (parent_id, position);
@@ -0,0 +1,5 @@
+-- Manual order of top-level todos, see src/rank.rs
+ALTER TABLE todos ADD COLUMN rank TEXT;
+UPDATE todos SET rank = 'V' || printf('%07d', id) || 'V' WHERE parent_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS todos_user_rank ON todos (user_id, rank);
@@ -0,0 +1,29 @@
+-- Full-text index of todos, along with the name of their project
+CREATE VIRTUAL TABLE IF NOT EXISTS todos_fts USING fts5 (
+    title,
+    project,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO todos_fts (rowid, title, project)
+SELECT t.id, t.title, p.name FROM todos t LEFT JOIN projects p ON p.id = t.project_id;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_insert AFTER INSERT ON todos BEGIN
+    INSERT INTO todos_fts (rowid, title, project)
+    VALUES (new.id, new.title, (SELECT name FROM projects WHERE id = new.project_id));
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_update AFTER UPDATE OF title, project_id ON todos BEGIN
+    DELETE FROM todos_fts WHERE rowid = old.id;
+    INSERT INTO todos_fts (rowid, title, project)
+    VALUES (new.id, new.title, (SELECT name FROM projects WHERE id = new.project_id));
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_delete AFTER DELETE ON todos BEGIN
+    DELETE FROM todos_fts WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_project_rename AFTER UPDATE OF name ON projects BEGIN
+    UPDATE todos_fts SET project = new.name
+    WHERE rowid IN (SELECT id FROM todos WHERE project_id = new.id);
+END;
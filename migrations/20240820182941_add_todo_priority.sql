@@ -0,0 +1,4 @@
+-- From 0 (none) to 4 (urgent)
+ALTER TABLE todos ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+-- Sort mode of the todo list, remembered across sessions
+ALTER TABLE users ADD COLUMN todo_sort TEXT NOT NULL DEFAULT 'manual';
@@ -0,0 +1,38 @@
+ALTER TABLE todos ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+
+-- Notes are searched too, so the full-text index is rebuilt with them
+DROP TRIGGER IF EXISTS todos_fts_insert;
+DROP TRIGGER IF EXISTS todos_fts_update;
+DROP TRIGGER IF EXISTS todos_fts_delete;
+DROP TRIGGER IF EXISTS todos_fts_project_rename;
+DROP TABLE IF EXISTS todos_fts;
+
+CREATE VIRTUAL TABLE todos_fts USING fts5 (
+    title,
+    notes,
+    project,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO todos_fts (rowid, title, notes, project)
+SELECT t.id, t.title, t.notes, p.name FROM todos t LEFT JOIN projects p ON p.id = t.project_id;
+
+CREATE TRIGGER todos_fts_insert AFTER INSERT ON todos BEGIN
+    INSERT INTO todos_fts (rowid, title, notes, project)
+    VALUES (new.id, new.title, new.notes, (SELECT name FROM projects WHERE id = new.project_id));
+END;
+
+CREATE TRIGGER todos_fts_update AFTER UPDATE OF title, notes, project_id ON todos BEGIN
+    DELETE FROM todos_fts WHERE rowid = old.id;
+    INSERT INTO todos_fts (rowid, title, notes, project)
+    VALUES (new.id, new.title, new.notes, (SELECT name FROM projects WHERE id = new.project_id));
+END;
+
+CREATE TRIGGER todos_fts_delete AFTER DELETE ON todos BEGIN
+    DELETE FROM todos_fts WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER todos_fts_project_rename AFTER UPDATE OF name ON projects BEGIN
+    UPDATE todos_fts SET project = new.name
+    WHERE rowid IN (SELECT id FROM todos WHERE project_id = new.id);
+END;
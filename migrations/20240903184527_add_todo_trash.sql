@@ -0,0 +1,19 @@
+-- Deleted todos stay in the trash until restored, purged or expired
+ALTER TABLE todos ADD COLUMN deleted_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS todos_deleted_at ON todos (deleted_at);
+
+-- Reminders of todos in the trash don't fire either
+DROP VIEW IF EXISTS reminder_schedule;
+CREATE VIEW reminder_schedule AS
+SELECT
+    r.id,
+    r.user_id,
+    r.todo_id,
+    r.message,
+    r.fired_at,
+    t.title AS todo_title,
+    COALESCE(r.remind_at, datetime(t.due_at, printf('%+d minutes', -r.offset_minutes))) AS fire_at
+FROM reminders r
+LEFT JOIN todos t ON t.id = r.todo_id
+WHERE t.id IS NULL OR (t.completed = false AND t.deleted_at IS NULL);
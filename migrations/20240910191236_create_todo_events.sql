@@ -0,0 +1,24 @@
+-- When the todo was last completed, unknown for todos completed before it was recorded
+ALTER TABLE todos ADD COLUMN completed_at TIMESTAMP;
+
+-- Append-only history of each todo, see src/history.rs
+CREATE TABLE IF NOT EXISTS todo_events (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id    INTEGER NOT NULL,
+    -- Who made the change
+    user_id    INTEGER NOT NULL,
+    kind       TEXT NOT NULL,
+    -- What changed for edits and moves, as shown to the user
+    field      TEXT,
+    old_value  TEXT,
+    new_value  TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (todo_id) REFERENCES todos (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS todo_events_todo_id ON todo_events (todo_id, id);
+
+CREATE TRIGGER IF NOT EXISTS todo_events_append_only BEFORE UPDATE ON todo_events BEGIN
+    SELECT RAISE(ABORT, 'todo events cannot be changed');
+END;
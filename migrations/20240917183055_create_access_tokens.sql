@@ -0,0 +1,17 @@
+-- Personal access tokens authenticating the REST API, see src/tokens.rs
+CREATE TABLE IF NOT EXISTS access_tokens (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id      INTEGER NOT NULL,
+    name         TEXT NOT NULL,
+    -- SHA-256 of the token, which is only shown once when created
+    token_hash   TEXT NOT NULL UNIQUE,
+    -- Start of the token, to tell tokens apart
+    prefix       TEXT NOT NULL,
+    -- 'read' or 'write'
+    scope        TEXT NOT NULL DEFAULT 'read',
+    created_at   TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS access_tokens_user_id ON access_tokens (user_id);
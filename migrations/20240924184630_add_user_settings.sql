@@ -0,0 +1,8 @@
+-- Display preferences picked on the settings page, see src/settings.rs
+-- Day weeks start on, counted from Monday (0) to Sunday (6)
+ALTER TABLE users ADD COLUMN week_start INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE users ADD COLUMN date_format TEXT NOT NULL DEFAULT 'iso';
+
+-- 'system' follows the preference of the browser
+ALTER TABLE users ADD COLUMN theme TEXT NOT NULL DEFAULT 'system';
@@ -0,0 +1,19 @@
+-- Failed logins counted per client IP and per username, see src/throttle.rs
+CREATE TABLE IF NOT EXISTS login_throttles (
+    -- 'ip:<address>' or 'user:<username>'
+    key             TEXT PRIMARY KEY,
+    -- Failures in a row, reset after a successful login or a quiet day
+    failures        INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP NOT NULL,
+    locked_until    TIMESTAMP
+);
+
+-- Audit log of failed logins, kept even for usernames which don't exist
+CREATE TABLE IF NOT EXISTS failed_logins (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    username   TEXT NOT NULL,
+    ip         TEXT,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS failed_logins_username ON failed_logins (username, created_at);
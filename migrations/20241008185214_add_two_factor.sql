@@ -0,0 +1,19 @@
+-- Optional TOTP two-factor authentication, see src/two_factor.rs
+-- Base32 secret of the authenticator app, set while 2FA is on
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+-- Secret being enrolled, until a first code confirms it
+ALTER TABLE users ADD COLUMN totp_pending_secret TEXT;
+-- Time step of the last accepted code, so no code works twice
+ALTER TABLE users ADD COLUMN totp_last_step INTEGER;
+
+-- One-time codes to log in without the authenticator app
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    id        INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id   INTEGER NOT NULL,
+    -- SHA-256 of the code, which is only shown once when created
+    code_hash TEXT NOT NULL,
+    used_at   TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_user_id ON recovery_codes (user_id);
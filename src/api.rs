@@ -0,0 +1,813 @@
+//! Versioned REST API under `/api/v1`, for scripts which can't use the session cookie
+//! the server functions rely on.
+//!
+//! Requests are authenticated by personal access tokens, see [`crate::tokens`], and the
+//! OpenAPI document describing the API is generated from the handlers below.
+
+use crate::{
+    auth::User,
+    habits::{ssr::load_habits, Habit, HabitPeriod},
+    state::AppState,
+    todo::{Priority, Todo, MAX_TODO_PAGE_SIZE, TODO_PAGE_SIZE},
+    tokens::{ssr::authenticate, TokenScope},
+};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path, Query, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use leptos::ServerFnError;
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::SqlitePool;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    IntoParams, Modify, OpenApi, ToSchema,
+};
+
+/// Error returned by the API, as `{"error": "..."}`.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(what: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("{what} not found."))
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(ErrorBody { error: self.message })).into_response()
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        log::error!("API database error: {e}");
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal error.")
+    }
+}
+
+/// Errors of the helpers shared with the server functions. Only the known ones about the request
+/// reach the client, anything else like a database error is logged instead.
+impl From<ServerFnError> for ApiError {
+    fn from(e: ServerFnError) -> Self {
+        use crate::{
+            habits::ssr::habit_not_found,
+            projects::ssr::project_not_found,
+            todo::ssr::{missing_title, starts_after_due, todo_not_found},
+        };
+
+        let is = |known: fn() -> ServerFnError| known().to_string() == e.to_string();
+        let status = if [todo_not_found, project_not_found, habit_not_found].into_iter().any(is) {
+            StatusCode::NOT_FOUND
+        } else if [missing_title, starts_after_due].into_iter().any(is) {
+            StatusCode::BAD_REQUEST
+        } else {
+            log::error!("API error: {e}");
+            return Self::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal error.");
+        };
+        Self::new(status, server_message(e))
+    }
+}
+
+/// Message of an error of a server function, without the prefix added when displayed.
+fn server_message(e: ServerFnError) -> String {
+    match e {
+        ServerFnError::ServerError(message) => message,
+        e => e.to_string(),
+    }
+}
+
+/// User authenticated by the personal access token in the `Authorization: Bearer` header.
+pub struct ApiUser {
+    pub user: User,
+    pub scope: TokenScope,
+}
+
+impl ApiUser {
+    /// Makes sure the token may change things.
+    pub fn require_write(&self) -> Result<(), ApiError> {
+        match self.scope {
+            TokenScope::Write => Ok(()),
+            TokenScope::Read => Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "This token is read only.",
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiUser
+where
+    SqlitePool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        let unauthorized = || {
+            ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "A valid access token is needed, as `Authorization: Bearer <token>`.",
+            )
+        };
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(unauthorized)?;
+
+        let pool = SqlitePool::from_ref(state);
+        let (user, scope) = authenticate(token.trim(), &pool)
+            .await?
+            .ok_or_else(unauthorized)?;
+
+        Ok(ApiUser { user, scope })
+    }
+}
+
+/// Tells a missing field apart from a `null` one, which clears it.
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiTodo {
+    pub id: u32,
+    pub title: String,
+    /// Markdown notes.
+    pub notes: String,
+    pub completed: bool,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub start_at: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    /// RFC 5545 recurrence rule, like `FREQ=WEEKLY;BYDAY=MO`.
+    pub rrule: Option<String>,
+    pub project_id: Option<u32>,
+    /// Todo this one is a subtask of.
+    pub parent_id: Option<u32>,
+    pub priority: Priority,
+    /// Names of the tags of the todo.
+    pub tags: Vec<String>,
+}
+
+impl From<Todo> for ApiTodo {
+    fn from(todo: Todo) -> Self {
+        ApiTodo {
+            id: todo.id,
+            title: todo.title,
+            notes: todo.notes.markdown,
+            completed: todo.completed,
+            completed_at: todo.completed_at,
+            created_at: todo.created_at,
+            start_at: todo.start_at,
+            due_at: todo.due_at,
+            rrule: todo.recurrence.map(|recurrence| recurrence.to_string()),
+            project_id: todo.project_id,
+            parent_id: todo.parent_id,
+            priority: todo.priority,
+            tags: todo.tags.into_iter().map(|tag| tag.name).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TodoQuery {
+    /// Only todos of this project.
+    pub project_id: Option<u32>,
+    /// Only completed or open todos.
+    pub completed: Option<bool>,
+    /// ID of the last todo of the previous page.
+    pub after: Option<u32>,
+    /// Todos per page, 100 by default and 500 at most.
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewTodo {
+    pub title: String,
+    pub notes: Option<String>,
+    pub start_at: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub rrule: Option<String>,
+    pub project_id: Option<u32>,
+    /// Makes the todo a subtask of another one, its project is then left out.
+    pub parent_id: Option<u32>,
+    pub priority: Option<Priority>,
+}
+
+/// Changes to a todo, fields left out are kept as they are.
+#[derive(Deserialize, ToSchema)]
+pub struct TodoPatch {
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub completed: Option<bool>,
+    /// `null` removes the due date.
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    pub priority: Option<Priority>,
+    /// `null` takes the todo out of its project.
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<u32>)]
+    pub project_id: Option<Option<u32>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiProject {
+    pub id: u32,
+    pub name: String,
+    pub archived: bool,
+    /// Number of top-level todos in the project.
+    pub total: u32,
+    /// Number of completed top-level todos in the project.
+    pub completed: u32,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewProject {
+    pub name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiHabit {
+    pub id: u32,
+    pub name: String,
+    /// Times the habit should be done per period.
+    pub target_count: u32,
+    pub target_period: HabitPeriod,
+    pub created_at: NaiveDate,
+    pub checked_today: bool,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Share of finished periods meeting the target, from 0 to 1.
+    pub completion_rate: f32,
+}
+
+impl From<Habit> for ApiHabit {
+    fn from(habit: Habit) -> Self {
+        ApiHabit {
+            id: habit.id,
+            name: habit.name,
+            target_count: habit.target.count,
+            target_period: habit.target.period,
+            created_at: habit.created_at,
+            checked_today: habit.checked_today,
+            current_streak: habit.stats.current_streak,
+            longest_streak: habit.stats.longest_streak,
+            completion_rate: habit.stats.completion_rate,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewCheckin {
+    /// Day of the check-in, today in the user's time zone by default.
+    pub day: Option<NaiveDate>,
+}
+
+/// Loads a todo of `user` with its tags, even if it's a subtask.
+async fn load_todo(id: u32, user: &User, pool: &SqlitePool) -> Result<ApiTodo, ApiError> {
+    use crate::{
+        tags::ssr::load_tags,
+        todo::ssr::{SqlTodo, SELECT_TODOS},
+    };
+
+    let todo = sqlx::query_as::<_, SqlTodo>(&format!(
+        "{SELECT_TODOS} WHERE t.id = ? AND t.user_id = ? AND t.deleted_at IS NULL"
+    ))
+    .bind(id)
+    .bind(user.id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Todo"))?;
+    let mut todos = [todo.into_todo(user)];
+    load_tags(&mut todos, pool).await?;
+    let [todo] = todos;
+
+    Ok(todo.into())
+}
+
+/// Makes sure `id` refers to a todo of `user` which is not in the trash.
+async fn authorize_todo(id: u32, user: &User, pool: &SqlitePool) -> Result<(), ApiError> {
+    Ok(crate::todo::ssr::authorize_todo(id, user, pool).await?)
+}
+
+async fn authorize_project(id: u32, user: &User, pool: &SqlitePool) -> Result<(), ApiError> {
+    Ok(crate::projects::ssr::authorize_project(id, user, pool).await?)
+}
+
+/// Lists the todos of the user, subtasks included, by ID.
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos",
+    params(TodoQuery),
+    responses((status = 200, body = [ApiTodo]), (status = 401, body = ErrorBody)),
+    security(("token" = [])),
+    tag = "todos"
+)]
+pub async fn list_todos(
+    State(pool): State<SqlitePool>,
+    ApiUser { user, .. }: ApiUser,
+    Query(query): Query<TodoQuery>,
+) -> Result<Json<Vec<ApiTodo>>, ApiError> {
+    use crate::{
+        tags::ssr::load_tags,
+        todo::ssr::{SqlTodo, SELECT_TODOS},
+    };
+
+    let limit = query.limit.unwrap_or(TODO_PAGE_SIZE).clamp(1, MAX_TODO_PAGE_SIZE);
+    let mut todos: Vec<_> = sqlx::query_as::<_, SqlTodo>(&format!(
+        "{SELECT_TODOS} WHERE t.user_id = ? AND t.deleted_at IS NULL
+        AND (? IS NULL OR t.project_id = ?) AND (? IS NULL OR t.completed = ?) AND t.id > ?
+        ORDER BY t.id LIMIT ?"
+    ))
+    .bind(user.id)
+    .bind(query.project_id)
+    .bind(query.project_id)
+    .bind(query.completed)
+    .bind(query.completed)
+    .bind(query.after.unwrap_or(0))
+    .bind(limit)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|todo| todo.into_todo(&user))
+    .collect();
+    load_tags(&mut todos, &pool).await?;
+
+    Ok(Json(todos.into_iter().map(ApiTodo::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/{id}",
+    params(("id" = u32, Path, description = "ID of the todo")),
+    responses((status = 200, body = ApiTodo), (status = 404, body = ErrorBody)),
+    security(("token" = [])),
+    tag = "todos"
+)]
+pub async fn get_todo(
+    State(pool): State<SqlitePool>,
+    ApiUser { user, .. }: ApiUser,
+    Path(id): Path<u32>,
+) -> Result<Json<ApiTodo>, ApiError> {
+    Ok(Json(load_todo(id, &user, &pool).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos",
+    request_body = NewTodo,
+    responses(
+        (status = 201, body = ApiTodo),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody)
+    ),
+    security(("token" = [])),
+    tag = "todos"
+)]
+pub async fn create_todo(
+    State(state): State<AppState>,
+    api_user: ApiUser,
+    Json(new): Json<NewTodo>,
+) -> Result<(StatusCode, Json<ApiTodo>), ApiError> {
+    use crate::{
+        history::{ssr as history, TodoEventKind},
+        subtasks::ssr::sync_completion,
+        todo::ssr::{missing_title, next_rank, parse_rrule, starts_after_due},
+    };
+
+    api_user.require_write()?;
+    let user = &api_user.user;
+    let pool = &state.pool;
+
+    let title = new.title.trim();
+    if title.is_empty() {
+        return Err(missing_title().into());
+    }
+    if let (Some(start_at), Some(due_at)) = (new.start_at, new.due_at) {
+        if start_at > due_at {
+            return Err(starts_after_due().into());
+        }
+    }
+    let recurrence = parse_rrule(new.rrule)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, server_message(e)))?;
+    // A recurrence rule needs a first occurrence to expand from
+    let due_at = match (&recurrence, new.due_at) {
+        (Some(_), None) => Some(Utc::now()),
+        (_, due_at) => due_at,
+    };
+    if let Some(parent_id) = new.parent_id {
+        authorize_todo(parent_id, user, pool).await?;
+    } else if let Some(project_id) = new.project_id {
+        authorize_project(project_id, user, pool).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    let (project_id, rank) = match new.parent_id {
+        Some(_) => (None, None),
+        None => (new.project_id, Some(next_rank(user, &mut *tx).await?)),
+    };
+    let id = sqlx::query_scalar::<_, u32>(
+        "INSERT INTO todos (title, notes, user_id, completed, start_at, due_at, rrule, project_id, parent_id, position, rank, priority)
+        SELECT ?, ?, ?, false, ?, ?, ?, ?, ?, COALESCE(MAX(position) + 1, 0), ?, ? FROM todos WHERE parent_id = ?
+        RETURNING id",
+    )
+    .bind(title)
+    .bind(new.notes.unwrap_or_default())
+    .bind(user.id)
+    .bind(new.start_at.map(|start_at| start_at.naive_utc()))
+    .bind(due_at.map(|due_at| due_at.naive_utc()))
+    .bind(recurrence.map(|recurrence| recurrence.to_string()))
+    .bind(project_id)
+    .bind(new.parent_id)
+    .bind(rank)
+    .bind(new.priority.unwrap_or_default().level())
+    .bind(new.parent_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    history::record(&mut tx, id, user.id, TodoEventKind::Created).await?;
+    // A new open subtask reopens a parent completed automatically
+    sync_completion(&mut tx, new.parent_id).await?;
+    tx.commit().await?;
+
+    if due_at.is_some() {
+        state.scheduler.wake();
+    }
+
+    Ok((StatusCode::CREATED, Json(load_todo(id, user, pool).await?)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/todos/{id}",
+    params(("id" = u32, Path, description = "ID of the todo")),
+    request_body = TodoPatch,
+    responses(
+        (status = 200, body = ApiTodo),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    ),
+    security(("token" = [])),
+    tag = "todos"
+)]
+pub async fn update_todo(
+    State(state): State<AppState>,
+    api_user: ApiUser,
+    Path(id): Path<u32>,
+    Json(patch): Json<TodoPatch>,
+) -> Result<Json<ApiTodo>, ApiError> {
+    use crate::todo::ssr::{edit_details, set_completed, TodoChanges};
+
+    api_user.require_write()?;
+    let user = &api_user.user;
+    let pool = &state.pool;
+
+    authorize_todo(id, user, pool).await?;
+    if let Some(Some(project_id)) = patch.project_id {
+        authorize_project(project_id, user, pool).await?;
+    }
+
+    let due_changed = patch.due_at.is_some();
+    let changes = TodoChanges {
+        title: patch.title,
+        notes: patch.notes,
+        due_at: patch.due_at,
+        priority: patch.priority,
+        project_id: patch.project_id,
+    };
+    let mut tx = pool.begin().await?;
+    edit_details(&mut tx, user, id, changes).await?;
+    if let Some(completed) = patch.completed {
+        set_completed(&mut tx, user, id, completed).await?;
+    }
+    tx.commit().await?;
+
+    // Reminders relative to the due date may now fire earlier
+    if due_changed {
+        state.scheduler.wake();
+    }
+
+    Ok(Json(load_todo(id, user, pool).await?))
+}
+
+/// Moves a todo to the trash, from which it can still be restored in the app.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/todos/{id}",
+    params(("id" = u32, Path, description = "ID of the todo")),
+    responses(
+        (status = 204),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    ),
+    security(("token" = [])),
+    tag = "todos"
+)]
+pub async fn delete_todo(
+    State(pool): State<SqlitePool>,
+    api_user: ApiUser,
+    Path(id): Path<u32>,
+) -> Result<StatusCode, ApiError> {
+    use crate::trash::ssr::trash_todo;
+
+    api_user.require_write()?;
+    authorize_todo(id, &api_user.user, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    trash_todo(&mut tx, &api_user.user, id, Utc::now()).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/projects",
+    responses((status = 200, body = [ApiProject]), (status = 401, body = ErrorBody)),
+    security(("token" = [])),
+    tag = "projects"
+)]
+pub async fn list_projects(
+    State(pool): State<SqlitePool>,
+    ApiUser { user, .. }: ApiUser,
+) -> Result<Json<Vec<ApiProject>>, ApiError> {
+    use crate::projects::ssr::{SqlProject, SELECT_PROJECTS};
+
+    Ok(Json(
+        sqlx::query_as::<_, SqlProject>(&format!("{SELECT_PROJECTS} GROUP BY p.id ORDER BY p.id"))
+            .bind(user.id)
+            .fetch_all(&pool)
+            .await?
+            .into_iter()
+            .map(|project| ApiProject {
+                id: project.id,
+                name: project.name,
+                archived: project.archived,
+                total: project.total,
+                completed: project.completed,
+            })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/projects",
+    request_body = NewProject,
+    responses(
+        (status = 201, body = ApiProject),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody)
+    ),
+    security(("token" = [])),
+    tag = "projects"
+)]
+pub async fn create_project(
+    State(pool): State<SqlitePool>,
+    api_user: ApiUser,
+    Json(new): Json<NewProject>,
+) -> Result<(StatusCode, Json<ApiProject>), ApiError> {
+    api_user.require_write()?;
+
+    let name = new.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "A project needs a name."));
+    }
+    let id = sqlx::query_scalar::<_, u32>(
+        "INSERT INTO projects (user_id, name) VALUES (?, ?) RETURNING id",
+    )
+    .bind(api_user.user.id)
+    .bind(name)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiProject {
+            id,
+            name: name.to_string(),
+            archived: false,
+            total: 0,
+            completed: 0,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/habits",
+    responses((status = 200, body = [ApiHabit]), (status = 401, body = ErrorBody)),
+    security(("token" = [])),
+    tag = "habits"
+)]
+pub async fn list_habits(
+    State(pool): State<SqlitePool>,
+    ApiUser { user, .. }: ApiUser,
+) -> Result<Json<Vec<ApiHabit>>, ApiError> {
+    Ok(Json(
+        load_habits(&user, &pool)
+            .await?
+            .into_iter()
+            .map(ApiHabit::from)
+            .collect(),
+    ))
+}
+
+/// Makes sure `id` refers to a habit of `user`.
+async fn authorize_habit(id: u32, user: &User, pool: &SqlitePool) -> Result<(), ApiError> {
+    sqlx::query("SELECT id FROM habits WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(pool)
+        .await?
+        .map(|_| ())
+        .ok_or_else(|| ApiError::not_found("Habit"))
+}
+
+/// Checks a habit in, doing nothing if it already is for that day.
+#[utoipa::path(
+    post,
+    path = "/api/v1/habits/{id}/checkins",
+    params(("id" = u32, Path, description = "ID of the habit")),
+    request_body = NewCheckin,
+    responses(
+        (status = 204),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    ),
+    security(("token" = [])),
+    tag = "habits"
+)]
+pub async fn create_checkin(
+    State(pool): State<SqlitePool>,
+    api_user: ApiUser,
+    Path(id): Path<u32>,
+    Json(new): Json<NewCheckin>,
+) -> Result<StatusCode, ApiError> {
+    use crate::habits::{checkin_day_error, ssr::{habit_created, today}};
+
+    api_user.require_write()?;
+    let created = habit_created(id, &api_user.user, &pool)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Habit"))?;
+
+    let today = today(&api_user.user);
+    let day = new.day.unwrap_or(today);
+    if let Some(error) = checkin_day_error(day, created, today) {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, error));
+    }
+    sqlx::query("INSERT OR IGNORE INTO habit_checkins (habit_id, day) VALUES (?, ?)")
+        .bind(id)
+        .bind(day)
+        .execute(&pool)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/habits/{id}/checkins/{day}",
+    params(
+        ("id" = u32, Path, description = "ID of the habit"),
+        ("day" = NaiveDate, Path, description = "Day of the check-in, like 2024-09-17")
+    ),
+    responses(
+        (status = 204),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    ),
+    security(("token" = [])),
+    tag = "habits"
+)]
+pub async fn delete_checkin(
+    State(pool): State<SqlitePool>,
+    api_user: ApiUser,
+    Path((id, day)): Path<(u32, NaiveDate)>,
+) -> Result<StatusCode, ApiError> {
+    api_user.require_write()?;
+    authorize_habit(id, &api_user.user, &pool).await?;
+
+    let removed = sqlx::query("DELETE FROM habit_checkins WHERE habit_id = ? AND day = ?")
+        .bind(id)
+        .bind(day)
+        .execute(&pool)
+        .await?
+        .rows_affected();
+
+    match removed {
+        0 => Err(ApiError::not_found("Check-in")),
+        _ => Ok(StatusCode::NO_CONTENT),
+    }
+}
+
+/// Adds the access token scheme the operations refer to.
+struct TokenAuth;
+
+impl Modify for TokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Kreqo Habits API", description = "Todos, projects and habits of the token owner."),
+    paths(
+        list_todos,
+        get_todo,
+        create_todo,
+        update_todo,
+        delete_todo,
+        list_projects,
+        create_project,
+        list_habits,
+        create_checkin,
+        delete_checkin
+    ),
+    components(schemas(
+        ApiTodo,
+        NewTodo,
+        TodoPatch,
+        Priority,
+        ApiProject,
+        NewProject,
+        ApiHabit,
+        HabitPeriod,
+        NewCheckin,
+        ErrorBody
+    )),
+    modifiers(&TokenAuth)
+)]
+pub struct ApiDoc;
+
+/// The OpenAPI document of the API, served without authentication.
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_known_server_fn_errors_reach_the_client() {
+        use crate::{habits::ssr::habit_not_found, todo::ssr::{missing_title, todo_not_found}};
+
+        let cases = [
+            (todo_not_found(), StatusCode::NOT_FOUND, "Todo not found."),
+            (habit_not_found(), StatusCode::NOT_FOUND, "Habit not found."),
+            (missing_title(), StatusCode::BAD_REQUEST, "A todo needs a title."),
+            (
+                ServerFnError::from(sqlx::Error::RowNotFound),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error.",
+            ),
+            (
+                ServerFnError::new("UNIQUE constraint failed: tags.user_id, tags.name"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error.",
+            ),
+        ];
+
+        for (error, status, message) in cases {
+            let error = ApiError::from(error);
+            assert_eq!((error.status, error.message.as_str()), (status, message));
+        }
+    }
+}
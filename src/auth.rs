@@ -1,3 +1,6 @@
+use crate::{settings::{DateFormat, Theme}, todo::TodoSort};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
 use leptos::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -7,8 +10,20 @@ pub struct User {
     pub id: i64,
     pub username: String,
     pub permissions: HashSet<String>,
+    /// IANA name of the zone dates are shown and entered in, e.g. "Europe/Paris".
+    pub timezone: String,
+    /// Last order picked for the todo list.
+    pub todo_sort: TodoSort,
+    /// Day weeks start on, for habits and the "this week" filter.
+    pub week_start: Weekday,
+    pub date_format: DateFormat,
+    pub theme: Theme,
 }
 
+/// The logged in user, loaded by the app and shared with the pages through the context.
+pub type UserResource =
+    Resource<(usize, usize, usize, usize), Result<Option<User>, ServerFnError>>;
+
 // Explicitly is not Serialize/Deserialize!
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UserPasshash(String);
@@ -21,13 +36,58 @@ impl Default for User {
             id: -1,
             username: "Guest".into(),
             permissions,
+            timezone: "UTC".into(),
+            todo_sort: TodoSort::default(),
+            week_start: Weekday::Mon,
+            date_format: DateFormat::default(),
+            theme: Theme::default(),
         }
     }
 }
 
+impl User {
+    /// The user's time zone, falling back to UTC if the stored name is unknown.
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    pub fn to_local(&self, datetime: DateTime<Utc>) -> NaiveDateTime {
+        datetime.with_timezone(&self.tz()).naive_local()
+    }
+
+    /// Converts a wall clock time in the user's zone to UTC.
+    /// Times skipped by a DST transition are moved forward by an hour.
+    pub fn from_local(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let tz = self.tz();
+
+        tz.from_local_datetime(&local)
+            .earliest()
+            .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+            .map(|datetime| datetime.with_timezone(&Utc))
+    }
+
+    pub fn local_today(&self) -> NaiveDate {
+        self.to_local(Utc::now()).date()
+    }
+
+    /// Shows a date in the format picked by the user.
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        date.format(self.date_format.pattern()).to_string()
+    }
+
+    /// Shows a point in time in the user's zone and date format.
+    pub fn format_datetime(&self, datetime: DateTime<Utc>) -> String {
+        let local = self.to_local(datetime);
+        format!("{} {}", self.format_date(local.date()), local.format("%H:%M"))
+    }
+}
+
 #[cfg(feature = "ssr")]
 pub mod ssr {
     pub use super::{User, UserPasshash};
+    use crate::{settings::{DateFormat, Theme}, throttle, todo::TodoSort};
+    use chrono::{DateTime, Duration, Utc, Weekday};
+    use leptos::ServerFnError;
     pub use axum_session_auth::{
         Authentication, HasPermission, SessionSqlitePool,
     };
@@ -108,6 +168,69 @@ pub mod ssr {
         }
     }
 
+    /// Hash of no password in particular, verified against when a user doesn't exist.
+    pub fn dummy_passhash() -> &'static str {
+        static DUMMY: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+        DUMMY.get_or_init(|| hash("not the password of anyone", DEFAULT_COST).unwrap())
+    }
+
+    /// Address of the client making the current request, if known.
+    pub async fn client_ip() -> Option<std::net::IpAddr> {
+        use axum::extract::ConnectInfo;
+        use std::net::SocketAddr;
+
+        leptos_axum::extract::<ConnectInfo<SocketAddr>>()
+            .await
+            .ok()
+            .map(|ConnectInfo(addr)| addr.ip())
+    }
+
+    /// Makes sure `password` is the one of `user`, before a sensitive change.
+    pub async fn verify_password(
+        user: &User,
+        password: &str,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        check_password(user, password, client_ip().await, Utc::now(), pool).await
+    }
+
+    /// Makes sure `password` is the one of `user`, coming from `ip`.
+    ///
+    /// Wrong passwords count towards the same lockout as failed logins, so a session can't be used
+    /// to guess the password either.
+    pub async fn check_password(
+        user: &User,
+        password: &str,
+        ip: Option<std::net::IpAddr>,
+        now: DateTime<Utc>,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        let keys = throttle::keys(&user.username, ip);
+        if let Some(wait) = throttle::locked_for(&keys, now, pool).await? {
+            return Err(too_many_attempts(wait));
+        }
+
+        let (_, UserPasshash(expected_passhash)) = User::get_with_passhash(user.id, pool)
+            .await
+            .ok_or_else(|| ServerFnError::new("User does not exist."))?;
+
+        match verify(password, &expected_passhash)? {
+            true => Ok(throttle::record_success(&user.username, pool).await?),
+            false => {
+                throttle::record_failure(&keys, &user.username, ip, now, pool).await?;
+                Err(ServerFnError::new("Password does not match."))
+            }
+        }
+    }
+
+    /// Error returned while failed attempts keep a user or address locked for `wait`.
+    pub fn too_many_attempts(wait: Duration) -> ServerFnError {
+        ServerFnError::new(format!(
+            "Too many failed attempts, try again in {} seconds.",
+            wait.num_seconds() + 1
+        ))
+    }
+
     #[derive(sqlx::FromRow, Clone)]
     pub struct SqlPermissionTokens {
         pub token: String,
@@ -151,6 +274,11 @@ pub mod ssr {
         pub id: i64,
         pub username: String,
         pub password: String,
+        pub timezone: String,
+        pub todo_sort: String,
+        pub week_start: u8,
+        pub date_format: String,
+        pub theme: String,
     }
 
     impl SqlUser {
@@ -170,6 +298,11 @@ pub mod ssr {
                     } else {
                         HashSet::<String>::new()
                     },
+                    timezone: self.timezone,
+                    todo_sort: TodoSort::parse(&self.todo_sort).unwrap_or_default(),
+                    week_start: Weekday::try_from(self.week_start).unwrap_or(Weekday::Mon),
+                    date_format: DateFormat::parse(&self.date_format).unwrap_or_default(),
+                    theme: Theme::parse(&self.theme).unwrap_or_default(),
                 },
                 UserPasshash(self.password),
             )
@@ -193,25 +326,46 @@ pub async fn login(
     remember: Option<String>,
 ) -> Result<(), ServerFnError> {
     use self::ssr::*;
+    use crate::{throttle, two_factor::ssr as two_factor};
 
     let pool = pool()?;
     let auth = auth()?;
+    let now = Utc::now();
 
-    let (user, UserPasshash(expected_passhash)) =
-        User::get_from_username_with_passhash(username, &pool)
-            .await
-            .ok_or_else(|| ServerFnError::new("User does not exist."))?;
+    let ip = client_ip().await;
+    let keys = throttle::keys(&username, ip);
+    if let Some(wait) = throttle::locked_for(&keys, now, &pool).await? {
+        return Err(too_many_attempts(wait));
+    }
 
-    match verify(password, &expected_passhash)? {
-        true => {
+    // Unknown users are checked against a dummy hash, so they take as long as wrong passwords
+    let found = User::get_from_username_with_passhash(username.clone(), &pool).await;
+    let matches = match &found {
+        Some((_, UserPasshash(passhash))) => verify(password, passhash)?,
+        None => {
+            verify(password, dummy_passhash())?;
+            false
+        }
+    };
+
+    match (found, matches) {
+        // The password is right, but the code is still needed before logging in
+        (Some((user, _)), true) if two_factor::load(user.id, &pool).await?.totp_secret.is_some() => {
+            two_factor::start_pending_login(user.id, user.username, remember.is_some(), now);
+            leptos_axum::redirect("/login/2fa");
+            Ok(())
+        }
+        (Some((user, _)), true) => {
+            throttle::record_success(&username, &pool).await?;
             auth.login_user(user.id);
             auth.remember_user(remember.is_some());
             leptos_axum::redirect("/");
             Ok(())
         }
-        false => Err(ServerFnError::ServerError(
-            "Password does not match.".to_string(),
-        )),
+        _ => {
+            throttle::record_failure(&keys, &username, ip, now, &pool).await?;
+            Err(ServerFnError::new("Invalid username or password."))
+        }
     }
 }
 
@@ -223,23 +377,34 @@ pub async fn signup(
     remember: Option<String>,
 ) -> Result<(), ServerFnError> {
     use self::ssr::*;
+    use crate::validation::{
+        ssr::{check_username_free, policy, username_taken},
+        FieldErrors,
+    };
 
     let pool = pool()?;
     let auth = auth()?;
 
+    let policy = policy();
+    let mut errors = FieldErrors::new();
+    policy.check_username(&username, &mut errors);
+    policy.check_password("password", &password, &username, &mut errors);
     if password != password_confirmation {
-        return Err(ServerFnError::ServerError(
-            "Passwords did not match.".to_string(),
-        ));
+        errors.add("password_confirmation", "Passwords did not match.");
     }
+    if errors.get("username").is_none() {
+        check_username_free(&username, None, &pool, &mut errors).await?;
+    }
+    errors.into_result()?;
 
-    let password_hashed = hash(password, DEFAULT_COST).unwrap();
+    let password_hashed = hash(password, DEFAULT_COST)?;
 
     sqlx::query("INSERT INTO users (username, password) VALUES (?,?)")
         .bind(username.clone())
         .bind(password_hashed)
         .execute(&pool)
-        .await?;
+        .await
+        .map_err(username_taken)?;
 
     let user =
         User::get_from_username(username, &pool)
@@ -267,3 +432,46 @@ pub async fn logout() -> Result<(), ServerFnError> {
 
     Ok(())
 }
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::{ssr::*, User};
+    use crate::{testing, throttle};
+    use chrono::{Duration, Utc};
+    use leptos::ServerFnError;
+    use std::net::IpAddr;
+
+    async fn with_password(password: &str, pool: &SqlitePool) -> User {
+        let user = testing::user("alice", pool).await;
+        sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+            .bind(hash(password, 4).unwrap())
+            .bind(user.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        user
+    }
+
+    #[tokio::test]
+    async fn wrong_passwords_count_towards_the_login_lockout() {
+        let pool = testing::pool().await;
+        let user = with_password("correct horse", &pool).await;
+        let ip = Some(IpAddr::from([192, 0, 2, 1]));
+        let now = Utc::now();
+
+        for _ in 0..throttle::FREE_FAILURES {
+            let error = check_password(&user, "wrong", ip, now, &pool).await.unwrap_err();
+            assert_eq!(error.to_string(), ServerFnError::new("Password does not match.").to_string());
+        }
+
+        // Even the right password waits, like logins do
+        let error = check_password(&user, "correct horse", ip, now, &pool).await.unwrap_err();
+        assert!(error.to_string().contains("Too many failed attempts"), "{error}");
+        let login_keys = throttle::keys("alice", Some(IpAddr::from([192, 0, 2, 2])));
+        assert!(throttle::locked_for(&login_keys, now, &pool).await.unwrap().is_some());
+
+        let later = now + Duration::hours(1);
+        check_password(&user, "correct horse", ip, later, &pool).await.unwrap();
+        assert_eq!(throttle::locked_for(&login_keys, later, &pool).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{projects::Project, tags::Tag, todo::{Priority, MAX_TODO_PAGE_SIZE}};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A change made to many todos at once, see [`bulk_edit_todos`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BulkEdit {
+    Complete,
+    Reopen,
+    /// Moves the todos to the trash.
+    Delete,
+    /// Adds the tag to the todos, or removes it when `tagged` is false.
+    Tag { tag_id: u32, tagged: bool },
+    SetPriority(Priority),
+    /// Moves the todos into a project, or out of any project.
+    SetProject(Option<u32>),
+}
+
+/// Most todos changed by a single bulk edit, a full page of the todo list.
+pub const MAX_BULK_TODOS: usize = MAX_TODO_PAGE_SIZE as usize;
+
+/// Applies `edit` to every todo in `ids`, in a single transaction.
+///
+/// Todos are changed one at a time so that each keeps track of the change in its history.
+///
+/// Either every todo is changed or none is, so a single todo which is not found
+/// fails the whole edit.
+#[server(name = BulkEditTodos, prefix = "/api", input = server_fn::codec::Json)]
+pub async fn bulk_edit_todos(ids: Vec<u32>, edit: BulkEdit) -> Result<(), ServerFnError> {
+    use crate::{
+        projects::ssr::authorize_project,
+        tags::ssr::{authorize_tag, json_ids, set_tagged},
+        todo::ssr::{pool, require_user, set_completed, set_priority, set_project, todo_not_found},
+        trash::ssr::trash_todo,
+    };
+    use chrono::Utc;
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let mut ids = ids;
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    if ids.len() > MAX_BULK_TODOS {
+        return Err(ServerFnError::new(format!(
+            "At most {MAX_BULK_TODOS} todos can be changed at once."
+        )));
+    }
+    match edit {
+        BulkEdit::Tag { tag_id, .. } => authorize_tag(tag_id, &user, &pool).await?,
+        BulkEdit::SetProject(Some(project_id)) => {
+            authorize_project(project_id, &user, &pool).await?
+        }
+        _ => {}
+    }
+
+    let mut tx = pool.begin().await?;
+    let json_ids = json_ids(ids.iter().copied());
+
+    let owned = sqlx::query_scalar::<_, u32>(
+        "SELECT COUNT(*) FROM todos WHERE user_id = ? AND deleted_at IS NULL
+        AND id IN (SELECT value FROM json_each(?))",
+    )
+    .bind(user.id)
+    .bind(&json_ids)
+    .fetch_one(&mut *tx)
+    .await?;
+    if owned as usize != ids.len() {
+        return Err(todo_not_found());
+    }
+
+    match edit {
+        BulkEdit::Complete | BulkEdit::Reopen => {
+            for id in ids {
+                set_completed(&mut tx, &user, id, edit == BulkEdit::Complete).await?;
+            }
+        }
+        BulkEdit::Delete => {
+            let now = Utc::now();
+            for id in ids {
+                trash_todo(&mut tx, &user, id, now).await?;
+            }
+        }
+        BulkEdit::Tag { tag_id, tagged } => {
+            for id in ids {
+                set_tagged(&mut tx, &user, id, tag_id, tagged).await?;
+            }
+        }
+        BulkEdit::SetPriority(priority) => {
+            for id in ids {
+                set_priority(&mut tx, &user, id, priority).await?;
+            }
+        }
+        BulkEdit::SetProject(project_id) => {
+            for id in ids {
+                set_project(&mut tx, &user, id, project_id).await?;
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Bar of the actions applying to the selected todos, with a checkbox selecting all of `ids`.
+#[component]
+pub fn BulkBar(
+    /// Todos shown in the list.
+    #[prop(into)]
+    ids: Signal<Vec<u32>>,
+    selected: RwSignal<HashSet<u32>>,
+    bulk_edit: Action<BulkEditTodos, Result<(), ServerFnError>>,
+    #[prop(into)] projects: Signal<Vec<Project>>,
+    #[prop(into)] tags: Signal<Vec<Tag>>,
+) -> impl IntoView {
+    let count = move || selected.with(HashSet::len);
+    let all_selected = move || {
+        let ids = ids.get();
+        !ids.is_empty() && selected.with(|selected| ids.iter().all(|id| selected.contains(id)))
+    };
+    let apply = move |edit: BulkEdit| {
+        let ids: Vec<u32> = selected.with_untracked(|selected| selected.iter().copied().collect());
+        if !ids.is_empty() {
+            bulk_edit.dispatch(BulkEditTodos { ids, edit });
+        }
+    };
+    // Selects act as menus, going back to their placeholder once something is picked
+    let pick = move |ev: ev::Event| {
+        let value = event_target_value(&ev);
+        event_target::<web_sys::HtmlSelectElement>(&ev).set_value("");
+        value
+    };
+    let error = move || match bulk_edit.value().get() {
+        Some(Err(e)) => Some(view! { <span class="text-error">{e.to_string()}</span> }),
+        _ => None,
+    };
+
+    view! {
+        <div class="flex flex-wrap items-center gap-2 mb-4 p-2 bg-base-100 rounded-xl">
+            <label class="label cursor-pointer gap-2">
+                <input
+                    type="checkbox"
+                    class="checkbox checkbox-accent"
+                    prop:checked=all_selected
+                    on:change=move |ev| {
+                        let ids = ids.get_untracked();
+                        selected
+                            .update(|selected| {
+                                if event_target_checked(&ev) {
+                                    selected.extend(ids);
+                                } else {
+                                    selected.clear();
+                                }
+                            });
+                    }
+                />
+
+                <span class="label-text text-lg">{move || format!("{} selected", count())}</span>
+            </label>
+            <button class="btn btn-sm btn-ghost" on:click=move |_| apply(BulkEdit::Complete)>
+                "Complete"
+            </button>
+            <button class="btn btn-sm btn-ghost" on:click=move |_| apply(BulkEdit::Reopen)>
+                "Reopen"
+            </button>
+            <select
+                class="select select-sm select-bordered"
+                on:change=move |ev| {
+                    if let Some(priority) = Priority::parse(&pick(ev)) {
+                        apply(BulkEdit::SetPriority(priority));
+                    }
+                }
+            >
+                <option value="" selected>
+                    "Set priority"
+                </option>
+                {Priority::ALL
+                    .into_iter()
+                    .map(|priority| view! { <option value=priority.as_str()>{priority.label()}</option> })
+                    .collect_view()}
+            </select>
+            <select
+                class="select select-sm select-bordered"
+                on:change=move |ev| {
+                    if let Ok(tag_id) = pick(ev).parse() {
+                        apply(BulkEdit::Tag { tag_id, tagged: true });
+                    }
+                }
+            >
+                <option value="" selected>
+                    "Add tag"
+                </option>
+                {move || {
+                    tags.get()
+                        .into_iter()
+                        .map(|tag| view! { <option value=tag.id>{tag.name}</option> })
+                        .collect_view()
+                }}
+            </select>
+            <select
+                class="select select-sm select-bordered"
+                on:change=move |ev| {
+                    if let Ok(tag_id) = pick(ev).parse() {
+                        apply(BulkEdit::Tag { tag_id, tagged: false });
+                    }
+                }
+            >
+                <option value="" selected>
+                    "Remove tag"
+                </option>
+                {move || {
+                    tags.get()
+                        .into_iter()
+                        .map(|tag| view! { <option value=tag.id>{tag.name}</option> })
+                        .collect_view()
+                }}
+            </select>
+            <select
+                class="select select-sm select-bordered"
+                on:change=move |ev| {
+                    match pick(ev).as_str() {
+                        "" => {}
+                        "none" => apply(BulkEdit::SetProject(None)),
+                        project_id => {
+                            if let Ok(project_id) = project_id.parse() {
+                                apply(BulkEdit::SetProject(Some(project_id)));
+                            }
+                        }
+                    }
+                }
+            >
+                <option value="" selected>
+                    "Move to"
+                </option>
+                <option value="none">"No project"</option>
+                {move || {
+                    projects
+                        .get()
+                        .into_iter()
+                        .filter(|project| !project.archived)
+                        .map(|project| view! { <option value=project.id>{project.name}</option> })
+                        .collect_view()
+                }}
+            </select>
+            <button
+                class="btn btn-sm btn-ghost text-error"
+                on:click=move |_| apply(BulkEdit::Delete)
+            >
+                "Delete"
+            </button>
+            {error}
+        </div>
+    }
+}
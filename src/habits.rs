@@ -0,0 +1,663 @@
+use crate::{error_template::ErrorTemplate, ui::{ActionIcon, Container}};
+use chrono::{Datelike, Days, Duration, NaiveDate, Weekday};
+use icondata as i;
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub enum HabitPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl HabitPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HabitPeriod::Day => "day",
+            HabitPeriod::Week => "week",
+            HabitPeriod::Month => "month",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "day" => Some(HabitPeriod::Day),
+            "week" => Some(HabitPeriod::Week),
+            "month" => Some(HabitPeriod::Month),
+            _ => None,
+        }
+    }
+
+    /// First day of the period containing `date`, weeks starting on `week_start`.
+    /// Weeks starting before the earliest date start on that date.
+    pub fn start_of(&self, date: NaiveDate, week_start: Weekday) -> NaiveDate {
+        match self {
+            HabitPeriod::Day => date,
+            HabitPeriod::Week => {
+                let into_week = date.weekday().days_since(week_start);
+                date.checked_sub_days(Days::new(into_week.into()))
+                    .unwrap_or(NaiveDate::MIN)
+            }
+            HabitPeriod::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// First day of the period following the one starting at `start`, unless it is past the
+    /// latest date.
+    pub fn next(&self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            HabitPeriod::Day => start.succ_opt(),
+            HabitPeriod::Week => start.checked_add_days(Days::new(7)),
+            HabitPeriod::Month => start.checked_add_months(chrono::Months::new(1)),
+        }
+    }
+}
+
+/// How often a habit should be done, e.g. 3 times per week.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HabitTarget {
+    pub count: u32,
+    pub period: HabitPeriod,
+}
+
+impl HabitTarget {
+    pub fn describe(&self) -> String {
+        match self.count {
+            1 => format!("Once per {}", self.period.as_str()),
+            n => format!("{n} times per {}", self.period.as_str()),
+        }
+    }
+}
+
+/// How long before its creation a habit can be checked in, for habits kept before being tracked.
+pub const MAX_BACKFILL: Duration = Duration::days(366);
+
+/// Why `day` can't be checked in on for a habit created on `created`, if it can't.
+pub fn checkin_day_error(day: NaiveDate, created: NaiveDate, today: NaiveDate) -> Option<&'static str> {
+    if day > today {
+        Some("Cannot check in on a future day.")
+    } else if day < earliest_checkin(created) {
+        Some("Cannot check in more than a year before the habit was created.")
+    } else {
+        None
+    }
+}
+
+/// Earliest day a habit created on `created` can be checked in on.
+fn earliest_checkin(created: NaiveDate) -> NaiveDate {
+    created.checked_sub_signed(MAX_BACKFILL).unwrap_or(NaiveDate::MIN)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HabitStats {
+    /// Consecutive periods meeting the target, up to the current one.
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Share of finished periods meeting the target, from 0 to 1.
+    pub completion_rate: f32,
+}
+
+impl HabitStats {
+    /// Computes the stats of a habit from its check-in days.
+    ///
+    /// The current period only counts once its target is met, so an unfinished day or week
+    /// neither breaks the streak nor lowers the completion rate.
+    /// Periods are counted from the creation day, or from the first check-in if it was
+    /// backdated before that, up to [`MAX_BACKFILL`] earlier.
+    pub fn compute(
+        target: HabitTarget,
+        created: NaiveDate,
+        checkins: &[NaiveDate],
+        today: NaiveDate,
+        week_start: Weekday,
+    ) -> Self {
+        let period = target.period;
+        let mut counts = BTreeMap::<NaiveDate, u32>::new();
+        for day in checkins {
+            *counts.entry(period.start_of(*day, week_start)).or_default() += 1;
+        }
+
+        let earliest = checkins
+            .iter()
+            .copied()
+            .filter(|day| *day >= earliest_checkin(created))
+            .fold(created.min(today), NaiveDate::min);
+        let first = period.start_of(earliest, week_start);
+        let current = period.start_of(today, week_start);
+        let mut met = Vec::new();
+        let mut start = Some(first);
+        while let Some(period_start) = start.filter(|start| *start <= current) {
+            met.push(counts.get(&period_start).copied().unwrap_or(0) >= target.count.max(1));
+            start = period.next(period_start);
+        }
+        if met.last() == Some(&false) {
+            met.pop();
+        }
+
+        let mut longest_streak = 0;
+        let mut run = 0;
+        for met in &met {
+            run = if *met { run + 1 } else { 0 };
+            longest_streak = longest_streak.max(run);
+        }
+        let current_streak = met.iter().rev().take_while(|met| **met).count() as u32;
+        let completion_rate = match met.len() {
+            0 => 0.0,
+            len => met.iter().filter(|met| **met).count() as f32 / len as f32,
+        };
+
+        Self {
+            current_streak,
+            longest_streak,
+            completion_rate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Habit {
+    pub id: u32,
+    pub name: String,
+    pub target: HabitTarget,
+    pub created_at: NaiveDate,
+    pub checked_today: bool,
+    pub stats: HabitStats,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{Habit, HabitPeriod, HabitStats, HabitTarget};
+    use crate::auth::User;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use leptos::ServerFnError;
+    use sqlx::SqlitePool;
+    use std::collections::HashMap;
+
+    /// Error returned when a habit does not exist or belongs to another user.
+    pub fn habit_not_found() -> ServerFnError {
+        ServerFnError::new("Habit not found.")
+    }
+
+    /// The date check-ins are recorded for when none is given, in the user's time zone.
+    pub fn today(user: &User) -> NaiveDate {
+        user.local_today()
+    }
+
+    /// The day a habit of `user` was created on in their time zone, if it is theirs.
+    pub async fn habit_created(
+        id: u32,
+        user: &User,
+        pool: &SqlitePool,
+    ) -> Result<Option<NaiveDate>, sqlx::Error> {
+        Ok(sqlx::query_scalar::<_, NaiveDateTime>(
+            "SELECT created_at FROM habits WHERE id = ? AND user_id = ?",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(pool)
+        .await?
+        .map(|created_at| user.to_local(created_at.and_utc()).date()))
+    }
+
+    /// Loads every habit of `user` along with its stats, oldest first.
+    pub async fn load_habits(user: &User, pool: &SqlitePool) -> Result<Vec<Habit>, sqlx::Error> {
+        let today = today(user);
+
+        let habits = sqlx::query_as::<_, SqlHabit>(
+            "SELECT * FROM habits WHERE user_id = ? ORDER BY created_at",
+        )
+        .bind(user.id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut checkins = HashMap::<u32, Vec<NaiveDate>>::new();
+        for checkin in sqlx::query_as::<_, SqlCheckin>(
+            "SELECT c.habit_id, c.day FROM habit_checkins c
+            JOIN habits h ON h.id = c.habit_id
+            WHERE h.user_id = ?",
+        )
+        .bind(user.id)
+        .fetch_all(pool)
+        .await?
+        {
+            checkins.entry(checkin.habit_id).or_default().push(checkin.day);
+        }
+
+        Ok(habits
+            .into_iter()
+            .map(|habit| {
+                let days = checkins.remove(&habit.id).unwrap_or_default();
+                habit.into_habit(&days, today, user)
+            })
+            .collect())
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlHabit {
+        pub id: u32,
+        pub user_id: i64,
+        pub name: String,
+        pub target_count: u32,
+        pub target_period: String,
+        pub created_at: NaiveDateTime,
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlCheckin {
+        pub habit_id: u32,
+        pub day: NaiveDate,
+    }
+
+    impl SqlHabit {
+        /// Builds the habit of `owner` from its row, days being in their time zone.
+        pub fn into_habit(self, checkins: &[NaiveDate], today: NaiveDate, owner: &User) -> Habit {
+            let target = HabitTarget {
+                count: self.target_count,
+                period: HabitPeriod::parse(&self.target_period)
+                    .unwrap_or(HabitPeriod::Day),
+            };
+            let created_at = owner.to_local(self.created_at.and_utc()).date();
+
+            Habit {
+                id: self.id,
+                name: self.name,
+                target,
+                created_at,
+                checked_today: checkins.contains(&today),
+                stats: HabitStats::compute(target, created_at, checkins, today, owner.week_start),
+            }
+        }
+    }
+}
+
+#[server(GetHabits, "/api")]
+pub async fn get_habits() -> Result<Vec<Habit>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(load_habits(&user, &pool).await?)
+}
+
+#[server(AddHabit, "/api")]
+pub async fn add_habit(
+    name: String,
+    target_count: u32,
+    target_period: String,
+) -> Result<(), ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let period = HabitPeriod::parse(&target_period)
+        .ok_or_else(|| ServerFnError::new("Invalid habit period."))?;
+    if name.trim().is_empty() || target_count == 0 {
+        return Err(ServerFnError::new(
+            "A habit needs a name and a target of at least once.",
+        ));
+    }
+
+    Ok(sqlx::query(
+        "INSERT INTO habits (user_id, name, target_count, target_period) VALUES (?, ?, ?, ?)",
+    )
+    .bind(user.id)
+    .bind(name.trim())
+    .bind(target_count)
+    .bind(period.as_str())
+    .execute(&pool)
+    .await
+    .map(|_| ())?)
+}
+
+#[server(DeleteHabit, "/api")]
+pub async fn delete_habit(id: u32) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let result = sqlx::query("DELETE FROM habits WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    match result.rows_affected() {
+        0 => Err(habit_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the habit in for `day` (today by default), or removes the check-in if there already is one.
+/// Returns whether the habit is now checked in.
+#[server(ToggleCheckin, "/api")]
+pub async fn toggle_checkin(
+    habit_id: u32,
+    day: Option<NaiveDate>,
+) -> Result<bool, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let today = today(&user);
+    let day = day.unwrap_or(today);
+
+    let created = habit_created(habit_id, &user, &pool)
+        .await?
+        .ok_or_else(habit_not_found)?;
+    if let Some(error) = checkin_day_error(day, created, today) {
+        return Err(ServerFnError::new(error));
+    }
+
+    let removed = sqlx::query("DELETE FROM habit_checkins WHERE habit_id = ? AND day = ?")
+        .bind(habit_id)
+        .bind(day)
+        .execute(&pool)
+        .await?
+        .rows_affected();
+
+    if removed > 0 {
+        return Ok(false);
+    }
+
+    sqlx::query("INSERT INTO habit_checkins (habit_id, day) VALUES (?, ?)")
+        .bind(habit_id)
+        .bind(day)
+        .execute(&pool)
+        .await?;
+
+    Ok(true)
+}
+
+#[component]
+pub fn Habits() -> impl IntoView {
+    let add_habit = create_server_action::<AddHabit>();
+    let delete_habit = create_server_action::<DeleteHabit>();
+    let toggle_checkin = create_server_action::<ToggleCheckin>();
+
+    let habits = create_resource(
+        move || {
+            (
+                add_habit.version().get(),
+                delete_habit.version().get(),
+                toggle_checkin.version().get(),
+            )
+        },
+        move |_| get_habits(),
+    );
+
+    view! {
+        <Container>
+            <ActionForm action=add_habit class="flex items-center gap-4 mb-4">
+                <label class="input input-bordered flex items-center flex-1 text-xl gap-4">
+                    <span class="text-primary">"Habit"</span>
+                    <input type="text" name="name"/>
+                </label>
+                <input
+                    type="number"
+                    name="target_count"
+                    min="1"
+                    value="1"
+                    class="input input-bordered w-24 text-xl"
+                />
+                <span class="text-xl">"times per"</span>
+                <select name="target_period" class="select select-bordered text-xl">
+                    <option value="day">"Day"</option>
+                    <option value="week">"Week"</option>
+                    <option value="month">"Month"</option>
+                </select>
+                <button type="submit" class="btn btn-primary text-lg">
+                    "Add Habit"
+                </button>
+            </ActionForm>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        habits
+                            .get()
+                            .map(move |habits| match habits {
+                                Err(e) => {
+                                    view! {
+                                        <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(habits) => {
+                                    if habits.is_empty() {
+                                        view! { <p>"No habits were found."</p> }.into_view()
+                                    } else {
+                                        view! {
+                                            <ul class="overflow-auto space-y-2">
+                                                {habits
+                                                    .into_iter()
+                                                    .map(move |habit| {
+                                                        view! {
+                                                            <li>
+                                                                <Habit habit toggle_checkin delete_habit/>
+                                                            </li>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </ul>
+                                        }
+                                            .into_view()
+                                    }
+                                }
+                            })
+                            .unwrap_or_default()
+                    }}
+
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+#[component]
+pub fn Habit(
+    habit: Habit,
+    toggle_checkin: Action<ToggleCheckin, Result<bool, ServerFnError>>,
+    delete_habit: Action<DeleteHabit, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let check_class = if habit.checked_today {
+        "btn-accent rounded-xl"
+    } else {
+        "btn-ghost bg-base-100 text-accent rounded-xl"
+    };
+
+    view! {
+        <div class="flex gap-2">
+            <ActionIcon action=toggle_checkin icon=i::LuCheck class=check_class>
+                <input type="hidden" name="habit_id" value=habit.id/>
+            </ActionIcon>
+            <div class="h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
+                <span class="text-xl">{habit.name}</span>
+                <span class="badge badge-secondary badge-outline">
+                    {habit.target.describe()}
+                </span>
+                <span class="flex-1 text-right">
+                    "Streak " <span class="text-primary">{habit.stats.current_streak}</span>
+                    " (best " <span class="text-primary">{habit.stats.longest_streak}</span> ") · "
+                    <span class="text-primary">
+                        {format!("{:.0}%", habit.stats.completion_rate * 100.0)}
+                    </span> " done since "
+                    <span class="text-primary">{habit.created_at.to_string()}</span>
+                </span>
+            </div>
+            <ActionIcon
+                action=delete_habit
+                icon=i::LuTrash2
+                class="btn-ghost bg-base-100 text-error rounded-xl"
+            >
+                <input type="hidden" name="id" value=habit.id/>
+            </ActionIcon>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn days(dates: &[&str]) -> Vec<NaiveDate> {
+        dates.iter().map(|date| day(date)).collect()
+    }
+
+    fn daily() -> HabitTarget {
+        HabitTarget {
+            count: 1,
+            period: HabitPeriod::Day,
+        }
+    }
+
+    fn stats(target: HabitTarget, created: &str, checkins: &[&str], today: &str) -> (u32, u32) {
+        let stats =
+            HabitStats::compute(target, day(created), &days(checkins), day(today), Weekday::Mon);
+        (stats.current_streak, stats.longest_streak)
+    }
+
+    #[test]
+    fn counts_streaks_across_gaps() {
+        let cases = [
+            // Created, check-ins, today, (current, longest)
+            ("2024-05-01", &["2024-05-01", "2024-05-02", "2024-05-03"][..], "2024-05-03", (3, 3)),
+            ("2024-05-01", &["2024-05-01", "2024-05-02", "2024-05-04"][..], "2024-05-04", (1, 2)),
+            ("2024-05-01", &["2024-05-01", "2024-05-02"][..], "2024-05-05", (0, 2)),
+            ("2024-05-01", &[][..], "2024-05-05", (0, 0)),
+        ];
+
+        for (created, checkins, today, expected) in cases {
+            assert_eq!(stats(daily(), created, checkins, today), expected, "{checkins:?}");
+        }
+    }
+
+    #[test]
+    fn today_only_counts_once_done() {
+        let checkins = ["2024-05-01", "2024-05-02"];
+
+        // Not checked in yet today, yesterday keeps the streak going
+        assert_eq!(stats(daily(), "2024-05-01", &checkins, "2024-05-03"), (2, 2));
+        // Checking in today extends it
+        let checkins = ["2024-05-01", "2024-05-02", "2024-05-03"];
+        assert_eq!(stats(daily(), "2024-05-01", &checkins, "2024-05-03"), (3, 3));
+        // Missing yesterday breaks it
+        assert_eq!(stats(daily(), "2024-05-01", &checkins[..2], "2024-05-04"), (0, 2));
+    }
+
+    #[test]
+    fn counts_weekly_targets_per_week() {
+        let target = HabitTarget {
+            count: 2,
+            period: HabitPeriod::Week,
+        };
+        // Weeks of 2024-04-29 and 2024-05-06 are met, the current one isn't yet
+        let checkins = ["2024-04-29", "2024-05-03", "2024-05-06", "2024-05-12", "2024-05-13"];
+
+        let stats =
+            HabitStats::compute(target, day("2024-04-29"), &days(&checkins), day("2024-05-14"), Weekday::Mon);
+        assert_eq!((stats.current_streak, stats.longest_streak), (2, 2));
+        assert_eq!(stats.completion_rate, 1.0);
+
+        // Weeks starting on Sunday split the check-ins differently, missing the one of 2024-05-05
+        let stats =
+            HabitStats::compute(target, day("2024-04-29"), &days(&checkins), day("2024-05-14"), Weekday::Sun);
+        assert_eq!((stats.current_streak, stats.longest_streak), (1, 1));
+        assert_eq!(stats.completion_rate, 2.0 / 3.0);
+    }
+
+    #[test]
+    fn keeps_check_ins_backdated_before_creation() {
+        let checkins = ["2024-04-28", "2024-04-29", "2024-04-30", "2024-05-01"];
+
+        assert_eq!(stats(daily(), "2024-05-01", &checkins, "2024-05-01"), (4, 4));
+    }
+
+    #[test]
+    fn bounds_check_in_days() {
+        let (created, today) = (day("2024-05-01"), day("2024-05-10"));
+        let cases = [
+            (today, true),
+            (day("2024-05-11"), false),
+            (NaiveDate::MAX, false),
+            (created - MAX_BACKFILL, true),
+            (created - MAX_BACKFILL - Duration::days(1), false),
+            (NaiveDate::MIN, false),
+        ];
+
+        for (checkin, allowed) in cases {
+            assert_eq!(checkin_day_error(checkin, created, today).is_none(), allowed, "{checkin}");
+        }
+    }
+
+    #[test]
+    fn ignores_check_ins_too_far_back() {
+        for period in [HabitPeriod::Day, HabitPeriod::Week, HabitPeriod::Month] {
+            let target = HabitTarget { count: 1, period };
+            let created = day("2024-05-01");
+            let today = day("2024-05-10");
+            let expected = HabitStats::compute(target, created, &[today], today, Weekday::Sun);
+
+            let checkins = [NaiveDate::MIN, today];
+            let stats = HabitStats::compute(target, created, &checkins, today, Weekday::Sun);
+            assert_eq!(stats, expected, "{period:?}");
+        }
+    }
+
+    #[test]
+    fn periods_stop_at_the_date_range_limits() {
+        for period in [HabitPeriod::Day, HabitPeriod::Week, HabitPeriod::Month] {
+            for week_start in [Weekday::Mon, Weekday::Sun] {
+                assert!(period.start_of(NaiveDate::MIN, week_start) >= NaiveDate::MIN);
+                let last = period.start_of(NaiveDate::MAX, week_start);
+                assert_eq!(period.next(last), None, "{period:?}");
+            }
+        }
+        assert_eq!(HabitPeriod::Week.next(day("2024-05-06")), Some(day("2024-05-13")));
+        assert_eq!(HabitPeriod::Month.next(day("2024-01-01")), Some(day("2024-02-01")));
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn creation_day_is_in_the_time_zone_of_the_user() {
+        use crate::auth::User;
+
+        // Created on the evening of 2024-05-01 in New York, already 2024-05-02 in UTC
+        let habit = || ssr::SqlHabit {
+            id: 1,
+            user_id: 1,
+            name: "Stretch".into(),
+            target_count: 1,
+            target_period: "day".into(),
+            created_at: day("2024-05-02").and_hms_opt(1, 0, 0).unwrap(),
+        };
+        let new_york = User {
+            timezone: "America/New_York".into(),
+            ..User::default()
+        };
+        let checkins = days(&["2024-05-01", "2024-05-02"]);
+
+        let habit_in_new_york = habit().into_habit(&checkins, day("2024-05-02"), &new_york);
+        assert_eq!(habit_in_new_york.created_at, day("2024-05-01"));
+        assert!(habit_in_new_york.checked_today);
+        assert_eq!(habit_in_new_york.stats.current_streak, 2);
+        assert_eq!(habit_in_new_york.stats.completion_rate, 1.0);
+
+        let habit_in_utc = habit().into_habit(&[], day("2024-05-02"), &User::default());
+        assert_eq!(habit_in_utc.created_at, day("2024-05-02"));
+    }
+}
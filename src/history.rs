@@ -0,0 +1,321 @@
+use crate::auth::User;
+use chrono::{DateTime, Utc};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TodoEventKind {
+    Created,
+    /// A field changed, see [`TodoEvent::field`].
+    Edited,
+    Completed,
+    Reopened,
+    /// Moved into, out of or between projects.
+    Moved,
+    /// Moved to the trash.
+    Deleted,
+    /// Taken out of the trash.
+    Restored,
+}
+
+impl TodoEventKind {
+    pub const ALL: [TodoEventKind; 7] = [
+        TodoEventKind::Created,
+        TodoEventKind::Edited,
+        TodoEventKind::Completed,
+        TodoEventKind::Reopened,
+        TodoEventKind::Moved,
+        TodoEventKind::Deleted,
+        TodoEventKind::Restored,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoEventKind::Created => "created",
+            TodoEventKind::Edited => "edited",
+            TodoEventKind::Completed => "completed",
+            TodoEventKind::Reopened => "reopened",
+            TodoEventKind::Moved => "moved",
+            TodoEventKind::Deleted => "deleted",
+            TodoEventKind::Restored => "restored",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        TodoEventKind::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+}
+
+/// Something that happened to a todo, see [`get_todo_history`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoEvent {
+    pub id: u32,
+    pub kind: TodoEventKind,
+    /// Name of the field which changed, like "title" or "tag".
+    pub field: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Who made the change.
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TodoEvent {
+    /// What happened, dates being shown in the time zone of `user`.
+    pub fn describe(&self, user: &User) -> String {
+        // Dates are recorded in UTC
+        let show = |value: &String| match DateTime::parse_from_rfc3339(value) {
+            Ok(datetime) => user.format_datetime(datetime.with_timezone(&Utc)),
+            Err(_) => format!("\"{value}\""),
+        };
+        let field = self.field.as_deref().unwrap_or("todo");
+
+        match (self.kind, self.from.as_ref(), self.to.as_ref()) {
+            (TodoEventKind::Created, ..) => "Created".to_string(),
+            (TodoEventKind::Completed, ..) => "Completed".to_string(),
+            (TodoEventKind::Reopened, ..) => "Reopened".to_string(),
+            (TodoEventKind::Deleted, ..) => "Moved to the trash".to_string(),
+            (TodoEventKind::Restored, ..) => "Restored from the trash".to_string(),
+            (TodoEventKind::Moved, Some(from), Some(to)) => {
+                format!("Moved from {} to {}", show(from), show(to))
+            }
+            (TodoEventKind::Moved, None, Some(to)) => format!("Moved to {}", show(to)),
+            (TodoEventKind::Moved, Some(from), None) => format!("Moved out of {}", show(from)),
+            (TodoEventKind::Moved, None, None) => "Moved".to_string(),
+            (TodoEventKind::Edited, Some(from), Some(to)) => {
+                format!("Changed {field} from {} to {}", show(from), show(to))
+            }
+            (TodoEventKind::Edited, None, Some(to)) => format!("Added {field} {}", show(to)),
+            (TodoEventKind::Edited, Some(from), None) => format!("Removed {field} {}", show(from)),
+            (TodoEventKind::Edited, None, None) => format!("Edited {field}"),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{TodoEvent, TodoEventKind};
+    use crate::todo::Priority;
+    use chrono::{DateTime, Utc};
+    use sqlx::SqliteConnection;
+
+    /// Appends an event without details to the history of `todo_id`, made by `user_id`.
+    pub async fn record(
+        conn: &mut SqliteConnection,
+        todo_id: u32,
+        user_id: i64,
+        kind: TodoEventKind,
+    ) -> Result<(), sqlx::Error> {
+        record_change(conn, todo_id, user_id, kind, None, None, None).await
+    }
+
+    /// Appends a change of `field` from `from` to `to` to the history of `todo_id`,
+    /// unless the value did not actually change.
+    pub async fn record_edit(
+        conn: &mut SqliteConnection,
+        todo_id: u32,
+        user_id: i64,
+        field: &str,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> Result<(), sqlx::Error> {
+        if from == to {
+            return Ok(());
+        }
+        record_change(conn, todo_id, user_id, TodoEventKind::Edited, Some(field), from, to).await
+    }
+
+    /// Appends a change of priority to the history of `todo_id`, recorded by label.
+    pub async fn record_priority(
+        conn: &mut SqliteConnection,
+        todo_id: u32,
+        user_id: i64,
+        from: Priority,
+        to: Priority,
+    ) -> Result<(), sqlx::Error> {
+        let label = |priority: Priority| Some(priority.label().to_string());
+        record_edit(conn, todo_id, user_id, "priority", label(from), label(to)).await
+    }
+
+    /// Appends a change of `field` to the history of `todo_id`, without its values.
+    pub async fn record_edited(
+        conn: &mut SqliteConnection,
+        todo_id: u32,
+        user_id: i64,
+        field: &str,
+    ) -> Result<(), sqlx::Error> {
+        record_change(conn, todo_id, user_id, TodoEventKind::Edited, Some(field), None, None).await
+    }
+
+    /// Appends a move of `todo_id` between the projects `from` and `to`, recorded by name.
+    pub async fn record_move(
+        conn: &mut SqliteConnection,
+        todo_id: u32,
+        user_id: i64,
+        from: Option<u32>,
+        to: Option<u32>,
+    ) -> Result<(), sqlx::Error> {
+        if from == to {
+            return Ok(());
+        }
+        let from = project_name(conn, from).await?;
+        let to = project_name(conn, to).await?;
+        record_change(conn, todo_id, user_id, TodoEventKind::Moved, Some("project"), from, to).await
+    }
+
+    async fn project_name(
+        conn: &mut SqliteConnection,
+        id: Option<u32>,
+    ) -> Result<Option<String>, sqlx::Error> {
+        match id {
+            Some(id) => {
+                sqlx::query_scalar::<_, String>("SELECT name FROM projects WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(conn)
+                    .await
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn record_change(
+        conn: &mut SqliteConnection,
+        todo_id: u32,
+        user_id: i64,
+        kind: TodoEventKind,
+        field: Option<&str>,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO todo_events (todo_id, user_id, kind, field, old_value, new_value, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(todo_id)
+        .bind(user_id)
+        .bind(kind.as_str())
+        .bind(field)
+        .bind(from)
+        .bind(to)
+        .bind(Utc::now().naive_utc())
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// How dates are recorded in events, read back by [`TodoEvent::describe`].
+    pub fn date_value(datetime: Option<DateTime<Utc>>) -> Option<String> {
+        datetime.map(|datetime| datetime.to_rfc3339())
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlTodoEvent {
+        pub id: u32,
+        pub kind: String,
+        pub field: Option<String>,
+        pub old_value: Option<String>,
+        pub new_value: Option<String>,
+        pub username: String,
+        pub created_at: DateTime<Utc>,
+    }
+
+    impl SqlTodoEvent {
+        pub fn into_todo_event(self) -> Option<TodoEvent> {
+            Some(TodoEvent {
+                id: self.id,
+                kind: TodoEventKind::parse(&self.kind)?,
+                field: self.field,
+                from: self.old_value,
+                to: self.new_value,
+                username: self.username,
+                created_at: self.created_at,
+            })
+        }
+    }
+}
+
+/// The history of a todo of the current user, oldest first.
+#[server(GetTodoHistory, "/api")]
+pub async fn get_todo_history(todo_id: u32) -> Result<Vec<TodoEvent>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user, todo_not_found};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    // Todos in the trash keep their history
+    sqlx::query("SELECT id FROM todos WHERE id = ? AND user_id = ?")
+        .bind(todo_id)
+        .bind(user.id)
+        .fetch_optional(&pool)
+        .await?
+        .ok_or_else(todo_not_found)?;
+
+    Ok(sqlx::query_as::<_, SqlTodoEvent>(
+        "SELECT e.id, e.kind, e.field, e.old_value, e.new_value, u.username, e.created_at
+        FROM todo_events e
+        JOIN users u ON u.id = e.user_id
+        WHERE e.todo_id = ?
+        ORDER BY e.id",
+    )
+    .bind(todo_id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .filter_map(SqlTodoEvent::into_todo_event)
+    .collect())
+}
+
+/// Timeline of the changes made to a todo, shown in its detail panel.
+#[component]
+pub fn TodoHistory(todo_id: u32, user: User) -> impl IntoView {
+    let history = create_resource(|| (), move |_| get_todo_history(todo_id));
+    let user = store_value(user);
+
+    view! {
+        <div class="ml-8 mt-2 p-4 bg-base-100 rounded-xl">
+            <h3 class="font-bold mb-2">"History"</h3>
+            <Transition fallback=move || view! { <span class="loading loading-spinner"></span> }>
+                {move || {
+                    history
+                        .get()
+                        .map(|history| match history {
+                            Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                            Ok(history) if history.is_empty() => {
+                                view! { <p class="opacity-50">"Nothing happened yet."</p> }.into_view()
+                            }
+                            Ok(history) => {
+                                view! {
+                                    <ul class="timeline timeline-vertical timeline-compact">
+                                        {history
+                                            .into_iter()
+                                            .map(|event| {
+                                                user.with_value(|user| {
+                                                    let at = user.format_datetime(event.created_at);
+                                                    view! {
+                                                        <li>
+                                                            <hr/>
+                                                            <div class="timeline-start text-sm opacity-75">{at}</div>
+                                                            <div class="timeline-middle">"•"</div>
+                                                            <div class="timeline-end">
+                                                                {event.describe(user)} " by "
+                                                                <span class="text-primary">{event.username}</span>
+                                                            </div>
+                                                            <hr/>
+                                                        </li>
+                                                    }
+                                                })
+                                            })
+                                            .collect_view()}
+                                    </ul>
+                                }
+                                    .into_view()
+                            }
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
@@ -1,12 +1,39 @@
+#[cfg(feature = "ssr")]
+pub mod api;
 pub mod auth;
+pub mod bulk;
 pub mod error_template;
 pub mod errors;
 #[cfg(feature = "ssr")]
 pub mod fallback;
+pub mod habits;
+pub mod history;
+pub mod notes;
+pub mod notifications;
+pub mod projects;
+pub mod quick_add;
+pub mod rank;
+pub mod recurrence;
+pub mod reminders;
+#[cfg(feature = "ssr")]
+pub mod scheduler;
+pub mod search;
+pub mod settings;
+pub mod stats;
 #[cfg(feature = "ssr")]
 pub mod state;
+pub mod subtasks;
+pub mod tags;
+#[cfg(all(test, feature = "ssr"))]
+mod testing;
+#[cfg(feature = "ssr")]
+pub mod throttle;
 pub mod todo;
+pub mod tokens;
+pub mod trash;
+pub mod two_factor;
 pub mod ui;
+pub mod validation;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
@@ -3,7 +3,7 @@ use axum::{
     extract::{Path, State},
     http::Request,
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{delete, get, post},
     Router,
 };
 use axum_session::{SessionConfig, SessionLayer, SessionStore};
@@ -13,10 +13,14 @@ use leptos_axum::{
     generate_route_list, handle_server_fns_with_context, LeptosRoutes,
 };
 use kreqo_habits::{
+    api,
     auth::{ssr::AuthSession, User},
     fallback::file_and_error_handler,
+    scheduler::{NotificationSink, Scheduler, WebhookSink},
     state::AppState,
     todo::*,
+    trash::ssr::TrashCleaner,
+    validation::ssr::ValidationPolicy,
 };
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
 
@@ -32,6 +36,8 @@ async fn server_fn_handler(
         move || {
             provide_context(auth_session.clone());
             provide_context(app_state.pool.clone());
+            provide_context(app_state.scheduler.clone());
+            provide_context(app_state.validation.clone());
         },
         request,
     )
@@ -49,6 +55,8 @@ async fn leptos_routes_handler(
         move || {
             provide_context(auth_session.clone());
             provide_context(app_state.pool.clone());
+            provide_context(app_state.scheduler.clone());
+            provide_context(app_state.validation.clone());
         },
         TodoApp,
     );
@@ -83,6 +91,38 @@ async fn main() {
         eprintln!("{e:?}");
     }
 
+    // Reminders are fired in the background for as long as the server runs
+    let mut scheduler =
+        Scheduler::new(pool.clone()).with_sink(NotificationSink::new(pool.clone()));
+    if let Ok(url) = std::env::var("REMINDER_WEBHOOK_URL") {
+        scheduler = scheduler.with_sink(WebhookSink::new(url));
+    }
+    let scheduler_handle = scheduler.handle();
+    scheduler.spawn();
+
+    // Todos left in the trash for too long are purged in the background as well
+    let mut trash_cleaner = TrashCleaner::new(pool.clone());
+    if let Some(days) = std::env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+    {
+        trash_cleaner = trash_cleaner.with_retention(chrono::Duration::days(days));
+    }
+    trash_cleaner.spawn();
+
+    // Rules for new usernames and passwords
+    let env_length = |name: &str| std::env::var(name).ok().and_then(|length| length.parse().ok());
+    let mut validation = ValidationPolicy::default();
+    if let Some(min) = env_length("PASSWORD_MIN_LENGTH") {
+        validation = validation.with_password_min_length(min);
+    }
+    if let Some(min) = env_length("USERNAME_MIN_LENGTH") {
+        validation = validation.with_username_min_length(min);
+    }
+    if let Some(max) = env_length("USERNAME_MAX_LENGTH") {
+        validation = validation.with_username_max_length(max);
+    }
+
     // Setting this to None means we'll be using cargo-leptos and its env vars
     let conf = get_configuration(None).await.unwrap();
     let leptos_options = conf.leptos_options;
@@ -93,10 +133,34 @@ async fn main() {
         leptos_options,
         pool: pool.clone(),
         routes: routes.clone(),
+        scheduler: scheduler_handle,
+        validation,
     };
 
     // Build our application with a route
     let app = Router::new()
+        // REST API authenticated by personal access tokens
+        .route("/api/v1/openapi.json", get(api::openapi))
+        .route("/api/v1/todos", get(api::list_todos).post(api::create_todo))
+        .route(
+            "/api/v1/todos/:id",
+            get(api::get_todo)
+                .patch(api::update_todo)
+                .delete(api::delete_todo),
+        )
+        .route(
+            "/api/v1/projects",
+            get(api::list_projects).post(api::create_project),
+        )
+        .route("/api/v1/habits", get(api::list_habits))
+        .route(
+            "/api/v1/habits/:id/checkins",
+            post(api::create_checkin),
+        )
+        .route(
+            "/api/v1/habits/:id/checkins/:day",
+            delete(api::delete_checkin),
+        )
         .route(
             "/api/*fn_name",
             get(server_fn_handler).post(server_fn_handler),
@@ -116,7 +180,11 @@ async fn main() {
     // `axum::Server` is a re-export of `hyper::Server`
     log!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
+    // The client address is needed to throttle failed logins
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
         .await
         .unwrap();
 }
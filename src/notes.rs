@@ -0,0 +1,358 @@
+use crate::todo::edit_todo;
+use icondata as i;
+use leptos::*;
+use leptos_icons::Icon;
+use serde::{Deserialize, Serialize};
+
+/// Markdown notes of a todo, along with their HTML rendered on the server.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notes {
+    pub markdown: String,
+    /// Sanitized HTML of the notes, safe to insert in the page.
+    pub html: String,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::Notes;
+    use pulldown_cmark::{html, CowStr, Event, Options, Parser};
+
+    fn options() -> Options {
+        Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES
+    }
+
+    impl Notes {
+        pub fn new(markdown: String) -> Self {
+            Self {
+                html: render(&markdown),
+                markdown,
+            }
+        }
+    }
+
+    /// Renders Markdown to sanitized HTML.
+    ///
+    /// Raw HTML is shown as text, and the checkboxes of task lists are numbered in `data-task`
+    /// so they can be toggled from the page.
+    pub fn render(markdown: &str) -> String {
+        let mut tasks = 0..;
+        let events = Parser::new_ext(markdown, options()).map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::TaskListMarker(checked) => Event::InlineHtml(CowStr::from(format!(
+                r#"<input type="checkbox" data-task="{}"{}>"#,
+                tasks.next().unwrap_or_default(),
+                if checked { " checked" } else { "" },
+            ))),
+            event => event,
+        });
+        let mut unsafe_html = String::new();
+        html::push_html(&mut unsafe_html, events);
+
+        ammonia::Builder::default()
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["type", "checked", "data-task"])
+            .clean(&unsafe_html)
+            .to_string()
+    }
+
+    /// Checks or unchecks the task list item numbered `task` in `markdown`.
+    /// Returns `None` when there is no such item.
+    pub fn toggle_task(markdown: &str, task: usize, checked: bool) -> Option<String> {
+        let (_, range) = Parser::new_ext(markdown, options())
+            .into_offset_iter()
+            .filter(|(event, _)| matches!(event, Event::TaskListMarker(_)))
+            .nth(task)?;
+
+        let mut toggled = markdown.to_string();
+        toggled.replace_range(range, if checked { "[x]" } else { "[ ]" });
+        Some(toggled)
+    }
+}
+
+#[server(GetNotes, "/api")]
+pub async fn get_notes(todo_id: u32) -> Result<Notes, ServerFnError> {
+    use crate::todo::ssr::{pool, require_user, todo_not_found};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    sqlx::query_scalar::<_, String>(
+        "SELECT notes FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+    )
+        .bind(todo_id)
+        .bind(user.id)
+        .fetch_optional(&pool)
+        .await?
+        .map(Notes::new)
+        .ok_or_else(todo_not_found)
+}
+
+/// Checks or unchecks an item of a task list in the notes of a todo.
+#[server(ToggleNotesTask, "/api")]
+pub async fn toggle_notes_task(
+    todo_id: u32,
+    task: u32,
+    checked: bool,
+) -> Result<Notes, ServerFnError> {
+    use self::ssr::*;
+    use crate::{history::ssr as history, todo::ssr::{pool, require_user, todo_not_found}};
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let mut tx = pool.begin().await?;
+
+    let notes = sqlx::query_scalar::<_, String>(
+        "SELECT notes FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+    )
+    .bind(todo_id)
+    .bind(user.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(todo_not_found)?;
+    let notes = toggle_task(&notes, task as usize, checked)
+        .ok_or_else(|| ServerFnError::new("Task not found in the notes."))?;
+
+    sqlx::query("UPDATE todos SET notes = ? WHERE id = ?")
+        .bind(&notes)
+        .bind(todo_id)
+        .execute(&mut *tx)
+        .await?;
+    history::record_edited(&mut tx, todo_id, user.id, "notes").await?;
+    tx.commit().await?;
+
+    Ok(Notes::new(notes))
+}
+
+#[component]
+pub fn NotesToggle(
+    notes: RwSignal<Notes>,
+    expanded: ReadSignal<bool>,
+    set_expanded: WriteSignal<bool>,
+) -> impl IntoView {
+    view! {
+        <button
+            type="button"
+            class="btn btn-ghost btn-sm"
+            class:btn-active=expanded
+            class:opacity-50=move || notes.with(|notes| notes.markdown.trim().is_empty())
+            on:click=move |_| set_expanded.update(|expanded| *expanded = !*expanded)
+        >
+            <Icon icon=i::LuStickyNote/>
+            "Notes"
+        </button>
+    }
+}
+
+/// Detail panel of a todo showing its notes, with a form to edit them.
+#[component]
+pub fn TodoNotes(todo_id: u32, notes: RwSignal<Notes>) -> impl IntoView {
+    let (editing, set_editing) = create_signal(false);
+    let (error, set_error) = create_signal(None::<String>);
+    let draft = create_node_ref::<html::Textarea>();
+
+    let save = move |_| {
+        let Some(draft) = draft.get_untracked() else {
+            return;
+        };
+        let markdown = draft.value();
+        spawn_local(async move {
+            let saved = match edit_todo(todo_id, None, Some(markdown), None, None, None).await {
+                Ok(()) => get_notes(todo_id).await,
+                Err(e) => Err(e),
+            };
+            match saved {
+                Ok(saved) => {
+                    notes.set(saved);
+                    set_error.set(None);
+                    set_editing.set(false);
+                }
+                Err(e) => set_error.set(Some(e.to_string())),
+            }
+        });
+    };
+
+    // Checkboxes are toggled right away, and put back if saving fails
+    let toggle_task = move |ev: ev::Event| {
+        let input = event_target::<web_sys::HtmlInputElement>(&ev);
+        let Some(task) = input.get_attribute("data-task").and_then(|task| task.parse().ok()) else {
+            return;
+        };
+        let checked = input.checked();
+        spawn_local(async move {
+            match toggle_notes_task(todo_id, task, checked).await {
+                Ok(toggled) => notes.set(toggled),
+                Err(_) => input.set_checked(!checked),
+            }
+        });
+    };
+
+    view! {
+        <div class="ml-8 mt-2 p-4 bg-base-100 rounded-xl">
+            {move || match editing.get() {
+                true => {
+                    view! {
+                        <textarea
+                            node_ref=draft
+                            class="textarea textarea-bordered w-full h-48 font-mono"
+                            placeholder="Markdown, with - [ ] for task lists"
+                            prop:value=notes.with_untracked(|notes| notes.markdown.clone())
+                        ></textarea>
+                        <div class="flex items-center gap-2 mt-2">
+                            <button type="button" class="btn btn-primary btn-sm" on:click=save>
+                                "Save"
+                            </button>
+                            <button
+                                type="button"
+                                class="btn btn-ghost btn-sm"
+                                on:click=move |_| {
+                                    set_error.set(None);
+                                    set_editing.set(false);
+                                }
+                            >
+                                "Cancel"
+                            </button>
+                            {move || error.get().map(|error| view! { <span class="text-error">{error}</span> })}
+                        </div>
+                    }
+                        .into_view()
+                }
+                false => {
+                    view! {
+                        <div class="flex items-start gap-4">
+                            {move || match notes.with(|notes| notes.markdown.trim().is_empty()) {
+                                true => view! { <p class="flex-1 opacity-50">"No notes yet."</p> }.into_view(),
+                                false => {
+                                    view! {
+                                        <div
+                                            class="flex-1 notes"
+                                            on:change=toggle_task
+                                            inner_html=move || notes.with(|notes| notes.html.clone())
+                                        ></div>
+                                    }
+                                        .into_view()
+                                }
+                            }}
+                            <button
+                                type="button"
+                                class="btn btn-ghost btn-sm"
+                                on:click=move |_| set_editing.set(true)
+                            >
+                                <Icon icon=i::LuPencil/>
+                                "Edit"
+                            </button>
+                        </div>
+                    }
+                        .into_view()
+                }
+            }}
+
+        </div>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::ssr::*;
+    use crate::testing;
+
+    #[test]
+    fn toggles_the_nth_task() {
+        let markdown = "- [ ] Milk\n- [x] Eggs\n- [ ] Bread\n";
+
+        let cases = [
+            (0, true, Some("- [x] Milk\n- [x] Eggs\n- [ ] Bread\n")),
+            (1, false, Some("- [ ] Milk\n- [ ] Eggs\n- [ ] Bread\n")),
+            (2, true, Some("- [ ] Milk\n- [x] Eggs\n- [x] Bread\n")),
+            (1, true, Some(markdown)),
+            (3, true, None),
+        ];
+
+        for (task, checked, expected) in cases {
+            assert_eq!(toggle_task(markdown, task, checked).as_deref(), expected, "task {task}");
+        }
+    }
+
+    #[test]
+    fn skips_checkboxes_in_code_blocks() {
+        let markdown = "```\n- [ ] Not a task\n```\n\n    - [ ] Nor this\n\n- [ ] Task\n";
+
+        assert_eq!(
+            toggle_task(markdown, 0, true).as_deref(),
+            Some("```\n- [ ] Not a task\n```\n\n    - [ ] Nor this\n\n- [x] Task\n")
+        );
+        assert_eq!(toggle_task(markdown, 1, true), None);
+    }
+
+    #[test]
+    fn numbers_nested_tasks_in_document_order() {
+        let markdown = "- [ ] Trip\n  - [ ] Tickets\n  - [ ] Hotel\n- [ ] Packing\n";
+
+        assert_eq!(
+            toggle_task(markdown, 2, true).as_deref(),
+            Some("- [ ] Trip\n  - [ ] Tickets\n  - [x] Hotel\n- [ ] Packing\n")
+        );
+        assert_eq!(
+            toggle_task(markdown, 3, true).as_deref(),
+            Some("- [ ] Trip\n  - [ ] Tickets\n  - [ ] Hotel\n- [x] Packing\n")
+        );
+
+        // The rendered checkboxes are numbered the same way
+        let html = render(markdown);
+        for task in 0..4 {
+            assert!(html.contains(&format!(r#"data-task="{task}""#)), "{html}");
+        }
+    }
+
+    #[test]
+    fn renders_sanitized_html() {
+        let cases = [
+            ("**Bold**", "<p><strong>Bold</strong></p>"),
+            ("<script>alert(1)</script>", "&lt;script&gt;alert(1)&lt;/script&gt;"),
+            ("Hi <img src=x onerror=alert(1)>", "<p>Hi &lt;img src=x onerror=alert(1)&gt;</p>"),
+            ("[Click](javascript:alert(1))", "<p><a rel=\"noopener noreferrer\">Click</a></p>"),
+            ("[Site](https://example.com)", "<p><a href=\"https://example.com\" rel=\"noopener noreferrer\">Site</a></p>"),
+            ("![x](x.png \"t\")", "<p><img src=\"x.png\" alt=\"x\" title=\"t\"></p>"),
+        ];
+
+        for (markdown, expected) in cases {
+            assert_eq!(render(markdown).trim(), expected, "{markdown}");
+        }
+    }
+
+    #[test]
+    fn renders_task_checkboxes_only() {
+        let html = render("- [x] Done\n\n<input type=\"text\" onfocus=\"alert(1)\">");
+
+        assert!(html.contains(r#"<input type="checkbox" data-task="0" checked="">"#), "{html}");
+        // Other inputs are shown as text
+        assert!(html.contains(r#"&lt;input type="text" onfocus="alert(1)"&gt;"#), "{html}");
+        assert_eq!(html.matches("<input").count(), 1, "{html}");
+    }
+
+    #[tokio::test]
+    async fn notes_are_searchable() {
+        let pool = testing::pool().await;
+        let user = testing::user("alice", &pool).await;
+        let id = testing::todo("Groceries", &user, &pool).await;
+        let matches = |query: &'static str| {
+            sqlx::query_scalar::<_, u32>("SELECT rowid FROM todos_fts WHERE todos_fts MATCH ?")
+                .bind(query)
+                .fetch_all(&pool)
+        };
+
+        sqlx::query("UPDATE todos SET notes = 'Oat milk and eggs' WHERE id = ?")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(matches("eggs").await.unwrap(), [id]);
+
+        sqlx::query("UPDATE todos SET notes = 'Only bread' WHERE id = ?")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(matches("eggs").await.unwrap().is_empty());
+        assert_eq!(matches("bread").await.unwrap(), [id]);
+    }
+}
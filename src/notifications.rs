@@ -0,0 +1,366 @@
+use crate::{auth::{User, UserResource}, error_template::ErrorTemplate, ui::{ActionIcon, Container}};
+use chrono::{DateTime, Utc};
+use icondata as i;
+use leptos::*;
+use leptos_icons::Icon;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: u32,
+    pub todo_id: Option<u32>,
+    pub title: String,
+    pub body: Option<String>,
+    pub read: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::Notification;
+    use chrono::{DateTime, Utc};
+    use leptos::ServerFnError;
+
+    pub fn notification_not_found() -> ServerFnError {
+        ServerFnError::new("Notification not found.")
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlNotification {
+        pub id: u32,
+        pub todo_id: Option<u32>,
+        pub title: String,
+        pub body: Option<String>,
+        pub read_at: Option<DateTime<Utc>>,
+        pub created_at: DateTime<Utc>,
+    }
+
+    impl SqlNotification {
+        pub fn into_notification(self) -> Notification {
+            Notification {
+                id: self.id,
+                todo_id: self.todo_id,
+                title: self.title,
+                body: self.body,
+                read: self.read_at.is_some(),
+                created_at: self.created_at,
+            }
+        }
+    }
+}
+
+/// Lists the latest notifications of the current user, newest first.
+#[server(GetNotifications, "/api")]
+pub async fn get_notifications(
+    unread_only: bool,
+    limit: Option<u32>,
+) -> Result<Vec<Notification>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(sqlx::query_as::<_, SqlNotification>(
+        "SELECT * FROM notifications
+        WHERE user_id = ? AND (? = false OR read_at IS NULL)
+        ORDER BY created_at DESC, id DESC
+        LIMIT ?",
+    )
+    .bind(user.id)
+    .bind(unread_only)
+    .bind(limit.unwrap_or(100))
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlNotification::into_notification)
+    .collect())
+}
+
+#[server(GetUnreadCount, "/api")]
+pub async fn get_unread_count() -> Result<u32, ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(sqlx::query_scalar::<_, u32>(
+        "SELECT COUNT(*) FROM notifications WHERE user_id = ? AND read_at IS NULL",
+    )
+    .bind(user.id)
+    .fetch_one(&pool)
+    .await?)
+}
+
+/// Marks one notification as read, or all of them when `id` is `None`.
+#[server(MarkNotificationsRead, "/api")]
+pub async fn mark_notifications_read(id: Option<u32>) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let result = sqlx::query(
+        "UPDATE notifications SET read_at = COALESCE(read_at, ?)
+        WHERE user_id = ? AND (? IS NULL OR id = ?)",
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(user.id)
+    .bind(id)
+    .bind(id)
+    .execute(&pool)
+    .await?;
+
+    match (id, result.rows_affected()) {
+        (Some(_), 0) => Err(notification_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Deletes one notification, or all of them when `id` is `None`.
+#[server(ClearNotifications, "/api")]
+pub async fn clear_notifications(id: Option<u32>) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let result = sqlx::query(
+        "DELETE FROM notifications WHERE user_id = ? AND (? IS NULL OR id = ?)",
+    )
+    .bind(user.id)
+    .bind(id)
+    .bind(id)
+    .execute(&pool)
+    .await?;
+
+    match (id, result.rows_affected()) {
+        (Some(_), 0) => Err(notification_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Actions shared by the navbar bell and the notifications page, so both stay in sync.
+#[derive(Clone, Copy)]
+pub struct NotificationActions {
+    pub mark_read: Action<MarkNotificationsRead, Result<(), ServerFnError>>,
+    pub clear: Action<ClearNotifications, Result<(), ServerFnError>>,
+}
+
+impl NotificationActions {
+    pub fn provide() {
+        provide_context(NotificationActions {
+            mark_read: create_server_action::<MarkNotificationsRead>(),
+            clear: create_server_action::<ClearNotifications>(),
+        });
+    }
+
+    fn versions(&self) -> (usize, usize) {
+        (self.mark_read.version().get(), self.clear.version().get())
+    }
+}
+
+/// Unread badge and dropdown of the latest unread notifications, for the navbar.
+#[component]
+pub fn NotificationBell(user: User) -> impl IntoView {
+    let actions = expect_context::<NotificationActions>();
+    let (tick, set_tick) = create_signal(0);
+
+    let unread = create_resource(
+        move || (actions.versions(), tick.get()),
+        move |_| get_notifications(true, Some(5)),
+    );
+    let count = create_resource(
+        move || (actions.versions(), tick.get()),
+        move |_| get_unread_count(),
+    );
+
+    // Reminders fire in the background, so look for new ones every now and then
+    create_effect(move |_| {
+        if let Ok(handle) = set_interval_with_handle(
+            move || set_tick.update(|tick| *tick += 1),
+            std::time::Duration::from_secs(30),
+        ) {
+            on_cleanup(move || handle.clear());
+        }
+    });
+
+    view! {
+        <div class="dropdown dropdown-end">
+            <div tabindex="0" role="button" class="btn btn-ghost btn-circle">
+                <div class="indicator">
+                    <Icon icon=i::LuBell class="text-2xl"/>
+                    <Transition>
+                        {move || {
+                            count
+                                .get()
+                                .and_then(Result::ok)
+                                .filter(|count| *count > 0)
+                                .map(|count| {
+                                    view! {
+                                        <span class="badge badge-sm badge-primary indicator-item">
+                                            {count}
+                                        </span>
+                                    }
+                                })
+                        }}
+
+                    </Transition>
+                </div>
+            </div>
+            <div
+                tabindex="0"
+                class="dropdown-content z-[1] mt-1 p-2 w-80 bg-base-200 border border-neutral rounded-xl"
+            >
+                <Transition fallback=move || view! { <span class="loading loading-spinner"></span> }>
+                    {
+                        let user = user.clone();
+                        move || {
+                            let user = user.clone();
+                            unread
+                                .get()
+                                .map(move |notifications| match notifications {
+                                    Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                                    Ok(notifications) if notifications.is_empty() => {
+                                        view! { <p class="p-2">"You're all caught up."</p> }.into_view()
+                                    }
+                                    Ok(notifications) => {
+                                        notifications
+                                            .into_iter()
+                                            .map(|notification| {
+                                                view! {
+                                                    <NotificationItem notification user=user.clone()/>
+                                                }
+                                            })
+                                            .collect_view()
+                                    }
+                                })
+                        }
+                    }
+
+                </Transition>
+                <div class="flex justify-between mt-2">
+                    <button
+                        class="btn btn-ghost btn-sm"
+                        on:click=move |_| actions.mark_read.dispatch(MarkNotificationsRead { id: None })
+                    >
+                        "Mark all read"
+                    </button>
+                    <A href="/notifications" class="btn btn-ghost btn-sm">
+                        "See all"
+                    </A>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn Notifications() -> impl IntoView {
+    let actions = expect_context::<NotificationActions>();
+
+    let notifications = create_resource(
+        move || actions.versions(),
+        move |_| get_notifications(false, None),
+    );
+    let user = expect_context::<UserResource>();
+
+    view! {
+        <Container>
+            <div class="flex items-center gap-4 mb-4">
+                <h2 class="flex-1 text-2xl font-bold">"Notifications"</h2>
+                <button
+                    class="btn btn-ghost text-lg"
+                    on:click=move |_| actions.mark_read.dispatch(MarkNotificationsRead { id: None })
+                >
+                    "Mark all read"
+                </button>
+                <button
+                    class="btn btn-ghost text-lg text-error"
+                    on:click=move |_| actions.clear.dispatch(ClearNotifications { id: None })
+                >
+                    "Clear all"
+                </button>
+            </div>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        let user = user.get().and_then(Result::ok).flatten().unwrap_or_default();
+                        notifications
+                            .get()
+                            .map(move |notifications| match notifications {
+                                Err(e) => {
+                                    view! {
+                                        <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(notifications) => {
+                                    if notifications.is_empty() {
+                                        view! { <p>"No notifications."</p> }.into_view()
+                                    } else {
+                                        view! {
+                                            <ul class="overflow-auto space-y-2">
+                                                {notifications
+                                                    .into_iter()
+                                                    .map(|notification| {
+                                                        view! {
+                                                            <li>
+                                                                <NotificationItem notification user=user.clone()/>
+                                                            </li>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </ul>
+                                        }
+                                            .into_view()
+                                    }
+                                }
+                            })
+                            .unwrap_or_default()
+                    }}
+
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+#[component]
+pub fn NotificationItem(notification: Notification, user: User) -> impl IntoView {
+    let actions = expect_context::<NotificationActions>();
+    let created_at = user.format_datetime(notification.created_at);
+
+    view! {
+        <div class="flex gap-2" class:opacity-60=notification.read>
+            <div class="min-h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
+                <div class="flex-1">
+                    <p class="font-bold">{notification.title}</p>
+                    {notification.body.map(|body| view! { <p>{body}</p> })}
+                </div>
+                <span class="text-sm text-primary">{created_at}</span>
+            </div>
+            <Show when=move || !notification.read>
+                <ActionIcon
+                    action=actions.mark_read
+                    icon=i::LuCheck
+                    class="btn-ghost bg-base-100 text-accent rounded-xl"
+                >
+                    <input type="hidden" name="id" value=notification.id/>
+                </ActionIcon>
+            </Show>
+            <ActionIcon
+                action=actions.clear
+                icon=i::LuX
+                class="btn-ghost bg-base-100 text-error rounded-xl"
+            >
+                <input type="hidden" name="id" value=notification.id/>
+            </ActionIcon>
+        </div>
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{error_template::ErrorTemplate, todo::Todos, ui::{ActionIcon, Container}};
+use icondata as i;
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Project {
+    pub id: u32,
+    pub name: String,
+    pub archived: bool,
+    /// Number of todos in the project.
+    pub total: u32,
+    /// Number of completed todos in the project.
+    pub completed: u32,
+}
+
+impl Project {
+    pub fn progress(&self) -> String {
+        format!("{}/{} done", self.completed, self.total)
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::Project;
+    use crate::auth::User;
+    use leptos::ServerFnError;
+    use sqlx::SqlitePool;
+
+    /// Error returned when a project does not exist or belongs to another user.
+    pub fn project_not_found() -> ServerFnError {
+        ServerFnError::new("Project not found.")
+    }
+
+    /// Makes sure `id` refers to a project owned by `user`.
+    pub async fn authorize_project(
+        id: u32,
+        user: &User,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        sqlx::query("SELECT id FROM projects WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user.id)
+            .fetch_optional(pool)
+            .await?
+            .map(|_| ())
+            .ok_or_else(project_not_found)
+    }
+
+    /// Finds a project of `user` by name, ignoring case and preferring active projects.
+    pub async fn find_project(
+        name: &str,
+        user: &User,
+        pool: &SqlitePool,
+    ) -> Result<u32, ServerFnError> {
+        sqlx::query_scalar::<_, u32>(
+            "SELECT id FROM projects WHERE user_id = ? AND name = ? COLLATE NOCASE
+            ORDER BY archived, id LIMIT 1",
+        )
+        .bind(user.id)
+        .bind(name.trim())
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ServerFnError::new(format!("No project named {name}.")))
+    }
+
+    pub fn ensure_affected(
+        result: sqlx::sqlite::SqliteQueryResult,
+    ) -> Result<(), ServerFnError> {
+        match result.rows_affected() {
+            0 => Err(project_not_found()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Projects along with their todo counts.
+    pub const SELECT_PROJECTS: &str = "SELECT p.id, p.name, p.archived,
+        COUNT(t.id) AS total,
+        COALESCE(SUM(t.completed), 0) AS completed
+        FROM projects p
+        LEFT JOIN todos t ON t.project_id = p.id AND t.parent_id IS NULL AND t.deleted_at IS NULL
+        WHERE p.user_id = ?";
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlProject {
+        pub id: u32,
+        pub name: String,
+        pub archived: bool,
+        pub total: u32,
+        pub completed: u32,
+    }
+
+    impl SqlProject {
+        pub fn into_project(self) -> Project {
+            Project {
+                id: self.id,
+                name: self.name,
+                archived: self.archived,
+                total: self.total,
+                completed: self.completed,
+            }
+        }
+    }
+}
+
+#[server(GetProjects, "/api")]
+pub async fn get_projects() -> Result<Vec<Project>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(sqlx::query_as::<_, SqlProject>(&format!(
+        "{SELECT_PROJECTS} GROUP BY p.id ORDER BY p.archived, p.name"
+    ))
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlProject::into_project)
+    .collect())
+}
+
+#[server(GetProject, "/api")]
+pub async fn get_project(id: u32) -> Result<Project, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    sqlx::query_as::<_, SqlProject>(&format!(
+        "{SELECT_PROJECTS} AND p.id = ? GROUP BY p.id"
+    ))
+    .bind(user.id)
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?
+    .map(SqlProject::into_project)
+    .ok_or_else(project_not_found)
+}
+
+#[server(AddProject, "/api")]
+pub async fn add_project(name: String) -> Result<(), ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    if name.trim().is_empty() {
+        return Err(ServerFnError::new("A project needs a name."));
+    }
+
+    Ok(sqlx::query("INSERT INTO projects (user_id, name) VALUES (?, ?)")
+        .bind(user.id)
+        .bind(name.trim())
+        .execute(&pool)
+        .await
+        .map(|_| ())?)
+}
+
+#[server(RenameProject, "/api")]
+pub async fn rename_project(id: u32, name: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    if name.trim().is_empty() {
+        return Err(ServerFnError::new("A project needs a name."));
+    }
+
+    ensure_affected(
+        sqlx::query("UPDATE projects SET name = ? WHERE id = ? AND user_id = ?")
+            .bind(name.trim())
+            .bind(id)
+            .bind(user.id)
+            .execute(&pool)
+            .await?,
+    )
+}
+
+#[server(ArchiveProject, "/api")]
+pub async fn archive_project(id: u32, archived: bool) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    ensure_affected(
+        sqlx::query("UPDATE projects SET archived = ? WHERE id = ? AND user_id = ?")
+            .bind(archived)
+            .bind(id)
+            .bind(user.id)
+            .execute(&pool)
+            .await?,
+    )
+}
+
+/// Deletes a project. Its todos are kept and moved out of the project.
+#[server(DeleteProject, "/api")]
+pub async fn delete_project(id: u32) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    ensure_affected(
+        sqlx::query("DELETE FROM projects WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user.id)
+            .execute(&pool)
+            .await?,
+    )
+}
+
+#[component]
+pub fn Projects() -> impl IntoView {
+    let add_project = create_server_action::<AddProject>();
+    let rename_project = create_server_action::<RenameProject>();
+    let archive_project = create_server_action::<ArchiveProject>();
+    let delete_project = create_server_action::<DeleteProject>();
+
+    let projects = create_resource(
+        move || {
+            (
+                add_project.version().get(),
+                rename_project.version().get(),
+                archive_project.version().get(),
+                delete_project.version().get(),
+            )
+        },
+        move |_| get_projects(),
+    );
+
+    view! {
+        <Container>
+            <ActionForm action=add_project class="flex items-center gap-4 mb-4">
+                <label class="input input-bordered flex items-center flex-1 text-xl gap-4">
+                    <span class="text-primary">"Project Name"</span>
+                    <input type="text" name="name"/>
+                </label>
+                <button type="submit" class="btn btn-primary text-lg">
+                    "Add Project"
+                </button>
+            </ActionForm>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        projects
+                            .get()
+                            .map(move |projects| match projects {
+                                Err(e) => {
+                                    view! {
+                                        <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(projects) => {
+                                    if projects.is_empty() {
+                                        view! { <p>"No projects were found."</p> }.into_view()
+                                    } else {
+                                        view! {
+                                            <ul class="overflow-auto space-y-2">
+                                                {projects
+                                                    .into_iter()
+                                                    .map(move |project| {
+                                                        view! {
+                                                            <li>
+                                                                <ProjectRow
+                                                                    project
+                                                                    rename_project
+                                                                    archive_project
+                                                                    delete_project
+                                                                />
+                                                            </li>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </ul>
+                                        }
+                                            .into_view()
+                                    }
+                                }
+                            })
+                            .unwrap_or_default()
+                    }}
+
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+#[component]
+pub fn ProjectRow(
+    project: Project,
+    rename_project: Action<RenameProject, Result<(), ServerFnError>>,
+    archive_project: Action<ArchiveProject, Result<(), ServerFnError>>,
+    delete_project: Action<DeleteProject, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let progress = project.progress();
+    let name = project.name.clone();
+    let (archive_icon, archive_class) = if project.archived {
+        (i::LuArchiveRestore, "btn-ghost bg-base-100 text-accent rounded-xl")
+    } else {
+        (i::LuArchive, "btn-ghost bg-base-100 rounded-xl")
+    };
+
+    view! {
+        <div class="flex gap-2" class:opacity-50=project.archived>
+            <div class="h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
+                <A href=format!("/projects/{}", project.id) class="text-xl link link-hover">
+                    {name}
+                </A>
+                <progress
+                    class="progress progress-accent w-32"
+                    value=project.completed
+                    max=project.total.max(1)
+                ></progress>
+                <span>{progress}</span>
+                <ActionForm action=rename_project class="flex flex-1 justify-end gap-2">
+                    <input type="hidden" name="id" value=project.id/>
+                    <input
+                        type="text"
+                        name="name"
+                        value=project.name
+                        class="input input-sm input-bordered"
+                    />
+                    <button type="submit" class="btn btn-sm btn-ghost">
+                        "Rename"
+                    </button>
+                </ActionForm>
+            </div>
+            <ActionIcon action=archive_project icon=archive_icon class=archive_class>
+                <input type="hidden" name="id" value=project.id/>
+                <input type="hidden" name="archived" value=(!project.archived).to_string()/>
+            </ActionIcon>
+            <ActionIcon
+                action=delete_project
+                icon=i::LuTrash2
+                class="btn-ghost bg-base-100 text-error rounded-xl"
+            >
+                <input type="hidden" name="id" value=project.id/>
+            </ActionIcon>
+        </div>
+    }
+}
+
+#[derive(Params, Clone, Debug, PartialEq, Eq)]
+pub struct ProjectParams {
+    id: Option<u32>,
+}
+
+#[component]
+pub fn ProjectView() -> impl IntoView {
+    let params = use_params::<ProjectParams>();
+    let id = move || params.with(|params| params.as_ref().ok().and_then(|params| params.id));
+
+    let project = create_resource(id, move |id| async move {
+        match id {
+            Some(id) => get_project(id).await,
+            None => Err(ServerFnError::new("Project not found.")),
+        }
+    });
+
+    view! {
+        <Transition fallback=move || view! { <p>"Loading..."</p> }>
+            {move || {
+                project
+                    .get()
+                    .map(|project| match project {
+                        Err(e) => {
+                            view! {
+                                <Container>
+                                    <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                </Container>
+                            }
+                                .into_view()
+                        }
+                        Ok(project) => {
+                            let progress = project.progress();
+                            view! {
+                                <Container>
+                                    <div class="flex items-center gap-4">
+                                        <h2 class="text-2xl font-bold">{project.name.clone()}</h2>
+                                        <progress
+                                            class="progress progress-accent w-48"
+                                            value=project.completed
+                                            max=project.total.max(1)
+                                        ></progress>
+                                        <span class="text-lg">{progress}</span>
+                                    </div>
+                                </Container>
+                                <Todos project=project.id/>
+                            }
+                                .into_view()
+                        }
+                    })
+            }}
+
+        </Transition>
+    }
+}
@@ -0,0 +1,684 @@
+//! Natural-language parsing of the title typed in the Add Todo form.
+//!
+//! Inputs like "Pay rent every month on the 1st at 9am #finance !high @Household" are split into
+//! a title and the fields found along it. Words that are not understood are kept in the title,
+//! as are words in double quotes, like in `Read "the daily" news`.
+//!
+//! Parsing is opt-in from the Add Todo form, titles are otherwise kept as typed.
+
+use crate::{
+    recurrence::{Frequency, Recurrence, MAX_INTERVAL},
+    todo::Priority,
+};
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+/// Time of day a todo is due when only a date was given.
+pub const DEFAULT_DUE_TIME: NaiveTime = NaiveTime::from_hms_opt(23, 59, 0).unwrap();
+
+/// Time of day "tonight" stands for.
+const TONIGHT: NaiveTime = NaiveTime::from_hms_opt(21, 0, 0).unwrap();
+
+/// How far to look for the first day a recurrence falls on, enough for any day of the month.
+const MAX_DAYS_AHEAD: usize = 100;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuickAdd {
+    pub title: String,
+    /// Due date in the user's time zone.
+    pub due: Option<NaiveDateTime>,
+    pub recurrence: Option<Recurrence>,
+    /// Tag names, without the leading `#`.
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+    /// Project name, with underscores turned into spaces.
+    pub project: Option<String>,
+}
+
+impl QuickAdd {
+    /// A todo titled `input` as typed, without looking for fields in it.
+    pub fn plain(input: &str) -> Self {
+        Self {
+            title: input.trim().to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether nothing but a title was found.
+    pub fn is_plain(&self) -> bool {
+        self.due.is_none()
+            && self.recurrence.is_none()
+            && self.tags.is_empty()
+            && self.priority.is_none()
+            && self.project.is_none()
+    }
+}
+
+/// Parses `input`, resolving relative dates against `now` in the user's time zone.
+///
+/// Every kind of field is only taken once, later mentions are left in the title.
+/// An input made only of fields is kept whole as the title.
+pub fn parse(input: &str, now: NaiveDateTime) -> QuickAdd {
+    let (words, quoted) = words(input);
+    // Quoted words get a key matching nothing, so fields never start or span over them
+    let keys: Vec<String> = words
+        .iter()
+        .zip(&quoted)
+        .map(|(word, quoted)| if *quoted { String::new() } else { key(word) })
+        .collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+    let mut fields = Fields::default();
+    let mut title = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let taken = match quoted[i] {
+            true => None,
+            false => fields.take(&words[i..], &keys[i..], now.date()),
+        };
+        match taken {
+            Some(taken) => i += taken,
+            None => {
+                title.push(words[i]);
+                i += 1;
+            }
+        }
+    }
+
+    if title.is_empty() {
+        return QuickAdd::plain(input);
+    }
+    fields.finish(title.join(" "), now)
+}
+
+/// Splits `input` into words, telling which ones were in double quotes.
+/// The quotes themselves are dropped, unless they are never closed.
+fn words(input: &str) -> (Vec<&str>, Vec<bool>) {
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let mut quoted = vec![false; words.len()];
+
+    let mut i = 0;
+    while i < words.len() {
+        let closing = match words[i].strip_prefix('"') {
+            Some(rest) if rest.ends_with('"') && !rest.is_empty() => Some(i),
+            Some(_) => (i + 1..words.len()).find(|j| words[*j].ends_with('"')),
+            None => None,
+        };
+        let Some(closing) = closing else {
+            i += 1;
+            continue;
+        };
+
+        words[i] = &words[i][1..];
+        words[closing] = &words[closing][..words[closing].len() - 1];
+        quoted[i..=closing].fill(true);
+        i = closing + 1;
+    }
+
+    // Lone quotes leave nothing behind
+    words
+        .into_iter()
+        .zip(quoted)
+        .filter(|(word, _)| !word.is_empty())
+        .unzip()
+}
+
+/// Lowercased word without trailing punctuation, used for matching.
+fn key(word: &str) -> String {
+    word.trim_end_matches([',', '.', ';']).to_lowercase()
+}
+
+/// Fields found so far while going through the words.
+#[derive(Default)]
+struct Fields {
+    date: Option<NaiveDate>,
+    /// Day of the month from "on the 1st", resolved once the recurrence is known.
+    month_day: Option<u32>,
+    time: Option<NaiveTime>,
+    /// Time implied by the date, like for "tonight".
+    default_time: Option<NaiveTime>,
+    recurrence: Option<Recurrence>,
+    tags: Vec<String>,
+    priority: Option<Priority>,
+    project: Option<String>,
+}
+
+impl Fields {
+    /// Takes the field starting at the first word, returning how many words it spans.
+    fn take(&mut self, words: &[&str], keys: &[&str], today: NaiveDate) -> Option<usize> {
+        let word = words[0].trim_end_matches([',', '.', ';']);
+
+        if let Some(tag) = word.strip_prefix('#').filter(|tag| starts_with_letter(tag)) {
+            if !self.tags.iter().any(|other| other.eq_ignore_ascii_case(tag)) {
+                self.tags.push(tag.to_string());
+            }
+            return Some(1);
+        }
+        if self.priority.is_none() {
+            if let Some(priority) = keys[0].strip_prefix('!').and_then(Priority::parse) {
+                self.priority = Some(priority);
+                return Some(1);
+            }
+        }
+        if self.project.is_none() {
+            if let Some(project) = word.strip_prefix('@').filter(|project| starts_with_letter(project)) {
+                self.project = Some(project.replace('_', " "));
+                return Some(1);
+            }
+        }
+        if self.recurrence.is_none() {
+            if let Some((recurrence, taken)) = recurrence(keys) {
+                self.recurrence = Some(recurrence);
+                return Some(taken);
+            }
+        }
+        if self.date.is_none() && self.month_day.is_none() {
+            if let Some((date, taken)) = date(keys, today) {
+                match date {
+                    DatePhrase::Date(date) => self.date = Some(date),
+                    DatePhrase::Tonight => {
+                        self.date = Some(today);
+                        self.default_time = Some(TONIGHT);
+                    }
+                    DatePhrase::MonthDay(day) => self.month_day = Some(day),
+                }
+                return Some(taken);
+            }
+        }
+        if self.time.is_none() {
+            if let Some((time, taken)) = time(keys) {
+                self.time = Some(time);
+                return Some(taken);
+            }
+        }
+
+        None
+    }
+
+    fn finish(self, title: String, now: NaiveDateTime) -> QuickAdd {
+        let today = now.date();
+        let mut recurrence = self.recurrence;
+        let mut month_day = self.month_day;
+
+        // "Every month on the 1st" repeats on that day rather than only starting on it
+        if let Some(recurrence) = &mut recurrence {
+            if recurrence.frequency == Frequency::Monthly {
+                recurrence.by_month_day = month_day.take();
+            }
+        }
+
+        let date = self
+            .date
+            .or_else(|| month_day.and_then(|day| next_month_day(today, day)));
+        let time = self.time.or(self.default_time);
+        let due = match (date, &recurrence) {
+            (Some(date), _) => Some(date.and_time(time.unwrap_or(DEFAULT_DUE_TIME))),
+            // A recurring todo is first due on the earliest occurrence still to come
+            (None, Some(recurrence)) => {
+                let time = time.unwrap_or(DEFAULT_DUE_TIME);
+                today
+                    .iter_days()
+                    .take(MAX_DAYS_AHEAD)
+                    .map(|date| date.and_time(time))
+                    .find(|due| *due > now && falls_on(recurrence, due.date()))
+            }
+            // A time that already passed today means tomorrow
+            (None, None) => time.map(|time| {
+                let due = today.and_time(time);
+                match due > now {
+                    true => due,
+                    false => due + Duration::days(1),
+                }
+            }),
+        };
+
+        QuickAdd {
+            title,
+            due,
+            recurrence,
+            tags: self.tags,
+            priority: self.priority,
+            project: self.project,
+        }
+    }
+}
+
+fn starts_with_letter(text: &str) -> bool {
+    text.chars().next().is_some_and(char::is_alphabetic)
+}
+
+/// Whether `date` is one of the days `recurrence` falls on.
+fn falls_on(recurrence: &Recurrence, date: NaiveDate) -> bool {
+    (recurrence.by_weekday.is_empty() || recurrence.by_weekday.contains(&date.weekday()))
+        && recurrence.by_month_day.is_none_or(|day| day == date.day())
+}
+
+/// Parses "daily", "every other week", "every 3 days", "every weekday", "every mon and fri"...
+fn recurrence(keys: &[&str]) -> Option<(Recurrence, usize)> {
+    let frequency = match keys[0] {
+        "daily" => Some(Frequency::Daily),
+        "weekly" => Some(Frequency::Weekly),
+        "monthly" => Some(Frequency::Monthly),
+        "yearly" | "annually" => Some(Frequency::Yearly),
+        "every" | "each" => None,
+        _ => return None,
+    };
+    if let Some(frequency) = frequency {
+        return Some((Recurrence::new(frequency), 1));
+    }
+
+    let mut taken = 1;
+    let interval = match keys.get(taken).copied() {
+        Some("other") => Some(2),
+        Some(key) => number(key).filter(|interval| (1..=MAX_INTERVAL).contains(interval)),
+        None => None,
+    };
+    if interval.is_some() {
+        taken += 1;
+    }
+
+    let key = *keys.get(taken)?;
+    taken += 1;
+    let mut recurrence = match key {
+        "day" | "days" => Recurrence::new(Frequency::Daily),
+        "week" | "weeks" => Recurrence::new(Frequency::Weekly),
+        "month" | "months" => Recurrence::new(Frequency::Monthly),
+        "year" | "years" => Recurrence::new(Frequency::Yearly),
+        "weekday" | "weekdays" if interval.is_none() => weekly(vec![
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ]),
+        "weekend" | "weekends" if interval.is_none() => weekly(vec![Weekday::Sat, Weekday::Sun]),
+        key => {
+            let mut days = vec![weekday(key, true)?];
+            // Further days may follow, separated by commas or "and"
+            loop {
+                match keys.get(taken..) {
+                    Some([day, ..]) if weekday(day, true).is_some() => {
+                        days.extend(weekday(day, true));
+                        taken += 1;
+                    }
+                    Some(["and", day, ..]) if weekday(day, true).is_some() => {
+                        days.extend(weekday(day, true));
+                        taken += 2;
+                    }
+                    _ => break,
+                }
+            }
+            days.dedup();
+            weekly(days)
+        }
+    };
+    recurrence.interval = interval.unwrap_or(1);
+
+    Some((recurrence, taken))
+}
+
+fn weekly(days: Vec<Weekday>) -> Recurrence {
+    Recurrence {
+        by_weekday: days,
+        ..Recurrence::new(Frequency::Weekly)
+    }
+}
+
+enum DatePhrase {
+    Date(NaiveDate),
+    Tonight,
+    /// A day of the month without a month, like "the 1st".
+    MonthDay(u32),
+}
+
+/// Parses a date, optionally introduced by "on", "by" or "due".
+fn date(keys: &[&str], today: NaiveDate) -> Option<(DatePhrase, usize)> {
+    let prefixes = keys
+        .iter()
+        .take(2)
+        .take_while(|key| matches!(**key, "on" | "by" | "due"))
+        .count();
+    let (date, taken) = date_phrase(&keys[prefixes..], today, prefixes > 0)?;
+
+    Some((date, prefixes + taken))
+}
+
+/// `prefixed` allows short weekday names, which are common words on their own.
+fn date_phrase(keys: &[&str], today: NaiveDate, prefixed: bool) -> Option<(DatePhrase, usize)> {
+    let key = *keys.first()?;
+    let next = keys.get(1).copied();
+    let date = |date: Option<NaiveDate>, taken| date.map(|date| (DatePhrase::Date(date), taken));
+
+    match key {
+        "today" => return date(Some(today), 1),
+        "tonight" => return Some((DatePhrase::Tonight, 1)),
+        "tomorrow" | "tmr" | "tmrw" => return date(today.succ_opt(), 1),
+        "next" => {
+            return match next? {
+                "week" => date(Some(next_weekday(today, Weekday::Mon, false)), 2),
+                "month" => date(first_of_month(today, 1), 2),
+                "year" => date(NaiveDate::from_ymd_opt(today.year() + 1, 1, 1), 2),
+                next => date(Some(next_weekday(today, weekday(next, true)?, false)), 2),
+            };
+        }
+        "this" => return date(Some(next_weekday(today, weekday(next?, true)?, true)), 2),
+        "in" => {
+            let amount = number(next?)?;
+            let date_in = match *keys.get(2)? {
+                "day" | "days" => today.checked_add_signed(Duration::days(amount.into())),
+                "week" | "weeks" => today.checked_add_signed(Duration::weeks(amount.into())),
+                "month" | "months" => today.checked_add_months(Months::new(amount)),
+                "year" | "years" => today.checked_add_months(Months::new(amount.checked_mul(12)?)),
+                _ => None,
+            };
+            return date(date_in, 3);
+        }
+        "the" if prefixed => return ordinal(next?, true).map(|day| (DatePhrase::MonthDay(day), 2)),
+        _ => {}
+    }
+
+    if let Some(day) = weekday(key, prefixed) {
+        return date(Some(next_weekday(today, day, true)), 1);
+    }
+    if let Ok(date_iso) = NaiveDate::parse_from_str(key, "%Y-%m-%d") {
+        return date(Some(date_iso), 1);
+    }
+
+    // "jan 5", "january 5th 2025", "5 jan", "5th of january"
+    let (month, day, taken) = match (month(key), next) {
+        (Some(month), Some(next)) => (month, ordinal(next, false)?, 2),
+        (None, _) => {
+            let day = ordinal(key, false)?;
+            match keys.get(1..) {
+                Some(["of", month_key, ..]) => (month(month_key)?, day, 3),
+                Some([month_key, ..]) => (month(month_key)?, day, 2),
+                _ => return None,
+            }
+        }
+        (Some(_), None) => return None,
+    };
+    if let Some(year) = keys.get(taken).and_then(|year| year.parse::<i32>().ok()) {
+        if (1000..=9999).contains(&year) {
+            return date(NaiveDate::from_ymd_opt(year, month, day), taken + 1);
+        }
+    }
+    // Without a year, dates that already passed are next year's
+    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day);
+    let date_of = match this_year {
+        Some(this_year) if this_year >= today => Some(this_year),
+        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+    };
+    date(date_of, taken)
+}
+
+/// Parses a time, optionally introduced by "at".
+/// Bare hours like "at 9" need the "at", otherwise they are just numbers.
+fn time(keys: &[&str]) -> Option<(NaiveTime, usize)> {
+    match keys[0] {
+        "at" => time_phrase(&keys[1..], true).map(|(time, taken)| (time, taken + 1)),
+        _ => time_phrase(keys, false),
+    }
+}
+
+fn time_phrase(keys: &[&str], bare: bool) -> Option<(NaiveTime, usize)> {
+    let key = *keys.first()?;
+    match key {
+        "noon" | "midday" => return Some((NaiveTime::from_hms_opt(12, 0, 0)?, 1)),
+        "midnight" => return Some((NaiveTime::MIN, 1)),
+        _ => {}
+    }
+
+    let (clock, meridiem, taken) = match (key.strip_suffix("am"), key.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(false), 1),
+        (_, Some(clock)) => (clock, Some(true), 1),
+        _ => match keys.get(1).copied() {
+            Some("am") => (key, Some(false), 2),
+            Some("pm") => (key, Some(true), 2),
+            _ => (key, None, 1),
+        },
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse::<u32>().ok()?, minute.parse().ok()?),
+        Some(_) => return None,
+        None if meridiem.is_some() || bare => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+    let hour = match meridiem {
+        Some(pm) if (1..=12).contains(&hour) => hour % 12 + if pm { 12 } else { 0 },
+        Some(_) => return None,
+        None => hour,
+    };
+
+    Some((NaiveTime::from_hms_opt(hour, minute, 0)?, taken))
+}
+
+/// Parses counts, spelled with digits or as small words.
+fn number(key: &str) -> Option<u32> {
+    let words = [
+        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+        "eleven", "twelve",
+    ];
+    match key {
+        "a" | "an" => Some(1),
+        key => key
+            .parse()
+            .ok()
+            .or_else(|| words.iter().position(|word| *word == key).map(|n| n as u32)),
+    }
+}
+
+/// Parses a day of the month like "1st" or "15th". Plain numbers are only allowed when
+/// `bare` is set, since they could be anything.
+fn ordinal(key: &str, bare: bool) -> Option<u32> {
+    let digits = key
+        .strip_suffix("st")
+        .or_else(|| key.strip_suffix("nd"))
+        .or_else(|| key.strip_suffix("rd"))
+        .or_else(|| key.strip_suffix("th"));
+    let day = match digits {
+        Some(digits) => digits.parse().ok()?,
+        None if bare => key.parse().ok()?,
+        // A number right next to a month name is a day, like in "jan 5"
+        None => key.parse().ok().filter(|_| key.len() <= 2)?,
+    };
+
+    (1..=31).contains(&day).then_some(day)
+}
+
+/// Parses a weekday name. Short names like "fri" are only allowed when `short` is set.
+fn weekday(key: &str, short: bool) -> Option<Weekday> {
+    let day = key.trim_end_matches('s');
+    let full = match day {
+        "monday" => Some(Weekday::Mon),
+        "tuesday" => Some(Weekday::Tue),
+        "wednesday" => Some(Weekday::Wed),
+        "thursday" => Some(Weekday::Thu),
+        "friday" => Some(Weekday::Fri),
+        "saturday" => Some(Weekday::Sat),
+        "sunday" => Some(Weekday::Sun),
+        _ => None,
+    };
+    if full.is_some() || !short {
+        return full;
+    }
+
+    match key {
+        "mon" => Some(Weekday::Mon),
+        "tue" | "tues" => Some(Weekday::Tue),
+        "wed" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" => Some(Weekday::Thu),
+        "fri" => Some(Weekday::Fri),
+        "sat" => Some(Weekday::Sat),
+        "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn month(key: &str) -> Option<u32> {
+    let months = [
+        "january", "february", "march", "april", "may", "june", "july", "august", "september",
+        "october", "november", "december",
+    ];
+    let key = match key {
+        "sept" => "sep",
+        key => key,
+    };
+    months
+        .iter()
+        .position(|month| *month == key || (key.len() == 3 && month.starts_with(key)))
+        .map(|month| month as u32 + 1)
+}
+
+/// The next `day` coming after `today`, or `today` itself when `inclusive` is set.
+fn next_weekday(today: NaiveDate, day: Weekday, inclusive: bool) -> NaiveDate {
+    let ahead = (day.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    let ahead = match (ahead, inclusive) {
+        (0, false) => 7,
+        (ahead, _) => ahead,
+    };
+    today + Duration::days(ahead.into())
+}
+
+/// The first day of the month `months` after the one of `today`.
+fn first_of_month(today: NaiveDate, months: u32) -> Option<NaiveDate> {
+    today.with_day(1)?.checked_add_months(Months::new(months))
+}
+
+/// The next date falling on the `day` of a month, `today` included.
+fn next_month_day(today: NaiveDate, day: u32) -> Option<NaiveDate> {
+    // Months missing the day are skipped, one of the next two always has it
+    (0..3)
+        .filter_map(|months| first_of_month(today, months)?.with_day(day))
+        .find(|date| *date >= today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday, May 1st 2024, 10am.
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+    }
+
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2024, month, day)?.and_hms_opt(hour, minute, 0)
+    }
+
+    fn rule(rrule: &str) -> Option<Recurrence> {
+        Some(rrule.parse().expect("valid rule"))
+    }
+
+    #[test]
+    fn parses_dates_and_times() {
+        let cases = [
+            ("Call mom tomorrow", "Call mom", at(5, 2, 23, 59)),
+            ("Call mom tmrw at 9", "Call mom", at(5, 2, 9, 0)),
+            ("Dentist next friday at 3pm", "Dentist", at(5, 3, 15, 0)),
+            ("Dentist next wednesday", "Dentist", at(5, 8, 23, 59)),
+            ("Dentist on fri", "Dentist", at(5, 3, 23, 59)),
+            ("Dentist this wednesday", "Dentist", at(5, 1, 23, 59)),
+            ("Report due next week", "Report", at(5, 6, 23, 59)),
+            ("Report in 3 days", "Report", at(5, 4, 23, 59)),
+            ("Report in two weeks", "Report", at(5, 15, 23, 59)),
+            ("Taxes by 2024-06-15", "Taxes", at(6, 15, 23, 59)),
+            ("Party jan 5th", "Party", NaiveDate::from_ymd_opt(2025, 1, 5).unwrap().and_hms_opt(23, 59, 0)),
+            ("Party 5th of may at 7:30pm", "Party", at(5, 5, 19, 30)),
+            ("Lunch at noon", "Lunch", at(5, 1, 12, 0)),
+            ("Movie tonight", "Movie", at(5, 1, 21, 0)),
+            // A time that already passed today is tomorrow's
+            ("Coffee 9am", "Coffee", at(5, 2, 9, 0)),
+            ("Coffee at 9 am", "Coffee", at(5, 2, 9, 0)),
+        ];
+
+        for (input, title, due) in cases {
+            let quick_add = parse(input, now());
+            assert_eq!(quick_add.title, title, "{input}");
+            assert_eq!(quick_add.due, due, "{input}");
+        }
+    }
+
+    #[test]
+    fn parses_recurrences() {
+        let cases = [
+            ("Water plants daily", "Water plants", "FREQ=DAILY", at(5, 1, 23, 59)),
+            ("Water plants every 3 days", "Water plants", "FREQ=DAILY;INTERVAL=3", at(5, 1, 23, 59)),
+            ("Run every other week", "Run", "FREQ=WEEKLY;INTERVAL=2", at(5, 1, 23, 59)),
+            ("Gym every mon and fri at 7am", "Gym", "FREQ=WEEKLY;BYDAY=MO,FR", at(5, 3, 7, 0)),
+            ("Stand-up every weekday at 9:15", "Stand-up", "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR", at(5, 2, 9, 15)),
+            ("Pay rent every month on the 1st", "Pay rent", "FREQ=MONTHLY;BYMONTHDAY=1", at(5, 1, 23, 59)),
+        ];
+
+        for (input, title, rrule, due) in cases {
+            let quick_add = parse(input, now());
+            assert_eq!(quick_add.title, title, "{input}");
+            assert_eq!(quick_add.recurrence, rule(rrule), "{input}");
+            assert_eq!(quick_add.due, due, "{input}");
+        }
+    }
+
+    #[test]
+    fn parses_tags_priority_and_project() {
+        let quick_add = parse("Pay rent #finance !high @Home_Stuff #Finance #bills", now());
+
+        assert_eq!(
+            quick_add,
+            QuickAdd {
+                title: "Pay rent".to_string(),
+                tags: vec!["finance".to_string(), "bills".to_string()],
+                priority: Some(Priority::High),
+                project: Some("Home Stuff".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_what_is_not_understood_in_the_title() {
+        let cases = [
+            // Quoted words are never fields
+            (r#"Read "the daily" news"#, "Read the daily news"),
+            (r#"Email "Friday" report"#, "Email Friday report"),
+            (r##"Buy "#1" gift "!high""##, "Buy #1 gift !high"),
+            // Unclosed and lone quotes
+            (r#"Say "hi"#, r#"Say "hi"#),
+            (r#"Say " hi""#, "Say hi"),
+            // Too large, invalid or incomplete fields
+            ("Nap every 4000000000 days", "Nap every 4000000000 days"),
+            ("Run every", "Run every"),
+            ("Buy #1 gift", "Buy #1 gift"),
+            ("Email @ noon", "Email @"),
+            ("Ship 25:00", "Ship 25:00"),
+            ("Ship 13pm", "Ship 13pm"),
+            ("Ship feb 30", "Ship feb 30"),
+            ("Call sat", "Call sat"),
+            ("Buy 3 apples", "Buy 3 apples"),
+            ("Say !loud", "Say !loud"),
+            // Every kind of field is only taken once
+            ("Call tomorrow or friday !low !high", "Call or friday !high"),
+        ];
+
+        for (input, title) in cases {
+            assert_eq!(parse(input, now()).title, title, "{input}");
+        }
+    }
+
+    #[test]
+    fn keeps_inputs_made_only_of_fields_whole() {
+        for input in ["Tomorrow", "daily at 9", "#finance !high", "  today  "] {
+            let quick_add = parse(input, now());
+            assert_eq!(quick_add, QuickAdd::plain(input), "{input}");
+            assert!(quick_add.is_plain(), "{input}");
+        }
+    }
+
+    #[test]
+    fn plain_only_trims() {
+        let quick_add = QuickAdd::plain("  Email Friday report every day #work  ");
+
+        assert_eq!(quick_add.title, "Email Friday report every day #work");
+        assert!(quick_add.is_plain());
+    }
+}
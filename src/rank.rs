@@ -0,0 +1,191 @@
+//! Fractional ranks used to keep todos in a manual order.
+//!
+//! A rank is a string of base 62 digits compared lexicographically. There is always room for
+//! another rank between two different ranks, so moving a todo only ever updates that todo.
+//! Ranks never end with the smallest digit, otherwise nothing could be placed right before them.
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Rank of the first item of a list, leaving plenty of room on both sides.
+const FIRST: &str = "VVVVVVVV";
+
+fn digit(byte: u8) -> usize {
+    DIGITS.iter().position(|digit| *digit == byte).unwrap_or_default()
+}
+
+/// A rank sorting after `after` and before `before`, where `None` stands for the start
+/// and the end of the list respectively.
+///
+/// Returns `None` when `after` does not sort before `before`.
+pub fn between(after: Option<&str>, before: Option<&str>) -> Option<String> {
+    let rank = match (after, before) {
+        (None, None) => return Some(FIRST.to_string()),
+        (Some(after), Some(before)) if after >= before => return None,
+        // Stepping the last digit keeps ranks short when adding to either end over and over
+        (Some(after), None) => step(after.as_bytes(), true),
+        (None, Some(before)) => step(before.as_bytes(), false),
+        _ => None,
+    };
+
+    let rank = rank.unwrap_or_else(|| {
+        midpoint(after.unwrap_or_default().as_bytes(), before.map(str::as_bytes))
+    });
+    String::from_utf8(rank).ok()
+}
+
+/// `count` ranks in increasing order, for spreading out a list whose ranks ran into each other.
+pub fn spread(count: usize) -> Vec<String> {
+    let mut ranks: Vec<String> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let rank = between(ranks.last().map(String::as_str), None)
+            .expect("stepping up from the last rank");
+        ranks.push(rank);
+    }
+    ranks
+}
+
+/// Increments or decrements the last digit of `rank`, carrying over to the previous ones
+/// and skipping ranks ending with the smallest digit.
+///
+/// Returns `None` when that would overflow.
+fn step(rank: &[u8], up: bool) -> Option<Vec<u8>> {
+    let mut digits: Vec<usize> = rank.iter().map(|byte| digit(*byte)).collect();
+    loop {
+        let mut i = digits.len();
+        loop {
+            i = i.checked_sub(1)?;
+            match (up, digits[i]) {
+                (true, digit) if digit + 1 == DIGITS.len() => digits[i] = 0,
+                (false, 0) => digits[i] = DIGITS.len() - 1,
+                (true, digit) => break digits[i] = digit + 1,
+                (false, digit) => break digits[i] = digit - 1,
+            }
+        }
+        if digits.last() != Some(&0) {
+            break;
+        }
+    }
+
+    Some(digits.into_iter().map(|digit| DIGITS[digit]).collect())
+}
+
+fn midpoint(after: &[u8], before: Option<&[u8]>) -> Vec<u8> {
+    // Digits missing at the end of `after` count as zeros
+    let after_digit = |i: usize| after.get(i).map_or(0, |byte| digit(*byte));
+
+    if let Some(before) = before {
+        let common = before
+            .iter()
+            .enumerate()
+            .take_while(|(i, byte)| after_digit(*i) == digit(**byte))
+            .count();
+        if common > 0 {
+            let mut rank = before[..common].to_vec();
+            rank.extend(midpoint(
+                after.get(common..).unwrap_or_default(),
+                Some(&before[common..]),
+            ));
+            return rank;
+        }
+    }
+
+    let low = after_digit(0);
+    let high = before.map_or(DIGITS.len(), |before| digit(before[0]));
+    if high - low > 1 {
+        vec![DIGITS[(low + high) / 2]]
+    } else if let Some(before) = before.filter(|before| before.len() > 1) {
+        // Cutting `before` short sorts it right before itself
+        vec![before[0]]
+    } else {
+        let mut rank = vec![DIGITS[low]];
+        rank.extend(midpoint(after.get(1..).unwrap_or_default(), None));
+        rank
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn between_ok(after: Option<&str>, before: Option<&str>) -> String {
+        let rank = between(after, before).unwrap();
+        assert!(after.is_none_or(|after| after < rank.as_str()), "{after:?} < {rank}");
+        assert!(before.is_none_or(|before| rank.as_str() < before), "{rank} < {before:?}");
+        assert!(!rank.ends_with('0'), "{rank} ends with the smallest digit");
+        rank
+    }
+
+    #[test]
+    fn starts_in_the_middle() {
+        assert_eq!(between(None, None).as_deref(), Some(FIRST));
+    }
+
+    #[test]
+    fn ranks_between_neighbours() {
+        let cases = [
+            (Some("V"), None),
+            (None, Some("V")),
+            (Some("A"), Some("B")),
+            (Some("A"), Some("A1")),
+            (Some("A"), Some("A01")),
+            (Some("A1"), Some("B")),
+            (Some("z"), None),
+            (Some("zzzz"), None),
+            (None, Some("1")),
+            (None, Some("01")),
+            (None, Some("0001")),
+            (Some("Vz"), Some("W")),
+            (Some("V"), Some("W01")),
+        ];
+
+        for (after, before) in cases {
+            between_ok(after, before);
+        }
+    }
+
+    #[test]
+    fn refuses_unordered_neighbours() {
+        assert_eq!(between(Some("V"), Some("V")), None);
+        assert_eq!(between(Some("W"), Some("V")), None);
+    }
+
+    #[test]
+    fn bisects_over_and_over() {
+        // Moving todos right after the first one, or right before the last one, again and again
+        for towards_after in [true, false] {
+            let (mut after, mut before) = (FIRST.to_string(), between_ok(Some(FIRST), None));
+            for _ in 0..500 {
+                let rank = between_ok(Some(&after), Some(&before));
+                match towards_after {
+                    true => before = rank,
+                    false => after = rank,
+                }
+            }
+            // Every new digit holds at least five halvings
+            let longest = after.len().max(before.len());
+            assert!(longest <= FIRST.len() + 500 / 5 + 1, "{after} {before}");
+        }
+    }
+
+    #[test]
+    fn stays_short_when_adding_at_either_end() {
+        let mut first = between_ok(None, None);
+        let mut last = first.clone();
+        for _ in 0..10_000 {
+            first = between_ok(None, Some(&first));
+            last = between_ok(Some(&last), None);
+        }
+
+        assert_eq!(first.len(), FIRST.len());
+        assert_eq!(last.len(), FIRST.len());
+    }
+
+    #[test]
+    fn spreads_ranks_in_order() {
+        let ranks = spread(100);
+
+        assert_eq!(ranks.len(), 100);
+        assert_eq!(ranks[0], FIRST);
+        assert!(ranks.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
@@ -0,0 +1,560 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Upper bound on how many occurrences are expanded in one go.
+pub const MAX_OCCURRENCES: usize = 1000;
+/// Largest interval a rule may have, so stepping through periods can't overflow.
+pub const MAX_INTERVAL: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A subset of the iCalendar (RFC 5545) RRULE model.
+///
+/// The first occurrence is always the `dtstart` the rule is expanded from, like DTSTART in iCalendar.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// Weekdays a weekly rule falls on. Defaults to the weekday of `dtstart` when empty.
+    pub by_weekday: Vec<Weekday>,
+    /// Day of the month a monthly rule falls on. Defaults to the day of `dtstart`.
+    pub by_month_day: Option<u32>,
+    pub until: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum RecurrenceError {
+    #[error("Recurrence rule is missing FREQ.")]
+    MissingFrequency,
+    #[error("Unsupported recurrence frequency: {0}")]
+    UnsupportedFrequency(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(&'static str, String),
+    #[error("Unsupported recurrence rule part: {0}")]
+    UnsupportedPart(String),
+    #[error("Only weekly recurrence rules can have BYDAY.")]
+    ByWeekdayNotWeekly,
+    #[error("Only monthly recurrence rules can have BYMONTHDAY.")]
+    ByMonthDayNotMonthly,
+}
+
+impl Recurrence {
+    pub fn new(frequency: Frequency) -> Self {
+        Self {
+            frequency,
+            interval: 1,
+            by_weekday: Vec::new(),
+            by_month_day: None,
+            until: None,
+        }
+    }
+
+    /// Returns the first occurrence strictly after `after`.
+    pub fn next_after(
+        &self,
+        dtstart: NaiveDateTime,
+        after: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        let next = if dtstart > after {
+            Some(dtstart)
+        } else {
+            match self.frequency {
+                Frequency::Daily => self.next_daily(dtstart, after),
+                Frequency::Weekly => self.next_weekly(dtstart, after),
+                Frequency::Monthly => self.next_monthly(dtstart, after),
+                Frequency::Yearly => self.next_yearly(dtstart, after),
+            }
+        }?;
+
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+
+    /// Expands every occurrence within `start..=end`, capped at [`MAX_OCCURRENCES`].
+    pub fn occurrences(
+        &self,
+        dtstart: NaiveDateTime,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Vec<NaiveDateTime> {
+        let mut occurrences = Vec::new();
+        let mut cursor = start - Duration::seconds(1);
+
+        while let Some(next) = self.next_after(dtstart, cursor) {
+            if next > end || occurrences.len() >= MAX_OCCURRENCES {
+                break;
+            }
+            occurrences.push(next);
+            cursor = next;
+        }
+
+        occurrences
+    }
+
+    /// A short human readable summary, e.g. "Every 2 weeks on Mon, Fri".
+    pub fn describe(&self) -> String {
+        let unit = match self.frequency {
+            Frequency::Daily => "day",
+            Frequency::Weekly => "week",
+            Frequency::Monthly => "month",
+            Frequency::Yearly => "year",
+        };
+        let mut text = match self.interval {
+            1 => format!("Every {unit}"),
+            n => format!("Every {n} {unit}s"),
+        };
+
+        if !self.by_weekday.is_empty() {
+            let days = self
+                .by_weekday
+                .iter()
+                .map(|day| day.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            text.push_str(&format!(" on {days}"));
+        }
+        if let Some(day) = self.by_month_day {
+            text.push_str(&format!(" on day {day}"));
+        }
+        if let Some(until) = self.until {
+            text.push_str(&format!(" until {}", until.date()));
+        }
+
+        text
+    }
+
+    fn interval(&self) -> i64 {
+        self.interval.max(1) as i64
+    }
+
+    fn next_daily(
+        &self,
+        dtstart: NaiveDateTime,
+        after: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        let step = Duration::try_days(self.interval())?;
+        let elapsed = (after - dtstart).num_days() / self.interval();
+        let mut candidate = dtstart
+            .checked_add_signed(Duration::try_days(elapsed.checked_mul(self.interval())?)?)?;
+
+        while candidate <= after {
+            candidate = candidate.checked_add_signed(step)?;
+        }
+
+        Some(candidate)
+    }
+
+    fn next_weekly(
+        &self,
+        dtstart: NaiveDateTime,
+        after: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        let mut weekdays = match self.by_weekday.is_empty() {
+            true => vec![dtstart.weekday()],
+            false => self.by_weekday.clone(),
+        };
+        weekdays.sort_by_key(|day| day.num_days_from_monday());
+
+        let first_week = week_start(dtstart.date());
+        let weeks_elapsed = (week_start(after.date()) - first_week).num_weeks();
+        let first = (weeks_elapsed / self.interval()).max(0);
+
+        // Two extra periods always contain the answer, since every period holds at least one weekday.
+        for period in first..first + 3 {
+            let weeks = Duration::try_weeks(period.checked_mul(self.interval())?)?;
+            let monday = first_week.checked_add_signed(weeks)?;
+            for day in &weekdays {
+                let date = monday + Duration::days(day.num_days_from_monday() as i64);
+                let candidate = date.and_time(dtstart.time());
+                if candidate >= dtstart && candidate > after {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn next_monthly(
+        &self,
+        dtstart: NaiveDateTime,
+        after: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        let day = self.by_month_day.unwrap_or(dtstart.day());
+        let start_month = month_index(dtstart.date());
+        let months_elapsed = month_index(after.date()) - start_month;
+        let first = (months_elapsed / self.interval()).max(0);
+
+        // Months missing the requested day (e.g. the 31st) are skipped, as in RFC 5545.
+        // Within 12 periods there is always a month long enough.
+        for period in first..=first + 12 {
+            let month = period
+                .checked_mul(self.interval())
+                .and_then(|months| months.checked_add(start_month))?;
+            if let Some(date) = date_from_month_index(month, day) {
+                let candidate = date.and_time(dtstart.time());
+                if candidate >= dtstart && candidate > after {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn next_yearly(
+        &self,
+        dtstart: NaiveDateTime,
+        after: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        let years_elapsed = (after.year() - dtstart.year()) as i64;
+        let first = (years_elapsed / self.interval()).max(0);
+
+        // February 29th only exists every four years, or eight around skipped century leap years.
+        for period in first..=first + 8 {
+            let year = period
+                .checked_mul(self.interval())
+                .and_then(|years| years.checked_add(dtstart.year() as i64))
+                .and_then(|year| i32::try_from(year).ok())?;
+            let date = NaiveDate::from_ymd_opt(year, dtstart.month(), dtstart.day());
+            if let Some(date) = date {
+                let candidate = date.and_time(dtstart.time());
+                if candidate > after {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn month_index(date: NaiveDate) -> i64 {
+    date.year() as i64 * 12 + date.month0() as i64
+}
+
+fn date_from_month_index(index: i64, day: u32) -> Option<NaiveDate> {
+    let year = i32::try_from(index.div_euclid(12)).ok()?;
+    let month = index.rem_euclid(12) as u32 + 1;
+
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday(code: &str) -> Result<Weekday, RecurrenceError> {
+    match code {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(RecurrenceError::InvalidValue("BYDAY", code.to_string())),
+    }
+}
+
+fn parse_until(value: &str) -> Result<NaiveDateTime, RecurrenceError> {
+    let value = value.trim_end_matches('Z');
+
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(|date| date.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap()))
+        })
+        .map_err(|_| RecurrenceError::InvalidValue("UNTIL", value.to_string()))
+}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut frequency = None;
+        let mut recurrence = Recurrence::new(Frequency::Daily);
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| RecurrenceError::UnsupportedPart(part.to_string()))?;
+            let value = value.trim().to_ascii_uppercase();
+
+            match key.trim().to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(RecurrenceError::UnsupportedFrequency(value)),
+                    })
+                }
+                "INTERVAL" => {
+                    recurrence.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| (1..=MAX_INTERVAL).contains(interval))
+                        .ok_or(RecurrenceError::InvalidValue("INTERVAL", value))?
+                }
+                "BYDAY" => {
+                    recurrence.by_weekday = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<_, _>>()?
+                }
+                "BYMONTHDAY" => {
+                    recurrence.by_month_day = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|day| (1..=31).contains(day))
+                            .ok_or(RecurrenceError::InvalidValue("BYMONTHDAY", value))?,
+                    )
+                }
+                "UNTIL" => recurrence.until = Some(parse_until(&value)?),
+                _ => return Err(RecurrenceError::UnsupportedPart(part.to_string())),
+            }
+        }
+
+        recurrence.frequency = frequency.ok_or(RecurrenceError::MissingFrequency)?;
+        if recurrence.frequency != Frequency::Weekly && !recurrence.by_weekday.is_empty() {
+            return Err(RecurrenceError::ByWeekdayNotWeekly);
+        }
+        if recurrence.frequency != Frequency::Monthly && recurrence.by_month_day.is_some() {
+            return Err(RecurrenceError::ByMonthDayNotMonthly);
+        }
+
+        Ok(recurrence)
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={frequency}")?;
+
+        if self.interval > 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_weekday.is_empty() {
+            let days = self
+                .by_weekday
+                .iter()
+                .map(|day| weekday_code(*day))
+                .collect::<Vec<_>>()
+                .join(",");
+            write!(f, ";BYDAY={days}")?;
+        }
+        if let Some(day) = self.by_month_day {
+            write!(f, ";BYMONTHDAY={day}")?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%S"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+    use chrono_tz::Europe::Paris;
+
+    fn at(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn rule(rule: &str) -> Recurrence {
+        rule.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_rules() {
+        let cases = [
+            ("FREQ=DAILY", Recurrence::new(Frequency::Daily)),
+            (
+                "RRULE:freq=weekly;interval=2;byday=MO,FR",
+                Recurrence {
+                    interval: 2,
+                    by_weekday: vec![Weekday::Mon, Weekday::Fri],
+                    ..Recurrence::new(Frequency::Weekly)
+                },
+            ),
+            (
+                "FREQ=MONTHLY;BYMONTHDAY=31",
+                Recurrence {
+                    by_month_day: Some(31),
+                    ..Recurrence::new(Frequency::Monthly)
+                },
+            ),
+            (
+                "FREQ=YEARLY;UNTIL=20300101",
+                Recurrence {
+                    until: Some(at("2030-01-01 23:59") + Duration::seconds(59)),
+                    ..Recurrence::new(Frequency::Yearly)
+                },
+            ),
+            (
+                "FREQ=DAILY;INTERVAL=1000;UNTIL=20300101T080000Z",
+                Recurrence {
+                    interval: 1000,
+                    until: Some(at("2030-01-01 08:00")),
+                    ..Recurrence::new(Frequency::Daily)
+                },
+            ),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(rule(text), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let cases = [
+            ("", RecurrenceError::MissingFrequency),
+            ("INTERVAL=2", RecurrenceError::MissingFrequency),
+            ("FREQ=HOURLY", RecurrenceError::UnsupportedFrequency("HOURLY".into())),
+            ("FREQ=DAILY;INTERVAL=0", RecurrenceError::InvalidValue("INTERVAL", "0".into())),
+            ("FREQ=DAILY;INTERVAL=1001", RecurrenceError::InvalidValue("INTERVAL", "1001".into())),
+            (
+                "FREQ=DAILY;INTERVAL=4000000000",
+                RecurrenceError::InvalidValue("INTERVAL", "4000000000".into()),
+            ),
+            ("FREQ=WEEKLY;BYDAY=XX", RecurrenceError::InvalidValue("BYDAY", "XX".into())),
+            ("FREQ=DAILY;BYDAY=MO", RecurrenceError::ByWeekdayNotWeekly),
+            ("FREQ=MONTHLY;BYDAY=MO", RecurrenceError::ByWeekdayNotWeekly),
+            ("FREQ=YEARLY;BYDAY=MO,FR", RecurrenceError::ByWeekdayNotWeekly),
+            ("FREQ=DAILY;BYMONTHDAY=1", RecurrenceError::ByMonthDayNotMonthly),
+            ("FREQ=WEEKLY;BYMONTHDAY=15", RecurrenceError::ByMonthDayNotMonthly),
+            ("FREQ=YEARLY;BYMONTHDAY=31", RecurrenceError::ByMonthDayNotMonthly),
+            ("FREQ=MONTHLY;BYDAY=MO;BYMONTHDAY=1", RecurrenceError::ByWeekdayNotWeekly),
+            ("FREQ=MONTHLY;BYMONTHDAY=32", RecurrenceError::InvalidValue("BYMONTHDAY", "32".into())),
+            ("FREQ=DAILY;UNTIL=tomorrow", RecurrenceError::InvalidValue("UNTIL", "TOMORROW".into())),
+            ("FREQ=DAILY;COUNT=3", RecurrenceError::UnsupportedPart("COUNT=3".into())),
+            ("FREQ=DAILY;BYHOUR", RecurrenceError::UnsupportedPart("BYHOUR".into())),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(text.parse::<Recurrence>(), Err(expected), "{text}");
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        for text in [
+            "FREQ=DAILY",
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR",
+            "FREQ=MONTHLY;BYMONTHDAY=15",
+            "FREQ=YEARLY;UNTIL=20300101T000000",
+        ] {
+            assert_eq!(rule(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn finds_next_occurrences() {
+        let cases = [
+            // Rule, dtstart, after, next
+            ("FREQ=DAILY", "2024-05-01 09:00", "2024-05-01 09:00", Some("2024-05-02 09:00")),
+            ("FREQ=DAILY;INTERVAL=3", "2024-05-01 09:00", "2024-05-05 12:00", Some("2024-05-07 09:00")),
+            ("FREQ=DAILY", "2024-05-01 09:00", "2024-04-01 00:00", Some("2024-05-01 09:00")),
+            ("FREQ=WEEKLY;BYDAY=MO,FR", "2024-05-01 09:00", "2024-05-01 09:00", Some("2024-05-03 09:00")),
+            ("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO", "2024-05-06 09:00", "2024-05-06 09:00", Some("2024-05-20 09:00")),
+            // Months without a 31st are skipped
+            ("FREQ=MONTHLY", "2024-01-31 09:00", "2024-01-31 09:00", Some("2024-03-31 09:00")),
+            ("FREQ=MONTHLY", "2024-03-31 09:00", "2024-03-31 09:00", Some("2024-05-31 09:00")),
+            ("FREQ=MONTHLY;BYMONTHDAY=30", "2024-01-30 09:00", "2024-01-30 09:00", Some("2024-03-30 09:00")),
+            ("FREQ=MONTHLY", "2024-12-15 09:00", "2024-12-15 09:00", Some("2025-01-15 09:00")),
+            ("FREQ=YEARLY", "2024-02-29 09:00", "2024-02-29 09:00", Some("2028-02-29 09:00")),
+            ("FREQ=DAILY;UNTIL=20240502", "2024-05-01 09:00", "2024-05-02 09:00", None),
+        ];
+
+        for (text, dtstart, after, next) in cases {
+            assert_eq!(
+                rule(text).next_after(at(dtstart), at(after)),
+                next.map(at),
+                "{text} from {dtstart} after {after}"
+            );
+        }
+    }
+
+    #[test]
+    fn large_intervals_do_not_overflow() {
+        let dtstart = at("2024-05-01 09:00");
+        for frequency in [Frequency::Daily, Frequency::Weekly, Frequency::Monthly, Frequency::Yearly] {
+            let recurrence = Recurrence {
+                interval: u32::MAX,
+                ..Recurrence::new(frequency)
+            };
+            assert_eq!(recurrence.next_after(dtstart, dtstart), None, "{frequency:?}");
+        }
+    }
+
+    #[test]
+    fn keeps_the_local_time_across_dst_changes() {
+        // Paris moved to summer time on 2024-03-31 and back on 2024-10-27
+        let recurrence = rule("FREQ=DAILY");
+        let cases = [
+            ("2024-03-30 09:00", "2024-03-31 09:00", 8, 7),
+            ("2024-10-26 09:00", "2024-10-27 09:00", 7, 8),
+        ];
+
+        for (dtstart, next, utc_before, utc_after) in cases {
+            let next_local = recurrence.next_after(at(dtstart), at(dtstart)).unwrap();
+            assert_eq!(next_local, at(next));
+
+            let utc_hour = |local: NaiveDateTime| {
+                Paris.from_local_datetime(&local).unwrap().naive_utc().hour()
+            };
+            assert_eq!(utc_hour(at(dtstart)), utc_before);
+            assert_eq!(utc_hour(next_local), utc_after);
+        }
+    }
+
+    #[test]
+    fn expands_occurrences_in_a_range() {
+        let occurrences = rule("FREQ=WEEKLY;BYDAY=TU,TH").occurrences(
+            at("2024-04-02 18:00"),
+            at("2024-04-29 00:00"),
+            at("2024-05-09 23:59"),
+        );
+
+        let expected = ["2024-04-30 18:00", "2024-05-02 18:00", "2024-05-07 18:00", "2024-05-09 18:00"];
+        assert_eq!(occurrences, expected.map(at));
+    }
+}
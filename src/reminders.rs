@@ -0,0 +1,164 @@
+use crate::ui::ActionIcon;
+use chrono::{DateTime, Utc};
+use icondata as i;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: u32,
+    pub todo_id: Option<u32>,
+    pub message: Option<String>,
+    /// When the reminder fires, `None` for relative reminders of a todo without a due date.
+    pub fire_at: Option<DateTime<Utc>>,
+    pub fired: bool,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::Reminder;
+    use chrono::{DateTime, Utc};
+    use leptos::ServerFnError;
+
+    pub fn reminder_not_found() -> ServerFnError {
+        ServerFnError::new("Reminder not found.")
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlReminder {
+        pub id: u32,
+        pub todo_id: Option<u32>,
+        pub message: Option<String>,
+        pub fire_at: Option<DateTime<Utc>>,
+        pub fired_at: Option<DateTime<Utc>>,
+    }
+
+    impl SqlReminder {
+        pub fn into_reminder(self) -> Reminder {
+            Reminder {
+                id: self.id,
+                todo_id: self.todo_id,
+                message: self.message,
+                fire_at: self.fire_at,
+                fired: self.fired_at.is_some(),
+            }
+        }
+    }
+}
+
+#[server(GetReminders, "/api")]
+pub async fn get_reminders(todo_id: Option<u32>) -> Result<Vec<Reminder>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(sqlx::query_as::<_, SqlReminder>(
+        "SELECT r.id, r.todo_id, r.message, r.fired_at, s.fire_at
+        FROM reminders r
+        LEFT JOIN reminder_schedule s ON s.id = r.id
+        WHERE r.user_id = ? AND (? IS NULL OR r.todo_id = ?)
+        ORDER BY s.fire_at",
+    )
+    .bind(user.id)
+    .bind(todo_id)
+    .bind(todo_id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlReminder::into_reminder)
+    .collect())
+}
+
+/// Adds a reminder, either at an absolute `remind_at` time (in the user's time zone)
+/// or `offset_minutes` before the todo is due.
+#[server(AddReminder, "/api")]
+pub async fn add_reminder(
+    todo_id: Option<u32>,
+    remind_at: Option<String>,
+    offset_minutes: Option<i64>,
+    message: Option<String>,
+) -> Result<(), ServerFnError> {
+    use crate::{
+        scheduler::SchedulerHandle,
+        todo::ssr::{authorize_todo, parse_datetime, pool, require_user},
+    };
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let remind_at = parse_datetime(remind_at, &user)?;
+    if let Some(todo_id) = todo_id {
+        authorize_todo(todo_id, &user, &pool).await?;
+    }
+    if remind_at.is_none() && (todo_id.is_none() || offset_minutes.is_none()) {
+        return Err(ServerFnError::new(
+            "A reminder needs a time, or a todo and an offset from its due date.",
+        ));
+    }
+
+    sqlx::query(
+        "INSERT INTO reminders (user_id, todo_id, remind_at, offset_minutes, message) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(user.id)
+    .bind(todo_id)
+    .bind(remind_at.map(|remind_at| remind_at.naive_utc()))
+    .bind(offset_minutes.filter(|_| remind_at.is_none()))
+    .bind(message.filter(|message| !message.trim().is_empty()))
+    .execute(&pool)
+    .await?;
+
+    // The new reminder may be due before the scheduler's next run
+    if let Some(scheduler) = use_context::<SchedulerHandle>() {
+        scheduler.wake();
+    }
+
+    Ok(())
+}
+
+#[server(DeleteReminder, "/api")]
+pub async fn delete_reminder(id: u32) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let result = sqlx::query("DELETE FROM reminders WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    match result.rows_affected() {
+        0 => Err(reminder_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Adds a reminder relative to the due date of a todo.
+#[component]
+pub fn ReminderButton(todo_id: u32) -> impl IntoView {
+    let add_reminder = create_server_action::<AddReminder>();
+    let added = move || matches!(add_reminder.value().get(), Some(Ok(())));
+
+    view! {
+        <ActionIcon
+            action=add_reminder
+            icon=i::LuBell
+            class="btn-ghost btn-sm text-accent"
+        >
+            <input type="hidden" name="todo_id" value=todo_id/>
+            <select name="offset_minutes" class="select select-sm select-ghost">
+                <option value="0">"At due time"</option>
+                <option value="10">"10 min before"</option>
+                <option value="60">"1 hour before"</option>
+                <option value="1440">"1 day before"</option>
+            </select>
+            <Show when=added>
+                <span class="text-accent">"Set"</span>
+            </Show>
+        </ActionIcon>
+    }
+}
@@ -0,0 +1,505 @@
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::Notify, task::JoinHandle};
+
+/// A reminder whose time has come, handed to every [`ReminderSink`].
+#[derive(sqlx::FromRow, Serialize, Clone, Debug)]
+pub struct DueReminder {
+    pub id: i64,
+    pub user_id: i64,
+    pub todo_id: Option<u32>,
+    pub todo_title: Option<String>,
+    pub message: Option<String>,
+    pub fire_at: DateTime<Utc>,
+}
+
+impl DueReminder {
+    pub fn title(&self) -> String {
+        match &self.todo_title {
+            Some(title) => format!("Reminder: {title}"),
+            None => "Reminder".to_string(),
+        }
+    }
+}
+
+/// Somewhere fired reminders are delivered to.
+#[async_trait]
+pub trait ReminderSink: Send + Sync {
+    /// Identifies the sink in the deliveries recorded for each reminder, so it must stay the same across restarts.
+    fn name(&self) -> String;
+
+    async fn deliver(&self, reminder: &DueReminder) -> anyhow::Result<()>;
+}
+
+/// Stores reminders as notifications shown in the app.
+pub struct NotificationSink {
+    pool: SqlitePool,
+}
+
+impl NotificationSink {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ReminderSink for NotificationSink {
+    fn name(&self) -> String {
+        "notifications".to_string()
+    }
+
+    async fn deliver(&self, reminder: &DueReminder) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO notifications (user_id, todo_id, title, body) VALUES (?, ?, ?, ?)",
+        )
+        .bind(reminder.user_id)
+        .bind(reminder.todo_id)
+        .bind(reminder.title())
+        .bind(&reminder.message)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Longest a webhook may take to answer before the delivery counts as failed.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// POSTs reminders as JSON to a URL.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: webhook_client(WEBHOOK_TIMEOUT),
+            url: url.into(),
+        }
+    }
+
+    /// Longest the webhook may take to answer.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = webhook_client(timeout);
+        self
+    }
+}
+
+fn webhook_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .expect("HTTP client")
+}
+
+#[async_trait]
+impl ReminderSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook:{}", self.url)
+    }
+
+    async fn deliver(&self, reminder: &DueReminder) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(reminder)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Wakes the scheduler up before its next planned run, e.g. after a reminder was added.
+#[derive(Clone, Debug, Default)]
+pub struct SchedulerHandle(Arc<Notify>);
+
+impl SchedulerHandle {
+    pub fn wake(&self) {
+        self.0.notify_one();
+    }
+}
+
+/// Deliveries tried before a reminder is given up on.
+const MAX_ATTEMPTS: i64 = 5;
+/// Wait before retrying a failed delivery, doubled after each further failure.
+const RETRY_BACKOFF: TimeDelta = TimeDelta::minutes(1);
+
+/// Background worker firing reminders stored in SQLite.
+///
+/// Nothing is kept in memory between runs, so pending reminders are picked up again after a restart.
+/// A reminder is only marked as fired once every sink delivered it. When one fails, the reminder is
+/// retried later on the sinks which failed only, up to [`MAX_ATTEMPTS`] times.
+pub struct Scheduler {
+    pool: SqlitePool,
+    sinks: Vec<Arc<dyn ReminderSink>>,
+    poll_interval: Duration,
+    handle: SchedulerHandle,
+}
+
+impl Scheduler {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            sinks: Vec::new(),
+            poll_interval: Duration::from_secs(30),
+            handle: SchedulerHandle::default(),
+        }
+    }
+
+    pub fn handle(&self) -> SchedulerHandle {
+        self.handle.clone()
+    }
+
+    pub fn with_sink(mut self, sink: impl ReminderSink + 'static) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Longest time to wait before looking for new reminders.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.fire_due(Utc::now()).await {
+                    log::error!("Failed to fire reminders: {e}");
+                }
+                let sleep = tokio::time::sleep(self.sleep_duration().await);
+                tokio::select! {
+                    _ = sleep => {}
+                    _ = self.handle.0.notified() => {}
+                }
+            }
+        })
+    }
+
+    /// Fires every pending reminder due at or before `now`, returning how many were delivered.
+    pub async fn fire_due(&self, now: DateTime<Utc>) -> Result<usize, sqlx::Error> {
+        let due = sqlx::query_as::<_, DueReminder>(
+            "SELECT s.id, s.user_id, s.todo_id, s.todo_title, s.message, s.fire_at
+            FROM reminder_schedule s JOIN reminders r ON r.id = s.id
+            WHERE s.fired_at IS NULL AND s.fire_at <= ? AND (r.retry_at IS NULL OR r.retry_at <= ?)
+            ORDER BY s.fire_at",
+        )
+        .bind(now.naive_utc())
+        .bind(now.naive_utc())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut fired = 0;
+        for reminder in due {
+            let done = sqlx::query_scalar::<_, String>(
+                "SELECT sink FROM reminder_deliveries WHERE reminder_id = ?",
+            )
+            .bind(reminder.id)
+            .fetch_all(&self.pool)
+            .await?;
+
+            let mut delivered = true;
+            for sink in &self.sinks {
+                let name = sink.name();
+                if done.contains(&name) {
+                    continue;
+                }
+                if let Err(e) = sink.deliver(&reminder).await {
+                    log::error!("Failed to deliver reminder {} to {name}: {e}", reminder.id);
+                    delivered = false;
+                    continue;
+                }
+                sqlx::query(
+                    "INSERT INTO reminder_deliveries (reminder_id, sink, delivered_at) VALUES (?, ?, ?)",
+                )
+                .bind(reminder.id)
+                .bind(&name)
+                .bind(now.naive_utc())
+                .execute(&self.pool)
+                .await?;
+            }
+
+            if delivered {
+                sqlx::query("UPDATE reminders SET fired_at = ?, retry_at = NULL WHERE id = ?")
+                    .bind(now.naive_utc())
+                    .bind(reminder.id)
+                    .execute(&self.pool)
+                    .await?;
+                fired += 1;
+                continue;
+            }
+
+            let attempts = sqlx::query_scalar::<_, i64>(
+                "UPDATE reminders SET attempts = attempts + 1 WHERE id = ? RETURNING attempts",
+            )
+            .bind(reminder.id)
+            .fetch_one(&self.pool)
+            .await?;
+            if attempts >= MAX_ATTEMPTS {
+                log::error!("Gave up on reminder {} after {attempts} attempts", reminder.id);
+                sqlx::query("UPDATE reminders SET fired_at = ?, retry_at = NULL WHERE id = ?")
+                    .bind(now.naive_utc())
+                    .bind(reminder.id)
+                    .execute(&self.pool)
+                    .await?;
+            } else {
+                sqlx::query("UPDATE reminders SET retry_at = ? WHERE id = ?")
+                    .bind((now + retry_backoff(attempts)).naive_utc())
+                    .bind(reminder.id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(fired)
+    }
+
+    /// Time until the next pending reminder, capped at the poll interval.
+    async fn sleep_duration(&self) -> Duration {
+        let next = sqlx::query_scalar::<_, DateTime<Utc>>(
+            "SELECT MAX(s.fire_at, COALESCE(r.retry_at, s.fire_at)) AS next
+            FROM reminder_schedule s JOIN reminders r ON r.id = s.id
+            WHERE s.fired_at IS NULL AND s.fire_at IS NOT NULL ORDER BY next LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .ok()
+        .flatten();
+
+        match next.map(|next| (next - Utc::now()).to_std()) {
+            Some(Ok(until_next)) => until_next.min(self.poll_interval),
+            // Already due, retry shortly in case firing it just failed
+            Some(Err(_)) => Duration::from_secs(1),
+            None => self.poll_interval,
+        }
+    }
+}
+
+/// Wait before the next delivery of a reminder which failed `attempts` times.
+fn retry_backoff(attempts: i64) -> TimeDelta {
+    RETRY_BACKOFF * 2i32.pow(attempts.clamp(1, MAX_ATTEMPTS) as u32 - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use std::sync::Mutex;
+
+    /// Payloads received by a local webhook, which answers with the status of `failing`,
+    /// after `delay`.
+    #[derive(Clone, Default)]
+    struct Webhook {
+        payloads: Arc<Mutex<Vec<Value>>>,
+        failing: Arc<Mutex<bool>>,
+        delay: Arc<Mutex<Duration>>,
+    }
+
+    impl Webhook {
+        async fn start(&self) -> String {
+            async fn receive(State(webhook): State<Webhook>, Json(payload): Json<Value>) -> StatusCode {
+                webhook.payloads.lock().unwrap().push(payload);
+                let delay = *webhook.delay.lock().unwrap();
+                tokio::time::sleep(delay).await;
+                match *webhook.failing.lock().unwrap() {
+                    true => StatusCode::INTERNAL_SERVER_ERROR,
+                    false => StatusCode::OK,
+                }
+            }
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let app = Router::new().route("/hook", post(receive)).with_state(self.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            url
+        }
+
+        fn payloads(&self) -> Vec<Value> {
+            self.payloads.lock().unwrap().clone()
+        }
+    }
+
+    fn at(date: &str) -> DateTime<Utc> {
+        chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_utc()
+    }
+
+    /// A todo of a new user due at `due_at`, returning their IDs.
+    async fn due_todo(due_at: &str, pool: &SqlitePool) -> (i64, u32) {
+        let user = testing::user("alice", pool).await;
+        let todo_id = testing::todo("Pay rent", &user, pool).await;
+        sqlx::query("UPDATE todos SET due_at = ? WHERE id = ?")
+            .bind(at(due_at).naive_utc())
+            .bind(todo_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        (user.id, todo_id)
+    }
+
+    /// Adds a reminder `offset_minutes` before the todo is due.
+    async fn relative_reminder(user_id: i64, todo_id: u32, offset_minutes: i64, pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar::<_, i64>(
+            "INSERT INTO reminders (user_id, todo_id, offset_minutes, message)
+            VALUES (?, ?, ?, 'Before noon') RETURNING id",
+        )
+        .bind(user_id)
+        .bind(todo_id)
+        .bind(offset_minutes)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn reminder_state(id: i64, pool: &SqlitePool) -> (Option<DateTime<Utc>>, i64, Option<DateTime<Utc>>) {
+        sqlx::query_as("SELECT fired_at, attempts, retry_at FROM reminders WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn posts_due_reminders_to_the_webhook() {
+        let pool = testing::pool().await;
+        let (user_id, todo_id) = due_todo("2024-05-01 12:00", &pool).await;
+        let reminder_id = relative_reminder(user_id, todo_id, 30, &pool).await;
+        let webhook = Webhook::default();
+        let scheduler = Scheduler::new(pool.clone()).with_sink(WebhookSink::new(webhook.start().await));
+
+        assert_eq!(scheduler.fire_due(at("2024-05-01 11:29")).await.unwrap(), 0);
+        assert!(webhook.payloads().is_empty());
+
+        assert_eq!(scheduler.fire_due(at("2024-05-01 11:30")).await.unwrap(), 1);
+        assert_eq!(
+            webhook.payloads(),
+            [json!({
+                "id": reminder_id,
+                "user_id": user_id,
+                "todo_id": todo_id,
+                "todo_title": "Pay rent",
+                "message": "Before noon",
+                "fire_at": "2024-05-01T11:30:00Z",
+            })]
+        );
+        assert_eq!(reminder_state(reminder_id, &pool).await.0, Some(at("2024-05-01 11:30")));
+
+        // Fired reminders are not sent again
+        assert_eq!(scheduler.fire_due(at("2024-05-01 12:00")).await.unwrap(), 0);
+        assert_eq!(webhook.payloads().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_failed_deliveries_with_a_backoff() {
+        let pool = testing::pool().await;
+        let (user_id, todo_id) = due_todo("2024-05-01 12:00", &pool).await;
+        let reminder_id = relative_reminder(user_id, todo_id, 0, &pool).await;
+        let webhook = Webhook::default();
+        *webhook.failing.lock().unwrap() = true;
+        let scheduler = Scheduler::new(pool.clone()).with_sink(WebhookSink::new(webhook.start().await));
+
+        assert_eq!(scheduler.fire_due(at("2024-05-01 12:00")).await.unwrap(), 0);
+        assert_eq!(reminder_state(reminder_id, &pool).await, (None, 1, Some(at("2024-05-01 12:01"))));
+
+        // Not retried before the backoff is over
+        assert_eq!(scheduler.fire_due(at("2024-05-01 12:00")).await.unwrap(), 0);
+        assert_eq!(webhook.payloads().len(), 1);
+
+        assert_eq!(scheduler.fire_due(at("2024-05-01 12:01")).await.unwrap(), 0);
+        assert_eq!(reminder_state(reminder_id, &pool).await, (None, 2, Some(at("2024-05-01 12:03"))));
+
+        *webhook.failing.lock().unwrap() = false;
+        assert_eq!(scheduler.fire_due(at("2024-05-01 12:03")).await.unwrap(), 1);
+        assert_eq!(reminder_state(reminder_id, &pool).await, (Some(at("2024-05-01 12:03")), 2, None));
+        assert_eq!(webhook.payloads().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_too_many_attempts() {
+        let pool = testing::pool().await;
+        let (user_id, todo_id) = due_todo("2024-05-01 12:00", &pool).await;
+        let reminder_id = relative_reminder(user_id, todo_id, 0, &pool).await;
+        let webhook = Webhook::default();
+        *webhook.failing.lock().unwrap() = true;
+        let scheduler = Scheduler::new(pool.clone()).with_sink(WebhookSink::new(webhook.start().await));
+
+        let mut now = at("2024-05-01 12:00");
+        for _ in 0..MAX_ATTEMPTS {
+            scheduler.fire_due(now).await.unwrap();
+            now += TimeDelta::days(1);
+        }
+
+        let (fired_at, attempts, _) = reminder_state(reminder_id, &pool).await;
+        assert!(fired_at.is_some());
+        assert_eq!(attempts, MAX_ATTEMPTS);
+        assert_eq!(webhook.payloads().len(), MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn negative_offsets_fire_after_the_due_date() {
+        let pool = testing::pool().await;
+        let (user_id, todo_id) = due_todo("2024-05-01 12:00", &pool).await;
+        let before = relative_reminder(user_id, todo_id, 90, &pool).await;
+        let after = relative_reminder(user_id, todo_id, -45, &pool).await;
+
+        let fire_at = |id: i64| {
+            sqlx::query_scalar::<_, Option<DateTime<Utc>>>("SELECT fire_at FROM reminder_schedule WHERE id = ?")
+                .bind(id)
+                .fetch_one(&pool)
+        };
+        assert_eq!(fire_at(before).await.unwrap(), Some(at("2024-05-01 10:30")));
+        assert_eq!(fire_at(after).await.unwrap(), Some(at("2024-05-01 12:45")));
+    }
+
+    #[tokio::test]
+    async fn retries_only_the_sinks_which_failed() {
+        let pool = testing::pool().await;
+        let (user_id, todo_id) = due_todo("2024-05-01 12:00", &pool).await;
+        let reminder_id = relative_reminder(user_id, todo_id, 0, &pool).await;
+        let webhook = Webhook::default();
+        *webhook.failing.lock().unwrap() = true;
+        let scheduler = Scheduler::new(pool.clone())
+            .with_sink(NotificationSink::new(pool.clone()))
+            .with_sink(WebhookSink::new(webhook.start().await));
+
+        let mut now = at("2024-05-01 12:00");
+        for _ in 0..MAX_ATTEMPTS {
+            scheduler.fire_due(now).await.unwrap();
+            now += TimeDelta::days(1);
+        }
+
+        let notifications = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM notifications")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(notifications, 1);
+        assert_eq!(webhook.payloads().len(), MAX_ATTEMPTS as usize);
+        assert_eq!(reminder_state(reminder_id, &pool).await.1, MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn slow_webhooks_time_out() {
+        let pool = testing::pool().await;
+        let (user_id, todo_id) = due_todo("2024-05-01 12:00", &pool).await;
+        let reminder_id = relative_reminder(user_id, todo_id, 0, &pool).await;
+        let webhook = Webhook::default();
+        *webhook.delay.lock().unwrap() = Duration::from_secs(60);
+        let sink = WebhookSink::new(webhook.start().await).with_timeout(Duration::from_millis(100));
+        let scheduler = Scheduler::new(pool.clone()).with_sink(sink);
+
+        let fired = tokio::time::timeout(Duration::from_secs(5), scheduler.fire_due(at("2024-05-01 12:00")))
+            .await
+            .expect("the webhook timeout to cut the delivery short");
+        assert_eq!(fired.unwrap(), 0);
+        assert_eq!(reminder_state(reminder_id, &pool).await, (None, 1, Some(at("2024-05-01 12:01"))));
+    }
+}
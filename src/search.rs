@@ -0,0 +1,332 @@
+use crate::{error_template::ErrorTemplate, ui::Container};
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+/// A piece of a search snippet, highlighted when it matched the query.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub todo_id: u32,
+    pub title: String,
+    pub completed: bool,
+    pub project_id: Option<u32>,
+    pub project: Option<String>,
+    /// The best matching part of the todo, with the matched terms highlighted.
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// Most results returned by a search.
+pub const MAX_SEARCH_RESULTS: u32 = 50;
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{SearchResult, SnippetPart, MAX_SEARCH_RESULTS};
+    use crate::auth::User;
+    use sqlx::SqlitePool;
+
+    /// Marks around matched terms in snippets, which can't appear in titles typed by users.
+    pub const HIGHLIGHT_START: char = '\u{2}';
+    pub const HIGHLIGHT_END: char = '\u{3}';
+
+    /// Turns what the user typed into an FTS5 query matching todos containing every word,
+    /// the words being prefixes so results show up while typing.
+    ///
+    /// Every word is quoted, so FTS5 operators typed by the user are searched as plain text.
+    pub fn match_query(input: &str) -> Option<String> {
+        let terms: Vec<String> = input
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{word}\"*"))
+            .collect();
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    pub fn parse_snippet(snippet: &str) -> Vec<SnippetPart> {
+        let mut parts = Vec::new();
+        let mut highlighted = false;
+        for (i, text) in snippet.split([HIGHLIGHT_START, HIGHLIGHT_END]).enumerate() {
+            // Delimiters alternate, so every other part is highlighted
+            if i > 0 {
+                highlighted = !highlighted;
+            }
+            if !text.is_empty() {
+                parts.push(SnippetPart {
+                    text: text.to_string(),
+                    highlighted,
+                });
+            }
+        }
+        parts
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlSearchResult {
+        pub todo_id: u32,
+        pub title: String,
+        pub completed: bool,
+        pub project_id: Option<u32>,
+        pub project: Option<String>,
+        pub snippet: String,
+    }
+
+    /// Todos of `user` matching what they typed, skipping trashed ones and subtasks of those.
+    ///
+    /// Snippets come from the column matching best, be it the title, the notes or the project.
+    pub async fn search(
+        user: &User,
+        input: &str,
+        pool: &SqlitePool,
+    ) -> Result<Vec<SearchResult>, sqlx::Error> {
+        let Some(query) = match_query(input) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(sqlx::query_as::<_, SqlSearchResult>(
+            "SELECT t.id AS todo_id, t.title, t.completed, t.project_id, p.name AS project,
+            snippet(todos_fts, -1, ?, ?, '…', 16) AS snippet
+            FROM todos_fts
+            JOIN todos t ON t.id = todos_fts.rowid
+            LEFT JOIN projects p ON p.id = t.project_id
+            WHERE todos_fts MATCH ? AND t.user_id = ? AND t.deleted_at IS NULL
+            ORDER BY todos_fts.rank
+            LIMIT ?",
+        )
+        .bind(HIGHLIGHT_START.to_string())
+        .bind(HIGHLIGHT_END.to_string())
+        .bind(query)
+        .bind(user.id)
+        .bind(MAX_SEARCH_RESULTS)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(SqlSearchResult::into_search_result)
+        .collect())
+    }
+
+    impl SqlSearchResult {
+        pub fn into_search_result(self) -> SearchResult {
+            SearchResult {
+                todo_id: self.todo_id,
+                title: self.title,
+                completed: self.completed,
+                project_id: self.project_id,
+                project: self.project,
+                snippet: parse_snippet(&self.snippet),
+            }
+        }
+    }
+}
+
+/// Searches the todos of the current user by title, notes and project name, best matches first.
+#[server(SearchTodos, "/api")]
+pub async fn search_todos(query: String) -> Result<Vec<SearchResult>, ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(ssr::search(&user, &query, &pool).await?)
+}
+
+/// Search box of the navbar, leading to the search page.
+#[component]
+pub fn SearchBox() -> impl IntoView {
+    let query = use_query_map();
+    let value = move || query.with(|query| query.get("q").cloned().unwrap_or_default());
+
+    view! {
+        <Form method="GET" action="/search" class="flex-none">
+            <input
+                type="search"
+                name="q"
+                placeholder="Search"
+                class="input input-bordered w-48 md:w-64"
+                prop:value=value
+            />
+        </Form>
+    }
+}
+
+#[component]
+pub fn Search() -> impl IntoView {
+    let query = use_query_map();
+    let q = move || query.with(|query| query.get("q").cloned().unwrap_or_default());
+
+    let results = create_resource(q, search_todos);
+
+    view! {
+        <Container>
+            <h2 class="text-2xl font-bold mb-4">"Search"</h2>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        results
+                            .get()
+                            .map(move |results| match results {
+                                Err(e) => {
+                                    view! {
+                                        <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(results) => {
+                                    if results.is_empty() {
+                                        view! { <p>"No tasks were found."</p> }.into_view()
+                                    } else {
+                                        view! {
+                                            <ul class="overflow-auto space-y-2">
+                                                {results
+                                                    .into_iter()
+                                                    .map(|result| {
+                                                        view! {
+                                                            <li>
+                                                                <SearchResultRow result/>
+                                                            </li>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </ul>
+                                        }
+                                            .into_view()
+                                    }
+                                }
+                            })
+                            .unwrap_or_default()
+                    }}
+
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+#[component]
+pub fn SearchResultRow(result: SearchResult) -> impl IntoView {
+    let snippet = result
+        .snippet
+        .into_iter()
+        .map(|part| match part.highlighted {
+            true => view! { <mark class="bg-accent text-accent-content rounded">{part.text}</mark> }
+                .into_view(),
+            false => part.text.into_view(),
+        })
+        .collect_view();
+
+    view! {
+        <div class="min-h-12 flex items-center gap-4 px-3 bg-base-100 rounded-xl">
+            <input type="checkbox" class="checkbox checkbox-accent" checked=result.completed disabled/>
+            <div class="flex-1">
+                <p class="text-xl">{result.title}</p>
+                <p class="text-sm opacity-75">{snippet}</p>
+            </div>
+            {result
+                .project_id
+                .zip(result.project)
+                .map(|(id, name)| {
+                    view! {
+                        <A href=format!("/projects/{id}") class="link link-hover text-primary">
+                            {name}
+                        </A>
+                    }
+                })}
+
+        </div>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::{ssr::*, SnippetPart};
+    use crate::testing;
+    use chrono::Utc;
+
+    #[test]
+    fn quotes_every_word_of_the_query() {
+        let cases = [
+            ("milk", Some(r#""milk"*"#)),
+            ("  oat  milk ", Some(r#""oat"* "milk"*"#)),
+            (r#"say "hi""#, Some(r#""say"* "hi"*"#)),
+            (r#"""#, None),
+            ("mi*lk", Some(r#""mi"* "lk"*"#)),
+            ("*", None),
+            ("milk AND eggs", Some(r#""milk"* "AND"* "eggs"*"#)),
+            ("NOT milk OR NEAR(a b)", Some(r#""NOT"* "milk"* "OR"* "NEAR"* "a"* "b"*"#)),
+            ("title:milk -eggs ^bread", Some(r#""title"* "milk"* "eggs"* "bread"*"#)),
+            ("café 東京", Some(r#""café"* "東京"*"#)),
+            ("", None),
+            ("  - + ", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(match_query(input).as_deref(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn splits_snippets_on_highlights() {
+        let part = |text: &str, highlighted| SnippetPart {
+            text: text.to_string(),
+            highlighted,
+        };
+        let cases = [
+            ("plain".to_string(), vec![part("plain", false)]),
+            (
+                format!("buy {HIGHLIGHT_START}milk{HIGHLIGHT_END} now"),
+                vec![part("buy ", false), part("milk", true), part(" now", false)],
+            ),
+            (
+                format!("{HIGHLIGHT_START}oat{HIGHLIGHT_END} {HIGHLIGHT_START}milk{HIGHLIGHT_END}"),
+                vec![part("oat", true), part(" ", false), part("milk", true)],
+            ),
+            (String::new(), vec![]),
+        ];
+
+        for (snippet, expected) in cases {
+            assert_eq!(parse_snippet(&snippet), expected, "{snippet:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_titles_and_notes_of_live_todos() {
+        let pool = testing::pool().await;
+        let alice = testing::user("alice", &pool).await;
+        let bob = testing::user("bob", &pool).await;
+        let groceries = testing::todo("Groceries", &alice, &pool).await;
+        let trip = testing::todo("Plan the trip", &alice, &pool).await;
+        let packing = testing::subtask("Pack the milk frother", trip, &alice, &pool).await;
+        testing::todo("Milk", &bob, &pool).await;
+        sqlx::query("UPDATE todos SET notes = 'Oat milk, eggs and bread' WHERE id = ?")
+            .bind(groceries)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let results = search(&alice, "milk", &pool).await.unwrap();
+        let mut ids: Vec<_> = results.iter().map(|result| result.todo_id).collect();
+        ids.sort();
+        assert_eq!(ids, [groceries, packing]);
+
+        let notes = results.iter().find(|result| result.todo_id == groceries).unwrap();
+        assert_eq!(notes.title, "Groceries");
+        assert!(notes
+            .snippet
+            .iter()
+            .any(|part| part.highlighted && part.text == "milk"));
+        assert!(notes.snippet.iter().any(|part| part.text.contains("eggs")));
+
+        // Trashing the trip hides its subtasks too
+        let mut conn = pool.acquire().await.unwrap();
+        crate::trash::ssr::trash_todo(&mut conn, &alice, trip, Utc::now()).await.unwrap();
+        drop(conn);
+        let results = search(&alice, "milk", &pool).await.unwrap();
+        assert_eq!(results.iter().map(|result| result.todo_id).collect::<Vec<_>>(), [groceries]);
+    }
+}
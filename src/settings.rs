@@ -0,0 +1,476 @@
+use crate::{
+    auth::{User, UserResource},
+    error_template::ErrorTemplate,
+    tokens::AccessTokens,
+    two_factor::TwoFactorSettings,
+    ui::Container,
+    validation::error_message,
+};
+use chrono::Weekday;
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+/// How dates are shown, remembered for each user.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateFormat {
+    /// 2024-09-24
+    #[default]
+    Iso,
+    /// 09/24/2024
+    Us,
+    /// 24/09/2024
+    European,
+}
+
+impl DateFormat {
+    pub const ALL: [DateFormat; 3] = [DateFormat::Iso, DateFormat::Us, DateFormat::European];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateFormat::Iso => "iso",
+            DateFormat::Us => "us",
+            DateFormat::European => "european",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        DateFormat::ALL.into_iter().find(|format| format.as_str() == value)
+    }
+
+    /// `chrono` format string of the dates.
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            DateFormat::Iso => "%Y-%m-%d",
+            DateFormat::Us => "%m/%d/%Y",
+            DateFormat::European => "%d/%m/%Y",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DateFormat::Iso => "2024-09-24",
+            DateFormat::Us => "09/24/2024",
+            DateFormat::European => "24/09/2024",
+        }
+    }
+}
+
+/// Color theme of the app, remembered for each user.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    /// Follows the preference of the browser.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Theme::ALL.into_iter().find(|theme| theme.as_str() == value)
+    }
+
+    /// The daisyUI theme to set on the page, if any.
+    pub fn data_theme(&self) -> Option<&'static str> {
+        match self {
+            Theme::System => None,
+            Theme::Light => Some("light"),
+            Theme::Dark => Some("dark"),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::System => "Same as the system",
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        }
+    }
+}
+
+/// Days weeks can start on.
+pub const WEEK_STARTS: [Weekday; 3] = [Weekday::Mon, Weekday::Sat, Weekday::Sun];
+
+fn weekday_label(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+#[server(ChangeUsername, "/api")]
+pub async fn change_username(username: String) -> Result<(), ServerFnError> {
+    use crate::{
+        todo::ssr::{auth, pool, require_user},
+        validation::{
+            ssr::{check_username_free, policy, username_taken},
+            FieldErrors,
+        },
+    };
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let auth = auth()?;
+
+    let mut errors = FieldErrors::new();
+    policy().check_username(&username, &mut errors);
+    if errors.is_empty() {
+        check_username_free(&username, Some(user.id), &pool, &mut errors).await?;
+    }
+    errors.into_result()?;
+
+    sqlx::query("UPDATE users SET username = ? WHERE id = ?")
+        .bind(username)
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .map_err(username_taken)?;
+
+    // The session caches the user, drop it so the change is picked up
+    auth.cache_clear_user(user.id);
+
+    Ok(())
+}
+
+/// Changes the password of the current user, once the old one is confirmed.
+#[server(ChangePassword, "/api")]
+pub async fn change_password(
+    old_password: String,
+    new_password: String,
+    new_password_confirmation: String,
+) -> Result<(), ServerFnError> {
+    use crate::{
+        auth::ssr::{hash, verify_password, DEFAULT_COST},
+        todo::ssr::{pool, require_user},
+        validation::{ssr::policy, FieldErrors},
+    };
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    verify_password(&user, &old_password, &pool).await?;
+    let mut errors = FieldErrors::new();
+    policy().check_password("new_password", &new_password, &user.username, &mut errors);
+    if new_password != new_password_confirmation {
+        errors.add("new_password_confirmation", "Passwords did not match.");
+    }
+    errors.into_result()?;
+
+    sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+        .bind(hash(new_password, DEFAULT_COST)?)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    Ok(())
+}
+
+#[server(UpdatePreferences, "/api")]
+pub async fn update_preferences(
+    timezone: String,
+    week_start: u8,
+    date_format: DateFormat,
+    theme: Theme,
+) -> Result<(), ServerFnError> {
+    use crate::todo::ssr::{auth, pool, require_user};
+    use chrono_tz::Tz;
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let auth = auth()?;
+
+    timezone
+        .parse::<Tz>()
+        .map_err(|_| ServerFnError::new(format!("Unknown time zone: {timezone}")))?;
+    let week_start = Weekday::try_from(week_start)
+        .ok()
+        .filter(|day| WEEK_STARTS.contains(day))
+        .ok_or_else(|| ServerFnError::new("Invalid week start."))?;
+
+    sqlx::query(
+        "UPDATE users SET timezone = ?, week_start = ?, date_format = ?, theme = ? WHERE id = ?",
+    )
+    .bind(timezone)
+    .bind(week_start.num_days_from_monday())
+    .bind(date_format.as_str())
+    .bind(theme.as_str())
+    .bind(user.id)
+    .execute(&pool)
+    .await?;
+
+    auth.cache_clear_user(user.id);
+
+    Ok(())
+}
+
+/// Deletes the current user along with everything they own, once their password is confirmed.
+#[server(DeleteAccount, "/api")]
+pub async fn delete_account(password: String) -> Result<(), ServerFnError> {
+    use crate::{
+        auth::ssr::verify_password,
+        todo::ssr::{auth, pool, require_user},
+    };
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let auth = auth()?;
+
+    verify_password(&user, &password, &pool).await?;
+
+    // Todos, habits, projects, tokens... all go with the user through their foreign keys
+    sqlx::query("DELETE FROM users WHERE id = ?")
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    auth.cache_clear_user(user.id);
+    auth.logout_user();
+    leptos_axum::redirect("/");
+
+    Ok(())
+}
+
+/// Shows how the last submission of a settings form went.
+fn action_status<I: 'static, O: Clone + 'static>(
+    action: Action<I, Result<O, ServerFnError>>,
+    success: &'static str,
+) -> impl Fn() -> Option<View> {
+    move || {
+        action.value().get().map(|result| match result {
+            Ok(_) => view! { <p class="text-success">{success}</p> }.into_view(),
+            Err(e) => view! { <p class="text-error">{error_message(&e)}</p> }.into_view(),
+        })
+    }
+}
+
+#[component]
+pub fn Settings() -> impl IntoView {
+    let user = expect_context::<UserResource>();
+
+    view! {
+        <Container>
+            <h2 class="text-2xl font-bold mb-4">"Settings"</h2>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        user.get()
+                            .map(|user| match user {
+                                Err(e) => {
+                                    view! {
+                                        <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(None) => view! { <p>"Log in to change your settings."</p> }.into_view(),
+                                Ok(Some(user)) => {
+                                    view! {
+                                        <AccountSettings user=user.clone()/>
+                                        <PreferenceSettings user=user.clone()/>
+                                        <TwoFactorSettings/>
+                                        <AccessTokens user/>
+                                        <DeleteAccountSettings/>
+                                    }
+                                        .into_view()
+                                }
+                            })
+                    }}
+
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+/// Username and password of the user.
+#[component]
+pub fn AccountSettings(user: User) -> impl IntoView {
+    let user_resource = expect_context::<UserResource>();
+    let change_username = create_server_action::<ChangeUsername>();
+    let change_password = create_server_action::<ChangePassword>();
+
+    // The header shows the username, so reload the user once it changed
+    create_effect(move |_| {
+        if let Some(Ok(())) = change_username.value().get() {
+            user_resource.refetch();
+        }
+    });
+
+    view! {
+        <section class="p-4 bg-base-100 rounded-xl mb-4">
+            <h3 class="text-xl font-bold mb-4">"Account"</h3>
+            <ActionForm action=change_username class="flex flex-wrap items-center gap-4 mb-2">
+                <input
+                    type="text"
+                    name="username"
+                    value=user.username
+                    maxlength=32
+                    class="input input-bordered flex-1"
+                />
+                <button type="submit" class="btn btn-primary">"Change username"</button>
+            </ActionForm>
+            {action_status(change_username, "Username changed.")}
+            <ActionForm action=change_password class="flex flex-wrap items-center gap-4 mt-4 mb-2">
+                <input
+                    type="password"
+                    name="old_password"
+                    placeholder="Current password"
+                    class="input input-bordered flex-1"
+                />
+                <input
+                    type="password"
+                    name="new_password"
+                    placeholder="New password"
+                    class="input input-bordered flex-1"
+                />
+                <input
+                    type="password"
+                    name="new_password_confirmation"
+                    placeholder="New password again"
+                    class="input input-bordered flex-1"
+                />
+                <button type="submit" class="btn btn-primary">"Change password"</button>
+            </ActionForm>
+            {action_status(change_password, "Password changed.")}
+        </section>
+    }
+}
+
+/// Time zone, week start, date format and theme of the user.
+#[component]
+pub fn PreferenceSettings(user: User) -> impl IntoView {
+    let user_resource = expect_context::<UserResource>();
+    let update_preferences = create_server_action::<UpdatePreferences>();
+
+    create_effect(move |_| {
+        if let Some(Ok(())) = update_preferences.value().get() {
+            user_resource.refetch();
+        }
+    });
+
+    view! {
+        <section class="p-4 bg-base-100 rounded-xl mb-4">
+            <h3 class="text-xl font-bold mb-4">"Preferences"</h3>
+            <ActionForm action=update_preferences class="grid grid-cols-[auto_1fr] items-center gap-4 mb-2">
+                <span>"Time zone"</span>
+                <select name="timezone" class="select select-bordered">
+                    {chrono_tz::TZ_VARIANTS
+                        .iter()
+                        .map(|tz| {
+                            let name = tz.name();
+                            view! {
+                                <option value=name selected=name == user.timezone>
+                                    {name}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                <span>"Weeks start on"</span>
+                <select name="week_start" class="select select-bordered">
+                    {WEEK_STARTS
+                        .into_iter()
+                        .map(|day| {
+                            view! {
+                                <option
+                                    value=day.num_days_from_monday()
+                                    selected=day == user.week_start
+                                >
+                                    {weekday_label(day)}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                <span>"Dates"</span>
+                <select name="date_format" class="select select-bordered">
+                    {DateFormat::ALL
+                        .into_iter()
+                        .map(|format| {
+                            view! {
+                                <option
+                                    value=format!("{format:?}")
+                                    selected=format == user.date_format
+                                >
+                                    {format.label()}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                <span>"Theme"</span>
+                <select name="theme" class="select select-bordered">
+                    {Theme::ALL
+                        .into_iter()
+                        .map(|theme| {
+                            view! {
+                                <option value=format!("{theme:?}") selected=theme == user.theme>
+                                    {theme.label()}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                <button type="submit" class="btn btn-primary col-start-2 justify-self-start">
+                    "Save preferences"
+                </button>
+            </ActionForm>
+            {action_status(update_preferences, "Preferences saved.")}
+        </section>
+    }
+}
+
+/// Deletes the account, after asking for the password again.
+#[component]
+pub fn DeleteAccountSettings() -> impl IntoView {
+    let user_resource = expect_context::<UserResource>();
+    let delete_account = create_server_action::<DeleteAccount>();
+
+    create_effect(move |_| {
+        if let Some(Ok(())) = delete_account.value().get() {
+            user_resource.refetch();
+        }
+    });
+
+    view! {
+        <section class="p-4 bg-base-100 rounded-xl mb-4 border border-error">
+            <h3 class="text-xl font-bold text-error">"Delete account"</h3>
+            <p class="opacity-75 mb-4">
+                "Your todos, projects, habits and tokens will be deleted for good."
+            </p>
+            <ActionForm action=delete_account class="flex flex-wrap items-center gap-4 mb-2">
+                <input
+                    type="password"
+                    name="password"
+                    placeholder="Password"
+                    class="input input-bordered flex-1"
+                />
+                <button type="submit" class="btn btn-error">"Delete my account"</button>
+            </ActionForm>
+            {move || {
+                delete_account
+                    .value()
+                    .get()
+                    .and_then(Result::err)
+                    .map(|e| view! { <p class="text-error">{error_message(&e)}</p> })
+            }}
+        </section>
+    }
+}
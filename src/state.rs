@@ -1,3 +1,4 @@
+use crate::{scheduler::SchedulerHandle, validation::ssr::ValidationPolicy};
 use axum::extract::FromRef;
 use leptos::LeptosOptions;
 use leptos_router::RouteListing;
@@ -10,4 +11,6 @@ pub struct AppState {
     pub leptos_options: LeptosOptions,
     pub pool: SqlitePool,
     pub routes: Vec<RouteListing>,
+    pub scheduler: SchedulerHandle,
+    pub validation: ValidationPolicy,
 }
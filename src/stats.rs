@@ -0,0 +1,569 @@
+use crate::{error_template::ErrorTemplate, habits::HabitPeriod, ui::Container};
+use chrono::{Duration, Months, NaiveDate, Weekday};
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+/// Todos created and completed, and habit check-ins made, during a period.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityBucket {
+    /// First day of the period, in the user's time zone.
+    pub start: NaiveDate,
+    pub created: u32,
+    pub completed: u32,
+    pub checkins: u32,
+}
+
+/// Activity of the current user over the last periods, oldest first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Activity {
+    pub period: HabitPeriod,
+    pub buckets: Vec<ActivityBucket>,
+}
+
+/// How many of the todos of a project or tag are done.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionRate {
+    pub name: String,
+    /// Color of the tag, projects have none.
+    pub color: Option<String>,
+    pub total: u32,
+    pub completed: u32,
+    /// Open todos past their due date.
+    pub overdue: u32,
+}
+
+impl CompletionRate {
+    /// Share of the todos which are done, from 0 to 1.
+    pub fn rate(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => self.completed as f64 / total as f64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompletionStats {
+    pub open: u32,
+    pub completed: u32,
+    pub overdue: u32,
+    /// Average time between creating and completing a todo, in seconds.
+    /// Unknown until a todo has been completed since completion times are recorded.
+    pub average_completion: Option<i64>,
+    pub by_project: Vec<CompletionRate>,
+    pub by_tag: Vec<CompletionRate>,
+}
+
+/// Number of periods shown in the activity chart.
+fn bucket_count(period: HabitPeriod) -> usize {
+    match period {
+        HabitPeriod::Day => 30,
+        HabitPeriod::Week | HabitPeriod::Month => 12,
+    }
+}
+
+/// First day of the period preceding the one starting at `start`.
+fn previous(period: HabitPeriod, start: NaiveDate) -> NaiveDate {
+    match period {
+        HabitPeriod::Day => start - Duration::days(1),
+        HabitPeriod::Week => start - Duration::weeks(1),
+        HabitPeriod::Month => start.checked_sub_months(Months::new(1)).unwrap_or(start),
+    }
+}
+
+/// Start of each of the last periods up to the one containing `today`, oldest first.
+pub fn period_starts(period: HabitPeriod, today: NaiveDate, week_start: Weekday) -> Vec<NaiveDate> {
+    let mut start = period.start_of(today, week_start);
+    let mut starts = vec![start];
+    for _ in 1..bucket_count(period) {
+        start = previous(period, start);
+        starts.push(start);
+    }
+    starts.reverse();
+    starts
+}
+
+/// Rounds a duration in seconds to its two largest units, e.g. "2 d 5 h".
+pub fn format_duration(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{minutes} min"),
+        (0, _) => format!("{hours} h {minutes} min"),
+        _ => format!("{days} d {hours} h"),
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::CompletionRate;
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlCompletionRate {
+        pub name: Option<String>,
+        pub color: Option<String>,
+        pub total: u32,
+        pub completed: u32,
+        pub overdue: u32,
+    }
+
+    impl SqlCompletionRate {
+        pub fn into_completion_rate(self) -> CompletionRate {
+            CompletionRate {
+                name: self.name.unwrap_or_else(|| "No project".to_string()),
+                color: self.color,
+                total: self.total,
+                completed: self.completed,
+                overdue: self.overdue,
+            }
+        }
+    }
+}
+
+/// Counts the todos created and completed by the current user, along with habit check-ins,
+/// over the last days, weeks or months.
+#[server(GetActivity, "/api")]
+pub async fn get_activity(period: HabitPeriod) -> Result<Activity, ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+    use chrono::{DateTime, Utc};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let starts = period_starts(period, user.local_today(), user.week_start);
+    let mut buckets: Vec<_> = starts
+        .iter()
+        .map(|&start| ActivityBucket {
+            start,
+            created: 0,
+            completed: 0,
+            checkins: 0,
+        })
+        .collect();
+    let first = starts[0];
+    let since = user
+        .from_local(first.and_hms_opt(0, 0, 0).unwrap())
+        .ok_or_else(|| ServerFnError::new("Invalid time zone."))?;
+    // Index of the period containing `date`, if shown
+    let bucket = |date: NaiveDate| starts.partition_point(|&start| start <= date).checked_sub(1);
+
+    let todos = sqlx::query_as::<_, (DateTime<Utc>, Option<DateTime<Utc>>)>(
+        "SELECT created_at, completed_at FROM todos
+        WHERE user_id = ? AND deleted_at IS NULL AND (created_at >= ? OR completed_at >= ?)",
+    )
+    .bind(user.id)
+    .bind(since.naive_utc())
+    .bind(since.naive_utc())
+    .fetch_all(&pool)
+    .await?;
+    for (created_at, completed_at) in todos {
+        if let Some(i) = bucket(user.to_local(created_at).date()) {
+            buckets[i].created += 1;
+        }
+        if let Some(i) = completed_at.and_then(|completed_at| bucket(user.to_local(completed_at).date())) {
+            buckets[i].completed += 1;
+        }
+    }
+
+    let checkins = sqlx::query_scalar::<_, NaiveDate>(
+        "SELECT c.day FROM habit_checkins c JOIN habits h ON h.id = c.habit_id
+        WHERE h.user_id = ? AND c.day >= ?",
+    )
+    .bind(user.id)
+    .bind(first)
+    .fetch_all(&pool)
+    .await?;
+    for day in checkins {
+        if let Some(i) = bucket(day) {
+            buckets[i].checkins += 1;
+        }
+    }
+
+    Ok(Activity { period, buckets })
+}
+
+/// Completion and overdue counts of the todos of the current user, overall and by project and tag.
+#[server(GetCompletionStats, "/api")]
+pub async fn get_completion_stats() -> Result<CompletionStats, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+    use chrono::Utc;
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let now = Utc::now().naive_utc();
+
+    let (open, completed, overdue) = sqlx::query_as::<_, (u32, u32, u32)>(
+        "SELECT COALESCE(SUM(NOT completed), 0), COALESCE(SUM(completed), 0),
+            COALESCE(SUM(NOT completed AND due_at < ?), 0)
+        FROM todos WHERE user_id = ? AND deleted_at IS NULL",
+    )
+    .bind(now)
+    .bind(user.id)
+    .fetch_one(&pool)
+    .await?;
+
+    let average_completion = sqlx::query_scalar::<_, Option<f64>>(
+        "SELECT AVG((julianday(completed_at) - julianday(created_at)) * 86400) FROM todos
+        WHERE user_id = ? AND deleted_at IS NULL AND completed AND completed_at IS NOT NULL",
+    )
+    .bind(user.id)
+    .fetch_one(&pool)
+    .await?
+    .map(|seconds| seconds.round() as i64);
+
+    let by_project = sqlx::query_as::<_, SqlCompletionRate>(
+        "SELECT p.name, NULL AS color, COUNT(*) AS total, COALESCE(SUM(t.completed), 0) AS completed,
+            COALESCE(SUM(NOT t.completed AND t.due_at < ?), 0) AS overdue
+        FROM todos t
+        LEFT JOIN projects p ON p.id = t.project_id
+        WHERE t.user_id = ? AND t.deleted_at IS NULL
+        GROUP BY t.project_id
+        ORDER BY p.name IS NULL, p.name COLLATE NOCASE",
+    )
+    .bind(now)
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlCompletionRate::into_completion_rate)
+    .collect();
+
+    let by_tag = sqlx::query_as::<_, SqlCompletionRate>(
+        "SELECT g.name, g.color, COUNT(*) AS total, COALESCE(SUM(t.completed), 0) AS completed,
+            COALESCE(SUM(NOT t.completed AND t.due_at < ?), 0) AS overdue
+        FROM todo_tags tt
+        JOIN tags g ON g.id = tt.tag_id
+        JOIN todos t ON t.id = tt.todo_id AND t.deleted_at IS NULL
+        WHERE g.user_id = ?
+        GROUP BY g.id
+        ORDER BY g.name COLLATE NOCASE",
+    )
+    .bind(now)
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlCompletionRate::into_completion_rate)
+    .collect();
+
+    Ok(CompletionStats {
+        open,
+        completed,
+        overdue,
+        average_completion,
+        by_project,
+        by_tag,
+    })
+}
+
+#[component]
+pub fn Stats() -> impl IntoView {
+    let query = use_query_map();
+    let period = move || {
+        query.with(|query| query.get("by").and_then(|by| HabitPeriod::parse(by)))
+            .unwrap_or(HabitPeriod::Day)
+    };
+
+    let activity = create_resource(period, get_activity);
+    let completion = create_resource(|| (), move |_| get_completion_stats());
+
+    view! {
+        <Container>
+            <h2 class="text-2xl font-bold mb-4">"Statistics"</h2>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        completion
+                            .get()
+                            .map(|completion| {
+                                completion
+                                    .map(|stats| {
+                                        view! {
+                                            <CompletionSummary stats=stats.clone()/>
+                                            <div class="grid md:grid-cols-2 gap-4 mb-4">
+                                                <div class="p-4 bg-base-100 rounded-xl">
+                                                    <h3 class="text-xl font-bold mb-2">"By project"</h3>
+                                                    <RateChart rates=stats.by_project/>
+                                                </div>
+                                                <div class="p-4 bg-base-100 rounded-xl">
+                                                    <h3 class="text-xl font-bold mb-2">"By tag"</h3>
+                                                    <RateChart rates=stats.by_tag/>
+                                                </div>
+                                            </div>
+                                        }
+                                    })
+                            })
+                    }}
+                    <div class="p-4 bg-base-100 rounded-xl">
+                        <div class="flex items-center gap-4 mb-2">
+                            <h3 class="flex-1 text-xl font-bold">"Activity"</h3>
+                            <PeriodTabs period/>
+                        </div>
+                        {move || {
+                            activity
+                                .get()
+                                .map(|activity| {
+                                    activity.map(|activity| view! { <ActivityChart activity/> })
+                                })
+                        }}
+
+                    </div>
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+#[component]
+pub fn PeriodTabs<F>(period: F) -> impl IntoView
+where
+    F: Fn() -> HabitPeriod + Copy + 'static,
+{
+    let tab = move |value: HabitPeriod, label: &'static str| {
+        view! {
+            <A
+                href=format!("/stats?by={}", value.as_str())
+                class=move || if period() == value { "tab tab-active" } else { "tab" }
+            >
+                {label}
+            </A>
+        }
+    };
+
+    view! {
+        <div role="tablist" class="tabs tabs-boxed">
+            {tab(HabitPeriod::Day, "Daily")}
+            {tab(HabitPeriod::Week, "Weekly")}
+            {tab(HabitPeriod::Month, "Monthly")}
+        </div>
+    }
+}
+
+#[component]
+pub fn CompletionSummary(stats: CompletionStats) -> impl IntoView {
+    let average = stats
+        .average_completion
+        .map(format_duration)
+        .unwrap_or_else(|| "-".to_string());
+
+    view! {
+        <div class="stats w-full mb-4 bg-base-100">
+            <div class="stat">
+                <div class="stat-title">"Open"</div>
+                <div class="stat-value">{stats.open}</div>
+            </div>
+            <div class="stat">
+                <div class="stat-title">"Completed"</div>
+                <div class="stat-value text-accent">{stats.completed}</div>
+            </div>
+            <div class="stat">
+                <div class="stat-title">"Overdue"</div>
+                <div class="stat-value" class:text-error={stats.overdue > 0}>
+                    {stats.overdue}
+                </div>
+            </div>
+            <div class="stat">
+                <div class="stat-title">"Average time to complete"</div>
+                <div class="stat-value text-primary">{average}</div>
+            </div>
+        </div>
+    }
+}
+
+/// Size of the activity chart, in SVG units.
+const CHART_WIDTH: f64 = 640.0;
+const CHART_HEIGHT: f64 = 240.0;
+/// Room left of and below the plot for the axis labels.
+const CHART_LEFT: f64 = 32.0;
+const CHART_BOTTOM: f64 = 24.0;
+
+/// Bars of the todos created and completed in each period, with a line of the habit check-ins.
+#[component]
+pub fn ActivityChart(activity: Activity) -> impl IntoView {
+    let buckets = activity.buckets;
+    let max = buckets
+        .iter()
+        .map(|bucket| bucket.created.max(bucket.completed).max(bucket.checkins))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let plot_width = CHART_WIDTH - CHART_LEFT;
+    let plot_height = CHART_HEIGHT - CHART_BOTTOM;
+    let slot = plot_width / buckets.len().max(1) as f64;
+    let bar = slot * 0.35;
+    let y = move |count: u32| plot_height - count as f64 / max as f64 * plot_height;
+    let x = move |i: usize| CHART_LEFT + i as f64 * slot;
+    let label_format = match activity.period {
+        HabitPeriod::Month => "%b",
+        HabitPeriod::Day | HabitPeriod::Week => "%b %d",
+    };
+    // Only some periods are labeled so the labels don't overlap
+    let label_every = buckets.len().div_ceil(8).max(1);
+
+    let grid = [0, max.div_ceil(2), max]
+        .into_iter()
+        .map(|count| {
+            view! {
+                <line
+                    x1=CHART_LEFT
+                    x2=CHART_WIDTH
+                    y1=y(count)
+                    y2=y(count)
+                    class="stroke-base-content opacity-20"
+                ></line>
+                <text x=CHART_LEFT - 6.0 y=y(count) + 4.0 text-anchor="end" class="fill-base-content text-xs">
+                    {count}
+                </text>
+            }
+        })
+        .collect_view();
+
+    let bars = buckets
+        .iter()
+        .enumerate()
+        .map(|(i, bucket)| {
+            let tooltip = format!(
+                "{}: {} created, {} completed, {} check-ins",
+                bucket.start.format("%Y-%m-%d"),
+                bucket.created,
+                bucket.completed,
+                bucket.checkins,
+            );
+            let label = (i % label_every == 0).then(|| {
+                view! {
+                    <text
+                        x=x(i) + slot / 2.0
+                        y=CHART_HEIGHT - 6.0
+                        text-anchor="middle"
+                        class="fill-base-content text-xs"
+                    >
+                        {bucket.start.format(label_format).to_string()}
+                    </text>
+                }
+            });
+            view! {
+                <g>
+                    <title>{tooltip}</title>
+                    <rect
+                        x=x(i) + slot / 2.0 - bar
+                        y=y(bucket.created)
+                        width=bar
+                        height=plot_height - y(bucket.created)
+                        class="fill-primary"
+                    ></rect>
+                    <rect
+                        x=x(i) + slot / 2.0
+                        y=y(bucket.completed)
+                        width=bar
+                        height=plot_height - y(bucket.completed)
+                        class="fill-accent"
+                    ></rect>
+                </g>
+                {label}
+            }
+        })
+        .collect_view();
+
+    let checkins = buckets
+        .iter()
+        .enumerate()
+        .map(|(i, bucket)| format!("{},{}", x(i) + slot / 2.0, y(bucket.checkins)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    view! {
+        <svg viewBox=format!("0 0 {CHART_WIDTH} {CHART_HEIGHT}") class="w-full">
+            {grid}
+            {bars}
+            <polyline points=checkins fill="none" stroke-width="2" class="stroke-secondary"></polyline>
+        </svg>
+        <div class="flex gap-4 text-sm">
+            <span class="flex items-center gap-1">
+                <span class="inline-block size-3 bg-primary rounded"></span>
+                "Created"
+            </span>
+            <span class="flex items-center gap-1">
+                <span class="inline-block size-3 bg-accent rounded"></span>
+                "Completed"
+            </span>
+            <span class="flex items-center gap-1">
+                <span class="inline-block size-3 bg-secondary rounded"></span>
+                "Habit check-ins"
+            </span>
+        </div>
+    }
+}
+
+/// Height of a row of the completion rate chart, in SVG units.
+const RATE_ROW: f64 = 28.0;
+/// Room for the names left of the bars, and for the counts right of them.
+const RATE_LEFT: f64 = 120.0;
+const RATE_RIGHT: f64 = 120.0;
+
+/// Horizontal bars of the share of completed todos of each project or tag.
+#[component]
+pub fn RateChart(rates: Vec<CompletionRate>) -> impl IntoView {
+    if rates.is_empty() {
+        return view! { <p class="opacity-50">"No todos yet."</p> }.into_view();
+    }
+
+    let height = rates.len() as f64 * RATE_ROW;
+    let track = CHART_WIDTH - RATE_LEFT - RATE_RIGHT;
+    let rows = rates
+        .into_iter()
+        .enumerate()
+        .map(|(i, rate)| {
+            let top = i as f64 * RATE_ROW;
+            let percent = (rate.rate() * 100.0).round();
+            let style = rate.color.as_ref().map(|color| format!("fill: {color}"));
+            let overdue = (rate.overdue > 0).then(|| format!(", {} overdue", rate.overdue));
+            view! {
+                <g>
+                    <title>{format!("{}: {}/{} done", rate.name, rate.completed, rate.total)}</title>
+                    <text x=0 y=top + RATE_ROW / 2.0 + 5.0 class="fill-base-content text-sm">
+                        {rate.name.clone()}
+                    </text>
+                    <rect
+                        x=RATE_LEFT
+                        y=top + 6.0
+                        width=track
+                        height=RATE_ROW - 12.0
+                        rx=4
+                        class="fill-base-300"
+                    ></rect>
+                    <rect
+                        x=RATE_LEFT
+                        y=top + 6.0
+                        width=track * rate.rate()
+                        height=RATE_ROW - 12.0
+                        rx=4
+                        class="fill-primary"
+                        style=style
+                    ></rect>
+                    <text
+                        x=CHART_WIDTH - RATE_RIGHT + 8.0
+                        y=top + RATE_ROW / 2.0 + 5.0
+                        class="text-sm"
+                        class:fill-error={rate.overdue > 0}
+                        class:fill-base-content={rate.overdue == 0}
+                    >
+                        {format!("{percent}%")}
+                        {overdue}
+                    </text>
+                </g>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <svg viewBox=format!("0 0 {CHART_WIDTH} {height}") class="w-full">
+            {rows}
+        </svg>
+    }
+        .into_view()
+}
@@ -0,0 +1,417 @@
+use crate::{
+    todo::{DeleteTodo, Todo, UpdateTodo},
+    trash::{RestoreTodo, UndoToast},
+    ui::ActionIcon,
+};
+use icondata as i;
+use leptos::*;
+use leptos_icons::Icon;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+/// The direct subtasks of a todo, along with the state of the todo itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubtaskList {
+    pub completed: bool,
+    pub auto_complete: bool,
+    pub subtasks: Vec<Todo>,
+}
+
+impl SubtaskList {
+    pub fn progress(&self) -> Option<String> {
+        let done = self.subtasks.iter().filter(|subtask| subtask.completed).count();
+        (!self.subtasks.is_empty()).then(|| format!("{}/{} done", done, self.subtasks.len()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use crate::history::{ssr as history, TodoEventKind};
+    use chrono::Utc;
+    use sqlx::SqliteConnection;
+
+    /// Completes or reopens `id` and its ancestors which complete automatically,
+    /// after one of their subtasks changed.
+    ///
+    /// Stops at the first todo whose state is left as is, since nothing changes above it.
+    pub async fn sync_completion(
+        conn: &mut SqliteConnection,
+        mut id: Option<u32>,
+    ) -> Result<(), sqlx::Error> {
+        while let Some(current) = id {
+            let (completed, auto_complete, parent_id, user_id) =
+                sqlx::query_as::<_, (bool, bool, Option<u32>, i64)>(
+                    "SELECT completed, auto_complete, parent_id, user_id FROM todos WHERE id = ?",
+                )
+                .bind(current)
+                .fetch_one(&mut *conn)
+                .await?;
+            if !auto_complete {
+                break;
+            }
+
+            let (total, done) = sqlx::query_as::<_, (u32, u32)>(
+                "SELECT COUNT(*), COALESCE(SUM(completed), 0) FROM todos
+                WHERE parent_id = ? AND deleted_at IS NULL",
+            )
+            .bind(current)
+            .fetch_one(&mut *conn)
+            .await?;
+            let all_done = total > 0 && done == total;
+            if all_done == completed {
+                break;
+            }
+
+            sqlx::query("UPDATE todos SET completed = ?, completed_at = ? WHERE id = ?")
+                .bind(all_done)
+                .bind(all_done.then(|| Utc::now().naive_utc()))
+                .bind(current)
+                .execute(&mut *conn)
+                .await?;
+            let kind = match all_done {
+                true => TodoEventKind::Completed,
+                false => TodoEventKind::Reopened,
+            };
+            history::record(&mut *conn, current, user_id, kind).await?;
+            id = parent_id;
+        }
+
+        Ok(())
+    }
+}
+
+#[server(GetSubtasks, "/api")]
+pub async fn get_subtasks(parent_id: u32) -> Result<SubtaskList, ServerFnError> {
+    use crate::{
+        tags::ssr::load_tags,
+        todo::ssr::{pool, require_user, todo_not_found, SqlTodo, SELECT_TODOS},
+    };
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let (completed, auto_complete) = sqlx::query_as::<_, (bool, bool)>(
+        "SELECT completed, auto_complete FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+    )
+    .bind(parent_id)
+    .bind(user.id)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(todo_not_found)?;
+
+    let mut subtasks: Vec<_> = sqlx::query_as::<_, SqlTodo>(&format!(
+        "{SELECT_TODOS} WHERE t.parent_id = ? AND t.user_id = ? AND t.deleted_at IS NULL
+        ORDER BY t.position, t.id"
+    ))
+    .bind(parent_id)
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|subtask| subtask.into_todo(&user))
+    .collect();
+    load_tags(&mut subtasks, &pool).await?;
+
+    Ok(SubtaskList {
+        completed,
+        auto_complete,
+        subtasks,
+    })
+}
+
+/// Adds a subtask at the end of the subtasks of `parent_id`.
+#[server(AddSubtask, "/api")]
+pub async fn add_subtask(parent_id: u32, title: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::{
+        history::{ssr as history, TodoEventKind},
+        todo::ssr::{authorize_todo, pool, require_user},
+    };
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    if title.trim().is_empty() {
+        return Err(ServerFnError::new("A subtask needs a title."));
+    }
+    authorize_todo(parent_id, &user, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    let id = sqlx::query_scalar::<_, u32>(
+        "INSERT INTO todos (title, user_id, completed, parent_id, position)
+        SELECT ?, ?, false, ?, COALESCE(MAX(position) + 1, 0) FROM todos WHERE parent_id = ?
+        RETURNING id",
+    )
+    .bind(title.trim())
+    .bind(user.id)
+    .bind(parent_id)
+    .bind(parent_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    history::record(&mut tx, id, user.id, TodoEventKind::Created).await?;
+
+    // A new open subtask reopens a parent completed automatically
+    sync_completion(&mut tx, Some(parent_id)).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Moves a subtask one place up or down among its siblings.
+#[server(MoveSubtask, "/api")]
+pub async fn move_subtask(id: u32, up: bool) -> Result<(), ServerFnError> {
+    use crate::todo::ssr::{pool, require_user, todo_not_found};
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let mut tx = pool.begin().await?;
+
+    let parent_id = sqlx::query_scalar::<_, Option<u32>>(
+        "SELECT parent_id FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .bind(user.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten()
+    .ok_or_else(todo_not_found)?;
+
+    let mut siblings = sqlx::query_scalar::<_, u32>(
+        "SELECT id FROM todos WHERE parent_id = ? AND deleted_at IS NULL ORDER BY position, id",
+    )
+    .bind(parent_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let index = siblings.iter().position(|sibling| *sibling == id).unwrap_or_default();
+    let target = match up {
+        true => index.checked_sub(1),
+        false => Some(index + 1).filter(|target| *target < siblings.len()),
+    };
+    let Some(target) = target else {
+        return Ok(());
+    };
+    siblings.swap(index, target);
+
+    // Renumbering the siblings also sorts out equal positions
+    for (position, sibling) in siblings.into_iter().enumerate() {
+        sqlx::query("UPDATE todos SET position = ? WHERE id = ?")
+            .bind(position as i64)
+            .bind(sibling)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Sets whether a todo completes itself once all of its subtasks are done.
+#[server(SetAutoComplete, "/api")]
+pub async fn set_auto_complete(id: u32, auto_complete: bool) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{ensure_affected, pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let mut tx = pool.begin().await?;
+
+    ensure_affected(
+        sqlx::query("UPDATE todos SET auto_complete = ? WHERE id = ? AND user_id = ?")
+            .bind(auto_complete)
+            .bind(id)
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?,
+    )?;
+
+    sync_completion(&mut tx, Some(id)).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Expandable list of the subtasks of a todo.
+///
+/// The state of the parent is reported back through `set_completed` and `set_progress`,
+/// as completing subtasks may complete the parent too.
+#[component]
+pub fn Subtasks(
+    parent_id: u32,
+    set_completed: WriteSignal<bool>,
+    set_progress: WriteSignal<Option<String>>,
+) -> impl IntoView {
+    let add_subtask = create_server_action::<AddSubtask>();
+    let update_todo = create_server_action::<UpdateTodo>();
+    let move_subtask = create_server_action::<MoveSubtask>();
+    let delete_todo = create_server_action::<DeleteTodo>();
+    let restore_todo = create_server_action::<RestoreTodo>();
+    let set_auto_complete = create_server_action::<SetAutoComplete>();
+
+    let subtasks = create_resource(
+        move || {
+            (
+                add_subtask.version().get(),
+                update_todo.version().get(),
+                move_subtask.version().get(),
+                delete_todo.version().get(),
+                restore_todo.version().get(),
+                set_auto_complete.version().get(),
+            )
+        },
+        move |_| get_subtasks(parent_id),
+    );
+
+    create_effect(move |_| {
+        if let Some(Ok(list)) = subtasks.get() {
+            set_completed.set(list.completed);
+            set_progress.set(list.progress());
+        }
+    });
+
+    view! {
+        <div class="ml-8 mt-2 space-y-2">
+            <Transition fallback=move || view! { <span class="loading loading-spinner"></span> }>
+                {move || {
+                    subtasks
+                        .get()
+                        .map(|list| match list {
+                            Err(e) => view! { <p class="error">{e.to_string()}</p> }.into_view(),
+                            Ok(list) => {
+                                view! {
+                                    <ul class="space-y-2">
+                                        {list
+                                            .subtasks
+                                            .into_iter()
+                                            .map(|subtask| {
+                                                view! {
+                                                    <li>
+                                                        <Subtask
+                                                            subtask
+                                                            update_todo
+                                                            move_subtask
+                                                            delete_todo
+                                                        />
+                                                    </li>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </ul>
+                                    <label class="label cursor-pointer justify-start gap-2">
+                                        <input
+                                            type="checkbox"
+                                            class="checkbox checkbox-sm"
+                                            checked=list.auto_complete
+                                            on:change=move |ev| {
+                                                set_auto_complete
+                                                    .dispatch(SetAutoComplete {
+                                                        id: parent_id,
+                                                        auto_complete: event_target_checked(&ev),
+                                                    });
+                                            }
+                                        />
+
+                                        <span class="label-text">"Complete when all subtasks are done"</span>
+                                    </label>
+                                }
+                                    .into_view()
+                            }
+                        })
+                }}
+
+            </Transition>
+            <ActionForm action=add_subtask class="flex items-center gap-2">
+                <input type="hidden" name="parent_id" value=parent_id/>
+                <input
+                    type="text"
+                    name="title"
+                    placeholder="New subtask"
+                    class="input input-sm input-bordered flex-1"
+                />
+                <button type="submit" class="btn btn-sm btn-ghost">
+                    "Add"
+                </button>
+            </ActionForm>
+            <UndoToast delete_todo restore_todo/>
+        </div>
+    }
+}
+
+#[component]
+pub fn Subtask(
+    subtask: Todo,
+    update_todo: Action<UpdateTodo, Result<(), ServerFnError>>,
+    move_subtask: Action<MoveSubtask, Result<(), ServerFnError>>,
+    delete_todo: Action<DeleteTodo, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let (completed, set_completed) = create_signal(subtask.completed);
+    let (progress, set_progress) = create_signal(subtask.progress());
+    let (expanded, set_expanded) = create_signal(false);
+    let id = subtask.id;
+
+    view! {
+        <div class="flex gap-2">
+            <div class="min-h-10 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
+                <input
+                    type="checkbox"
+                    class="checkbox checkbox-sm checkbox-accent"
+                    prop:checked=completed
+                    on:change=move |ev| {
+                        update_todo
+                            .dispatch(UpdateTodo {
+                                id,
+                                completed: event_target_checked(&ev),
+                            });
+                    }
+                />
+
+                <span class="flex-1" class:line-through=completed>
+                    {subtask.title}
+                </span>
+                <SubtasksToggle progress expanded set_expanded/>
+            </div>
+            <ActionIcon action=move_subtask icon=i::LuArrowUp class="btn-ghost btn-sm rounded-xl">
+                <input type="hidden" name="id" value=id/>
+                <input type="hidden" name="up" value="true"/>
+            </ActionIcon>
+            <ActionIcon action=move_subtask icon=i::LuArrowDown class="btn-ghost btn-sm rounded-xl">
+                <input type="hidden" name="id" value=id/>
+                <input type="hidden" name="up" value="false"/>
+            </ActionIcon>
+            <ActionIcon
+                action=delete_todo
+                icon=i::LuTrash2
+                class="btn-ghost btn-sm text-error rounded-xl"
+            >
+                <input type="hidden" name="id" value=id/>
+            </ActionIcon>
+        </div>
+        {move || {
+            expanded
+                .get()
+                .then(|| view! { <Subtasks parent_id=id set_completed set_progress/> }.into_view())
+        }}
+    }
+}
+
+/// Shows the subtask progress of a todo, and expands or collapses its subtasks.
+#[component]
+pub fn SubtasksToggle(
+    progress: ReadSignal<Option<String>>,
+    expanded: ReadSignal<bool>,
+    set_expanded: WriteSignal<bool>,
+) -> impl IntoView {
+    view! {
+        <button
+            type="button"
+            class="btn btn-ghost btn-sm"
+            on:click=move |_| set_expanded.update(|expanded| *expanded = !*expanded)
+        >
+            {move || match expanded.get() {
+                true => view! { <Icon icon=i::LuChevronDown/> },
+                false => view! { <Icon icon=i::LuChevronRight/> },
+            }}
+            {move || progress.get().unwrap_or_else(|| "Subtasks".to_string())}
+        </button>
+    }
+}
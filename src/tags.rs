@@ -0,0 +1,548 @@
+use crate::{error_template::ErrorTemplate, ui::{ActionIcon, Container}};
+use icondata as i;
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: u32,
+    pub name: String,
+    /// Hex color, like `#22c55e`.
+    pub color: String,
+}
+
+impl Tag {
+    /// Inline style of the chip showing the tag.
+    pub fn style(&self) -> String {
+        format!("border-color: {0}; color: {0}", self.color)
+    }
+}
+
+/// Whether todos need one or all of the selected tags to be listed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+impl TagMatch {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagMatch::Any => "any",
+            TagMatch::All => "all",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "any" => Some(TagMatch::Any),
+            "all" => Some(TagMatch::All),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TagMatch::Any => "Any tag",
+            TagMatch::All => "All tags",
+        }
+    }
+}
+
+/// Color given to tags created without one.
+pub const DEFAULT_TAG_COLOR: &str = "#a3a3a3";
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{Tag, DEFAULT_TAG_COLOR};
+    use crate::{auth::User, todo::Todo};
+    use leptos::ServerFnError;
+    use sqlx::{SqliteConnection, SqlitePool};
+
+    pub fn tag_not_found() -> ServerFnError {
+        ServerFnError::new("Tag not found.")
+    }
+
+    /// Makes sure `id` refers to a tag owned by `user`.
+    pub async fn authorize_tag(
+        id: u32,
+        user: &User,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        sqlx::query("SELECT id FROM tags WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user.id)
+            .fetch_optional(pool)
+            .await?
+            .map(|_| ())
+            .ok_or_else(tag_not_found)
+    }
+
+    /// Makes sure `color` is a hex color like `#22c55e`, and lowercases it.
+    pub fn parse_color(color: &str) -> Result<String, ServerFnError> {
+        let color = color.trim();
+        match color.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Ok(color.to_ascii_lowercase())
+            }
+            _ => Err(ServerFnError::new(format!("Invalid color: {color}"))),
+        }
+    }
+
+    /// Reports a clash with another tag of the user, which the unique index on names prevents.
+    pub fn duplicate_tag(e: sqlx::Error) -> ServerFnError {
+        match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                ServerFnError::new("A tag with this name already exists.")
+            }
+            e => e.into(),
+        }
+    }
+
+    /// Encodes IDs as a JSON array, to be expanded with `json_each` in queries.
+    pub fn json_ids(ids: impl IntoIterator<Item = u32>) -> String {
+        let ids: Vec<String> = ids.into_iter().map(|id| id.to_string()).collect();
+        format!("[{}]", ids.join(","))
+    }
+
+    /// Tags `todo_id` with the tags of `user` named `names`, ignoring case.
+    /// Tags that don't exist yet are created with the default color.
+    pub async fn tag_by_names(
+        todo_id: u32,
+        names: &[String],
+        user: &User,
+        conn: &mut SqliteConnection,
+    ) -> Result<(), ServerFnError> {
+        for name in names {
+            let existing = sqlx::query_scalar::<_, u32>(
+                "SELECT id FROM tags WHERE user_id = ? AND name = ? COLLATE NOCASE",
+            )
+            .bind(user.id)
+            .bind(name)
+            .fetch_optional(&mut *conn)
+            .await?;
+            let tag_id = match existing {
+                Some(tag_id) => tag_id,
+                None => {
+                    sqlx::query_scalar::<_, u32>(
+                        "INSERT INTO tags (user_id, name, color) VALUES (?, ?, ?) RETURNING id",
+                    )
+                    .bind(user.id)
+                    .bind(name)
+                    .bind(DEFAULT_TAG_COLOR)
+                    .fetch_one(&mut *conn)
+                    .await?
+                }
+            };
+
+            set_tagged(conn, user, todo_id, tag_id, true).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the tag `tag_id` to `todo_id`, or removes it when `tagged` is false,
+    /// keeping track of it in the history of the todo when something changed.
+    ///
+    /// Both the todo and the tag are expected to be authorized already.
+    pub async fn set_tagged(
+        conn: &mut SqliteConnection,
+        user: &User,
+        todo_id: u32,
+        tag_id: u32,
+        tagged: bool,
+    ) -> Result<(), ServerFnError> {
+        use crate::history::ssr as history;
+
+        let query = match tagged {
+            true => "INSERT OR IGNORE INTO todo_tags (todo_id, tag_id) VALUES (?, ?)",
+            false => "DELETE FROM todo_tags WHERE todo_id = ? AND tag_id = ?",
+        };
+        let result = sqlx::query(query)
+            .bind(todo_id)
+            .bind(tag_id)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        let name = sqlx::query_scalar::<_, String>("SELECT name FROM tags WHERE id = ?")
+            .bind(tag_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        let (from, to) = match tagged {
+            true => (None, Some(name)),
+            false => (Some(name), None),
+        };
+        history::record_edit(conn, todo_id, user.id, "tag", from, to).await?;
+
+        Ok(())
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlTag {
+        pub id: u32,
+        pub name: String,
+        pub color: String,
+    }
+
+    impl SqlTag {
+        pub fn into_tag(self) -> Tag {
+            Tag {
+                id: self.id,
+                name: self.name,
+                color: self.color,
+            }
+        }
+    }
+
+    #[derive(sqlx::FromRow)]
+    struct SqlTodoTag {
+        todo_id: u32,
+        #[sqlx(flatten)]
+        tag: SqlTag,
+    }
+
+    /// Fills in the tags of `todos` with a single query.
+    pub async fn load_tags(todos: &mut [Todo], pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        if todos.is_empty() {
+            return Ok(());
+        }
+
+        let tags = sqlx::query_as::<_, SqlTodoTag>(
+            "SELECT tt.todo_id, g.id, g.name, g.color FROM todo_tags tt
+            JOIN tags g ON g.id = tt.tag_id
+            WHERE tt.todo_id IN (SELECT value FROM json_each(?))
+            ORDER BY g.name",
+        )
+        .bind(json_ids(todos.iter().map(|todo| todo.id)))
+        .fetch_all(pool)
+        .await?;
+
+        for SqlTodoTag { todo_id, tag } in tags {
+            if let Some(todo) = todos.iter_mut().find(|todo| todo.id == todo_id) {
+                todo.tags.push(tag.into_tag());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[server(GetTags, "/api")]
+pub async fn get_tags() -> Result<Vec<Tag>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(sqlx::query_as::<_, SqlTag>(
+        "SELECT id, name, color FROM tags WHERE user_id = ? ORDER BY name",
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlTag::into_tag)
+    .collect())
+}
+
+#[server(AddTag, "/api")]
+pub async fn add_tag(name: String, color: Option<String>) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    if name.trim().is_empty() {
+        return Err(ServerFnError::new("A tag needs a name."));
+    }
+    let color = parse_color(color.as_deref().unwrap_or(DEFAULT_TAG_COLOR))?;
+
+    sqlx::query("INSERT INTO tags (user_id, name, color) VALUES (?, ?, ?)")
+        .bind(user.id)
+        .bind(name.trim())
+        .bind(color)
+        .execute(&pool)
+        .await
+        .map_err(duplicate_tag)?;
+
+    Ok(())
+}
+
+/// Renames a tag and changes its color.
+#[server(UpdateTag, "/api")]
+pub async fn update_tag(id: u32, name: String, color: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    if name.trim().is_empty() {
+        return Err(ServerFnError::new("A tag needs a name."));
+    }
+    let color = parse_color(&color)?;
+
+    let result = sqlx::query("UPDATE tags SET name = ?, color = ? WHERE id = ? AND user_id = ?")
+        .bind(name.trim())
+        .bind(color)
+        .bind(id)
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .map_err(duplicate_tag)?;
+
+    match result.rows_affected() {
+        0 => Err(tag_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Deletes a tag, removing it from every todo.
+#[server(DeleteTag, "/api")]
+pub async fn delete_tag(id: u32) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let result = sqlx::query("DELETE FROM tags WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    match result.rows_affected() {
+        0 => Err(tag_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Adds a tag to a todo, or removes it when `tagged` is false.
+#[server(TagTodo, "/api")]
+pub async fn tag_todo(todo_id: u32, tag_id: u32, tagged: bool) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{authorize_todo, pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    authorize_todo(todo_id, &user, &pool).await?;
+    authorize_tag(tag_id, &user, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    set_tagged(&mut tx, &user, todo_id, tag_id, tagged).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[component]
+pub fn Tags() -> impl IntoView {
+    let add_tag = create_server_action::<AddTag>();
+    let update_tag = create_server_action::<UpdateTag>();
+    let delete_tag = create_server_action::<DeleteTag>();
+
+    let tags = create_resource(
+        move || {
+            (
+                add_tag.version().get(),
+                update_tag.version().get(),
+                delete_tag.version().get(),
+            )
+        },
+        move |_| get_tags(),
+    );
+
+    view! {
+        <Container>
+            <ActionForm action=add_tag class="flex items-center gap-4 mb-4">
+                <label class="input input-bordered flex items-center flex-1 text-xl gap-4">
+                    <span class="text-primary">"Tag Name"</span>
+                    <input type="text" name="name"/>
+                </label>
+                <input
+                    type="color"
+                    name="color"
+                    value=DEFAULT_TAG_COLOR
+                    class="h-12 w-12 bg-transparent"
+                />
+                <button type="submit" class="btn btn-primary text-lg">
+                    "Add Tag"
+                </button>
+            </ActionForm>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        tags.get()
+                            .map(move |tags| match tags {
+                                Err(e) => {
+                                    view! {
+                                        <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(tags) => {
+                                    if tags.is_empty() {
+                                        view! { <p>"No tags were found."</p> }.into_view()
+                                    } else {
+                                        view! {
+                                            <ul class="overflow-auto space-y-2">
+                                                {tags
+                                                    .into_iter()
+                                                    .map(move |tag| {
+                                                        view! {
+                                                            <li>
+                                                                <TagRow tag update_tag delete_tag/>
+                                                            </li>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </ul>
+                                        }
+                                            .into_view()
+                                    }
+                                }
+                            })
+                            .unwrap_or_default()
+                    }}
+
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+#[component]
+pub fn TagRow(
+    tag: Tag,
+    update_tag: Action<UpdateTag, Result<(), ServerFnError>>,
+    delete_tag: Action<DeleteTag, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let style = tag.style();
+
+    view! {
+        <div class="flex gap-2">
+            <div class="h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
+                <span class="badge badge-outline badge-lg" style=style>
+                    {tag.name.clone()}
+                </span>
+                <ActionForm action=update_tag class="flex flex-1 justify-end items-center gap-2">
+                    <input type="hidden" name="id" value=tag.id/>
+                    <input
+                        type="text"
+                        name="name"
+                        value=tag.name
+                        class="input input-sm input-bordered"
+                    />
+                    <input type="color" name="color" value=tag.color class="h-8 w-8 bg-transparent"/>
+                    <button type="submit" class="btn btn-sm btn-ghost">
+                        "Save"
+                    </button>
+                </ActionForm>
+            </div>
+            <ActionIcon
+                action=delete_tag
+                icon=i::LuTrash2
+                class="btn-ghost bg-base-100 text-error rounded-xl"
+            >
+                <input type="hidden" name="id" value=tag.id/>
+            </ActionIcon>
+        </div>
+    }
+}
+
+/// Chips of the tags of a todo, each removing itself from the todo when clicked,
+/// followed by a select to add more.
+#[component]
+pub fn TodoTags(
+    todo_id: u32,
+    tags: Vec<Tag>,
+    all_tags: Vec<Tag>,
+    tag_todo: Action<TagTodo, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let options = all_tags
+        .into_iter()
+        .filter(|tag| !tags.contains(tag))
+        .map(|tag| view! { <option value=tag.id>{tag.name}</option> })
+        .collect_view();
+
+    view! {
+        {tags
+            .into_iter()
+            .map(|tag| {
+                let style = tag.style();
+                view! {
+                    <button
+                        type="button"
+                        class="badge badge-outline gap-1"
+                        style=style
+                        title="Remove tag"
+                        on:click=move |_| {
+                            tag_todo
+                                .dispatch(TagTodo {
+                                    todo_id,
+                                    tag_id: tag.id,
+                                    tagged: false,
+                                });
+                        }
+                    >
+                        {tag.name}
+                    </button>
+                }
+            })
+            .collect_view()}
+        <select
+            class="select select-sm select-ghost"
+            on:change=move |ev| {
+                if let Ok(tag_id) = event_target_value(&ev).parse() {
+                    tag_todo
+                        .dispatch(TagTodo {
+                            todo_id,
+                            tag_id,
+                            tagged: true,
+                        });
+                }
+            }
+        >
+            <option value="" selected>
+                "+ Tag"
+            </option>
+            {options}
+        </select>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::{ssr::*, DEFAULT_TAG_COLOR};
+    use crate::testing;
+
+    #[tokio::test]
+    async fn tag_names_are_unique_per_user_regardless_of_case() {
+        let pool = testing::pool().await;
+        let alice = testing::user("alice", &pool).await;
+        let bob = testing::user("bob", &pool).await;
+        let add = |user_id: i64, name: &'static str| {
+            sqlx::query("INSERT INTO tags (user_id, name, color) VALUES (?, ?, ?)")
+                .bind(user_id)
+                .bind(name)
+                .bind(DEFAULT_TAG_COLOR)
+                .execute(&pool)
+        };
+
+        add(alice.id, "Home").await.expect("first tag");
+        let err = add(alice.id, "home").await.map_err(duplicate_tag).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        add(bob.id, "home").await.expect("tag of another user");
+    }
+}
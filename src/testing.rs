@@ -0,0 +1,73 @@
+//! Helpers for tests needing a database.
+
+use crate::auth::User;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+use std::str::FromStr;
+
+/// A fresh in-memory database with every migration applied.
+pub async fn pool() -> SqlitePool {
+    pool_with(SqliteConnectOptions::from_str("sqlite::memory:").expect("options")).await
+}
+
+/// A fresh database opened with `options`, with every migration applied.
+///
+/// Each connection to `sqlite::memory:` opens its own database, so the pool keeps a single one.
+pub async fn pool_with(options: SqliteConnectOptions) -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .expect("in-memory database");
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .expect("migrations");
+    pool
+}
+
+/// Signs up a user named `username`.
+pub async fn user(username: &str, pool: &SqlitePool) -> User {
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO users (username, password) VALUES (?, '') RETURNING id",
+    )
+    .bind(username)
+    .fetch_one(pool)
+    .await
+    .expect("user");
+    User::get(id, pool).await.expect("user")
+}
+
+/// Adds a top-level todo of `user` titled `title`.
+pub async fn todo(title: &str, user: &User, pool: &SqlitePool) -> u32 {
+    let mut conn = pool.acquire().await.expect("connection");
+    let rank = crate::todo::ssr::next_rank(user, &mut *conn)
+        .await
+        .expect("rank");
+    sqlx::query_scalar::<_, u32>(
+        "INSERT INTO todos (title, user_id, completed, rank) VALUES (?, ?, false, ?) RETURNING id",
+    )
+    .bind(title)
+    .bind(user.id)
+    .bind(rank)
+    .fetch_one(&mut *conn)
+    .await
+    .expect("todo")
+}
+
+/// Adds a subtask of `parent` titled `title`.
+pub async fn subtask(title: &str, parent: u32, user: &User, pool: &SqlitePool) -> u32 {
+    sqlx::query_scalar::<_, u32>(
+        "INSERT INTO todos (title, user_id, completed, parent_id, position)
+        SELECT ?, ?, false, ?, COUNT(*) FROM todos WHERE parent_id = ? RETURNING id",
+    )
+    .bind(title)
+    .bind(user.id)
+    .bind(parent)
+    .bind(parent)
+    .fetch_one(pool)
+    .await
+    .expect("subtask")
+}
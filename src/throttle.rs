@@ -0,0 +1,242 @@
+//! Throttling of failed logins, so passwords can't be brute forced.
+//!
+//! Failures are counted both per client IP and per username, in SQLite so restarting
+//! the server doesn't reset them. Past a few failures in a row the key is locked for a
+//! while, twice as long after each further failure.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+use std::net::IpAddr;
+
+/// Failures in a row allowed before a key gets locked.
+pub(crate) const FREE_FAILURES: u32 = 5;
+/// Lockout after the first failure past [`FREE_FAILURES`].
+const BASE_LOCKOUT: Duration = Duration::seconds(30);
+/// Longest lockout, however many failures there were.
+const MAX_LOCKOUT: Duration = Duration::hours(1);
+/// Failures older than this are forgotten.
+const FAILURE_WINDOW: Duration = Duration::days(1);
+
+/// How long a key is locked after `failures` failures in a row.
+pub fn lockout(failures: u32) -> Option<Duration> {
+    let extra = failures.checked_sub(FREE_FAILURES)?;
+    // Past 2^7 times the base, the cap is reached anyway
+    let lockout = BASE_LOCKOUT * 2i32.pow(extra.min(7));
+    Some(lockout.min(MAX_LOCKOUT))
+}
+
+/// Keys failures of a login attempt are counted under.
+pub fn keys(username: &str, ip: Option<IpAddr>) -> Vec<String> {
+    let mut keys = vec![format!("user:{username}")];
+    keys.extend(ip.map(|ip| format!("ip:{ip}")));
+    keys
+}
+
+/// How long the longest lock on `keys` still lasts, if any.
+pub async fn locked_for(
+    keys: &[String],
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<Option<Duration>, sqlx::Error> {
+    let mut locked = None;
+    for key in keys {
+        let until = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            "SELECT locked_until FROM login_throttles WHERE key = ?",
+        )
+        .bind(key)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+        if let Some(until) = until.filter(|until| *until > now) {
+            locked = locked.max(Some(until - now));
+        }
+    }
+    Ok(locked)
+}
+
+/// Counts a failed login under `keys`, locking them once there were too many,
+/// and adds it to the audit log.
+pub async fn record_failure(
+    keys: &[String],
+    username: &str,
+    ip: Option<IpAddr>,
+    now: DateTime<Utc>,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // Forget keys which have been quiet for long enough
+    sqlx::query("DELETE FROM login_throttles WHERE last_failure_at < ?")
+        .bind((now - FAILURE_WINDOW).naive_utc())
+        .execute(&mut *tx)
+        .await?;
+
+    for key in keys {
+        let failures = sqlx::query_scalar::<_, u32>(
+            "INSERT INTO login_throttles (key, failures, last_failure_at) VALUES (?, 1, ?)
+            ON CONFLICT (key) DO UPDATE SET failures = failures + 1, last_failure_at = excluded.last_failure_at
+            RETURNING failures",
+        )
+        .bind(key)
+        .bind(now.naive_utc())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if let Some(lockout) = lockout(failures) {
+            sqlx::query("UPDATE login_throttles SET locked_until = ? WHERE key = ?")
+                .bind((now + lockout).naive_utc())
+                .bind(key)
+                .execute(&mut *tx)
+                .await?;
+            log::warn!(
+                "Locked {key} for {}s after {failures} failed logins",
+                lockout.num_seconds()
+            );
+        }
+    }
+
+    sqlx::query("INSERT INTO failed_logins (username, ip, created_at) VALUES (?, ?, ?)")
+        .bind(username)
+        .bind(ip.map(|ip| ip.to_string()))
+        .bind(now.naive_utc())
+        .execute(&mut *tx)
+        .await?;
+    log::warn!(
+        "Failed login for {username:?} from {}",
+        ip.map_or_else(|| "an unknown address".to_string(), |ip| ip.to_string())
+    );
+
+    tx.commit().await
+}
+
+/// Forgets the failures of `username` after it logged in.
+///
+/// Those of the IP are kept, or logging into an account of one's own would reset them.
+pub async fn record_success(username: &str, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_throttles WHERE key = ?")
+        .bind(format!("user:{username}"))
+        .execute(pool)
+        .await
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn ip(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([192, 0, 2, last]))
+    }
+
+    async fn fail(times: u32, username: &str, ip: Option<IpAddr>, now: DateTime<Utc>, pool: &SqlitePool) {
+        for _ in 0..times {
+            record_failure(&keys(username, ip), username, ip, now, pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn locked(username: &str, ip: Option<IpAddr>, now: DateTime<Utc>, pool: &SqlitePool) -> Option<Duration> {
+        locked_for(&keys(username, ip), now, pool).await.unwrap()
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let schedule = [
+            (0, None),
+            (4, None),
+            (5, Some(Duration::seconds(30))),
+            (6, Some(Duration::seconds(60))),
+            (7, Some(Duration::seconds(120))),
+            (11, Some(Duration::seconds(1920))),
+            (12, Some(MAX_LOCKOUT)),
+            (13, Some(MAX_LOCKOUT)),
+            (u32::MAX, Some(MAX_LOCKOUT)),
+        ];
+
+        for (failures, expected) in schedule {
+            assert_eq!(lockout(failures), expected, "{failures}");
+        }
+    }
+
+    #[test]
+    fn keys_by_username_and_ip() {
+        assert_eq!(keys("alice", ip(1)), ["user:alice", "ip:192.0.2.1"]);
+        assert_eq!(keys("alice", None), ["user:alice"]);
+    }
+
+    #[tokio::test]
+    async fn locks_after_too_many_failures() {
+        let pool = testing::pool().await;
+        let now = Utc::now();
+
+        fail(FREE_FAILURES - 1, "alice", ip(1), now, &pool).await;
+        assert_eq!(locked("alice", ip(1), now, &pool).await, None);
+
+        fail(1, "alice", ip(1), now, &pool).await;
+        assert_eq!(locked("alice", ip(1), now, &pool).await, Some(BASE_LOCKOUT));
+        let later = now + Duration::seconds(10);
+        assert_eq!(locked("alice", ip(1), later, &pool).await, Some(BASE_LOCKOUT - Duration::seconds(10)));
+        assert_eq!(locked("alice", ip(1), now + BASE_LOCKOUT, &pool).await, None);
+
+        // Each further failure doubles the lockout
+        fail(1, "alice", ip(1), now + BASE_LOCKOUT, &pool).await;
+        assert_eq!(locked("alice", ip(1), now + BASE_LOCKOUT, &pool).await, Some(BASE_LOCKOUT * 2));
+
+        let failed_logins = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM failed_logins")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(failed_logins, i64::from(FREE_FAILURES) + 1);
+    }
+
+    #[tokio::test]
+    async fn counts_ips_and_usernames_independently() {
+        let pool = testing::pool().await;
+        let now = Utc::now();
+
+        // Guessing several accounts from one address locks the address
+        fail(3, "alice", ip(1), now, &pool).await;
+        fail(2, "bob", ip(1), now, &pool).await;
+        assert!(locked("carol", ip(1), now, &pool).await.is_some());
+        assert_eq!(locked("alice", ip(2), now, &pool).await, None);
+        assert_eq!(locked("bob", ip(2), now, &pool).await, None);
+
+        // Guessing one account from several addresses locks the account
+        for last in 10..10 + FREE_FAILURES as u8 {
+            fail(1, "dave", ip(last), now, &pool).await;
+        }
+        assert!(locked("dave", ip(99), now, &pool).await.is_some());
+        assert!(locked("dave", None, now, &pool).await.is_some());
+        assert_eq!(locked("erin", ip(10), now, &pool).await, None);
+    }
+
+    #[tokio::test]
+    async fn success_resets_the_username_only() {
+        let pool = testing::pool().await;
+        let now = Utc::now();
+
+        fail(FREE_FAILURES, "alice", ip(1), now, &pool).await;
+        record_success("alice", &pool).await.unwrap();
+
+        assert_eq!(locked("alice", ip(2), now, &pool).await, None);
+        assert!(locked("alice", ip(1), now, &pool).await.is_some());
+
+        // Counting starts over for the username
+        fail(FREE_FAILURES - 1, "alice", ip(2), now, &pool).await;
+        assert_eq!(locked("alice", ip(2), now, &pool).await, None);
+    }
+
+    #[tokio::test]
+    async fn forgets_old_failures() {
+        let pool = testing::pool().await;
+        let now = Utc::now();
+
+        fail(FREE_FAILURES - 1, "alice", ip(1), now, &pool).await;
+        let later = now + FAILURE_WINDOW + Duration::seconds(1);
+        fail(1, "alice", ip(1), later, &pool).await;
+
+        assert_eq!(locked("alice", ip(1), later, &pool).await, None);
+    }
+}
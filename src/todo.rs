@@ -1,25 +1,275 @@
-use crate::{auth::{get_user, User, Login, Logout, Signup}, error_template::ErrorTemplate, ui::{ActionIcon, CenteredCard, Container, Form, FormCheckbox, FormInput}};
+use crate::{auth::{get_user, User, UserResource, Login, Logout, Signup}, bulk::{BulkBar, BulkEditTodos}, error_template::ErrorTemplate, habits::Habits, history::TodoHistory, notes::{Notes, NotesToggle, TodoNotes}, notifications::{NotificationActions, NotificationBell, Notifications}, projects::{get_projects, Project, ProjectView, Projects}, quick_add::{self, QuickAdd}, recurrence::Recurrence, reminders::ReminderButton, search::{Search, SearchBox}, settings::Settings, stats::Stats, subtasks::{Subtasks, SubtasksToggle}, tags::{get_tags, Tag, TagMatch, TagTodo, Tags, TodoTags}, trash::{RestoreTodo, Trash, UndoToast}, two_factor::{TwoFactorLogin, VerifyTwoFactor}, ui::{ActionIcon, CenteredCard, Container, Form, FormCheckbox, FormInput, InlineEdit}};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use icondata as i;
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Todo {
-    id: u32,
-    user: Option<User>,
-    title: String,
-    created_at: String,
-    completed: bool,
+    pub id: u32,
+    pub user: Option<User>,
+    pub title: String,
+    pub notes: Notes,
+    pub created_at: DateTime<Utc>,
+    pub completed: bool,
+    /// When the todo was last completed, unknown for todos completed before it was recorded.
+    pub completed_at: Option<DateTime<Utc>>,
+    pub start_at: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub recurrence: Option<Recurrence>,
+    pub project_id: Option<u32>,
+    pub parent_id: Option<u32>,
+    pub priority: Priority,
+    /// Whether the todo completes itself once all of its subtasks are done.
+    pub auto_complete: bool,
+    /// Number of direct subtasks.
+    pub subtasks: u32,
+    /// Number of completed direct subtasks.
+    pub subtasks_completed: u32,
+    pub tags: Vec<Tag>,
+}
+
+impl Todo {
+    /// Subtask progress, `None` when the todo has no subtasks.
+    pub fn progress(&self) -> Option<String> {
+        (self.subtasks > 0).then(|| format!("{}/{} done", self.subtasks_completed, self.subtasks))
+    }
+}
+
+/// How important a todo is, stored as its level from 0 to 4.
+///
+/// Read back from any case of its name, so forms can submit [`Priority::as_str`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 5] = [
+        Priority::None,
+        Priority::Low,
+        Priority::Medium,
+        Priority::High,
+        Priority::Urgent,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::None => "none",
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Priority::ALL
+            .into_iter()
+            .find(|priority| priority.as_str().eq_ignore_ascii_case(value))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Priority::None => "No priority",
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+            Priority::Urgent => "Urgent",
+        }
+    }
+
+    pub fn level(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_level(level: u8) -> Self {
+        Priority::ALL.get(level as usize).copied().unwrap_or_default()
+    }
+
+    /// Text color of the priority in the todo list.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Priority::None => "",
+            Priority::Low => "text-info",
+            Priority::Medium => "text-success",
+            Priority::High => "text-warning",
+            Priority::Urgent => "text-error",
+        }
+    }
+}
+
+impl TryFrom<String> for Priority {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Priority::parse(&value).ok_or_else(|| format!("Unknown priority: {value}"))
+    }
+}
+
+/// Order of the todo list, remembered for each user.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TodoSort {
+    /// The order todos were dragged into.
+    #[default]
+    Manual,
+    /// Most important first.
+    Priority,
+    /// Soonest due first, todos without a due date last.
+    Due,
+    /// Newest first.
+    Created,
+}
+
+impl TodoSort {
+    pub const ALL: [TodoSort; 4] = [
+        TodoSort::Manual,
+        TodoSort::Priority,
+        TodoSort::Due,
+        TodoSort::Created,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoSort::Manual => "manual",
+            TodoSort::Priority => "priority",
+            TodoSort::Due => "due",
+            TodoSort::Created => "created",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        TodoSort::ALL
+            .into_iter()
+            .find(|sort| sort.as_str().eq_ignore_ascii_case(value))
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TodoSort::Manual => "My order",
+            TodoSort::Priority => "Priority",
+            TodoSort::Due => "Due date",
+            TodoSort::Created => "Newest",
+        }
+    }
+}
+
+/// One page of the todo list, see [`get_todos`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoPage {
+    /// Order of the todos.
+    pub sort: TodoSort,
+    pub todos: Vec<Todo>,
+    /// Pass as `after` to get the next page, `None` on the last page.
+    pub next_cursor: Option<u32>,
+}
+
+/// Number of todos per page when no limit is requested.
+pub const TODO_PAGE_SIZE: u32 = 100;
+/// Largest page a client can request.
+pub const MAX_TODO_PAGE_SIZE: u32 = 500;
+
+/// Filters of the todo list, kept in the query string.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TodoFilter {
+    pub due: Option<DueFilter>,
+    pub tags: Vec<u32>,
+    pub tag_match: TagMatch,
+}
+
+impl TodoFilter {
+    pub fn from_query(query: &ParamsMap) -> Self {
+        TodoFilter {
+            due: query.get("due").and_then(|due| DueFilter::parse(due)),
+            tags: query
+                .get("tags")
+                .map(|tags| tags.split(',').filter_map(|tag| tag.parse().ok()).collect())
+                .unwrap_or_default(),
+            tag_match: query
+                .get("match")
+                .and_then(|tag_match| TagMatch::parse(tag_match))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Link to `path` with these filters, starting the list after the todo `after`.
+    pub fn href(&self, path: &str, after: Option<u32>) -> String {
+        let tags: Vec<String> = self.tags.iter().map(|tag| tag.to_string()).collect();
+        let query: Vec<String> = [
+            self.due.map(|due| format!("due={}", due.as_str())),
+            (!tags.is_empty()).then(|| format!("tags={}", tags.join(","))),
+            (self.tag_match != TagMatch::default())
+                .then(|| format!("match={}", self.tag_match.as_str())),
+            after.map(|after| format!("after={after}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        match query.is_empty() {
+            true => path.to_string(),
+            false => format!("{path}?{}", query.join("&")),
+        }
+    }
+}
+
+/// Narrows the todo list down by due date, in the user's time zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DueFilter {
+    Overdue,
+    Today,
+    ThisWeek,
+}
+
+impl DueFilter {
+    pub const ALL: [DueFilter; 3] = [DueFilter::Overdue, DueFilter::Today, DueFilter::ThisWeek];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DueFilter::Overdue => "overdue",
+            DueFilter::Today => "today",
+            DueFilter::ThisWeek => "week",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        DueFilter::ALL.into_iter().find(|filter| filter.as_str() == value)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DueFilter::Overdue => "Overdue",
+            DueFilter::Today => "Due today",
+            DueFilter::ThisWeek => "Due this week",
+        }
+    }
+}
+
+/// A single expanded occurrence of a recurring todo.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Occurrence {
+    pub todo_id: u32,
+    pub title: String,
+    pub at: DateTime<Utc>,
 }
 
 #[cfg(feature = "ssr")]
 pub mod ssr {
-    use super::Todo;
-    use crate::auth::{ssr::AuthSession, User};
+    use super::{DueFilter, Priority, Todo, TodoFilter, TodoPage, TodoSort};
+    use crate::{auth::{ssr::AuthSession, User}, notes::Notes, rank, recurrence::Recurrence};
+    use chrono::{DateTime, Duration, NaiveDateTime, Utc};
     use leptos::*;
-    use sqlx::SqlitePool;
+    use sqlx::{SqliteConnection, SqlitePool};
 
     pub fn pool() -> Result<SqlitePool, ServerFnError> {
         use_context::<SqlitePool>()
@@ -32,71 +282,755 @@ pub mod ssr {
         })
     }
 
-    #[derive(sqlx::FromRow, Clone)]
-    pub struct SqlTodo {
+    /// Returns the logged in user, or an error for anonymous requests.
+    pub fn require_user() -> Result<User, ServerFnError> {
+        auth()?
+            .current_user
+            .ok_or_else(|| ServerFnError::new("User needs to be logged in."))
+    }
+
+    /// Error returned when a todo does not exist or belongs to another user.
+    /// Both cases are reported the same way so IDs of foreign todos can't be probed.
+    pub fn todo_not_found() -> ServerFnError {
+        ServerFnError::new("Todo not found.")
+    }
+
+    /// Error returned when a todo is given a blank title.
+    pub fn missing_title() -> ServerFnError {
+        ServerFnError::new("A todo needs a title.")
+    }
+
+    /// Error returned when a todo would start after it is due.
+    pub fn starts_after_due() -> ServerFnError {
+        ServerFnError::new("A todo cannot start after it is due.")
+    }
+
+    /// Makes sure `id` refers to a todo owned by `user`.
+    pub async fn authorize_todo(
+        id: u32,
+        user: &User,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        sqlx::query("SELECT id FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL")
+            .bind(id)
+            .bind(user.id)
+            .fetch_optional(pool)
+            .await?
+            .map(|_| ())
+            .ok_or_else(todo_not_found)
+    }
+
+    /// Turns the result of a mutation scoped to the current user into an error
+    /// when no row matched.
+    pub fn ensure_affected(
+        result: sqlx::sqlite::SqliteQueryResult,
+    ) -> Result<(), ServerFnError> {
+        match result.rows_affected() {
+            0 => Err(todo_not_found()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses a recurrence rule submitted by the client, treating blank input as no rule.
+    pub fn parse_rrule(
+        rrule: Option<String>,
+    ) -> Result<Option<Recurrence>, ServerFnError> {
+        rrule
+            .filter(|rule| !rule.trim().is_empty())
+            .map(|rule| rule.parse::<Recurrence>())
+            .transpose()
+            .map_err(ServerFnError::new)
+    }
+
+    /// Parses the value of a `datetime-local` input, entered in the user's time zone.
+    /// Blank input means no date.
+    pub fn parse_datetime(
+        value: Option<String>,
+        user: &User,
+    ) -> Result<Option<DateTime<Utc>>, ServerFnError> {
+        value
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| {
+                NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M")
+                    .or_else(|_| NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M:%S"))
+                    .ok()
+                    .and_then(|local| user.from_local(local))
+                    .ok_or_else(|| ServerFnError::new(format!("Invalid date: {value}")))
+            })
+            .transpose()
+    }
+
+    /// The `[from, to)` window of due dates matching `filter`, as UTC bounds.
+    pub fn due_window(
+        filter: DueFilter,
+        user: &User,
+        now: DateTime<Utc>,
+    ) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let today = user.local_today();
+        let start_of = |date: chrono::NaiveDate| {
+            user.from_local(date.and_hms_opt(0, 0, 0).unwrap())
+        };
+
+        match filter {
+            DueFilter::Overdue => (None, Some(now)),
+            DueFilter::Today => (start_of(today), start_of(today + Duration::days(1))),
+            DueFilter::ThisWeek => {
+                let first = today.week(user.week_start).first_day();
+                (start_of(first), start_of(first + Duration::weeks(1)))
+            }
+        }
+    }
+
+    /// Columns todos are ordered by for `sort`, for the todo aliased `alias`.
+    ///
+    /// They are also compared as a row value against those of the last todo of a page
+    /// to get the next one, so every column is in ascending order and ends with the ID.
+    pub fn sort_columns(sort: TodoSort, alias: &str) -> String {
+        match sort {
+            TodoSort::Manual => format!("{alias}.rank, {alias}.id"),
+            TodoSort::Priority => format!("-{alias}.priority, {alias}.rank, {alias}.id"),
+            TodoSort::Due => format!(
+                "{alias}.due_at IS NULL, COALESCE({alias}.due_at, ''), {alias}.rank, {alias}.id"
+            ),
+            TodoSort::Created => format!("-{alias}.id"),
+        }
+    }
+
+    /// Rank placing a new top-level todo at the end of the list of `user`.
+    pub async fn next_rank<'c>(
+        user: &User,
+        executor: impl sqlx::SqliteExecutor<'c>,
+    ) -> Result<String, ServerFnError> {
+        let last = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT MAX(rank) FROM todos WHERE user_id = ? AND parent_id IS NULL",
+        )
+        .bind(user.id)
+        .fetch_one(executor)
+        .await?;
+
+        rank::between(last.as_deref(), None)
+            .ok_or_else(|| ServerFnError::new("Could not rank the todo."))
+    }
+
+    /// Completes or reopens a todo of `user`, along with its parents completing automatically.
+    ///
+    /// Completing an occurrence of a recurring todo hands the rule over to the next occurrence.
+    pub async fn set_completed(
+        conn: &mut SqliteConnection,
+        user: &User,
         id: u32,
-        user_id: i64,
-        title: String,
-        created_at: String,
         completed: bool,
+    ) -> Result<(), ServerFnError> {
+        use crate::{history::{ssr as history, TodoEventKind}, subtasks::ssr::sync_completion};
+
+        let todo = sqlx::query_as::<_, SqlTodo>(
+            "SELECT * FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(todo_not_found)?;
+
+        if completed == todo.completed {
+            return Ok(());
+        }
+
+        sqlx::query("UPDATE todos SET completed = ?, completed_at = ? WHERE id = ?")
+            .bind(completed)
+            .bind(completed.then(|| Utc::now().naive_utc()))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        let kind = match completed {
+            true => TodoEventKind::Completed,
+            false => TodoEventKind::Reopened,
+        };
+        history::record(&mut *conn, id, user.id, kind).await?;
+
+        if completed {
+            let recurrence = todo.rrule.as_ref().and_then(|rule| rule.parse::<Recurrence>().ok());
+            if let (Some(recurrence), Some(due_at)) = (recurrence, todo.due_at) {
+                // Expanding in local time keeps the wall clock time across DST changes
+                let local = user.to_local(due_at);
+                let next = recurrence
+                    .next_after(local, local)
+                    .and_then(|next| user.from_local(next));
+                if let Some(next) = next {
+                    let start_at = todo.start_at.map(|start_at| start_at + (next - due_at));
+                    let rank = match todo.parent_id {
+                        Some(_) => None,
+                        None => Some(next_rank(user, &mut *conn).await?),
+                    };
+                    let next_id = sqlx::query_scalar::<_, u32>(
+                        "INSERT INTO todos (title, user_id, completed, start_at, due_at, rrule, project_id, parent_id, position, rank, priority) VALUES (?, ?, false, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+                    )
+                    .bind(&todo.title)
+                    .bind(user.id)
+                    .bind(start_at.map(|start_at| start_at.naive_utc()))
+                    .bind(next.naive_utc())
+                    .bind(recurrence.to_string())
+                    .bind(todo.project_id)
+                    .bind(todo.parent_id)
+                    .bind(todo.position)
+                    .bind(rank)
+                    .bind(todo.priority)
+                    .fetch_one(&mut *conn)
+                    .await?;
+                    history::record(&mut *conn, next_id, user.id, TodoEventKind::Created).await?;
+
+                    // Reminders relative to the due date follow the todo to its next occurrence
+                    sqlx::query(
+                        "INSERT INTO reminders (user_id, todo_id, offset_minutes, message)
+                        SELECT user_id, ?, offset_minutes, message FROM reminders
+                        WHERE todo_id = ? AND remind_at IS NULL AND offset_minutes IS NOT NULL",
+                    )
+                    .bind(next_id)
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+                }
+
+                sqlx::query("UPDATE todos SET rrule = NULL WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+
+        sync_completion(conn, todo.parent_id).await?;
+
+        Ok(())
+    }
+
+    /// Changes made to the details of a todo, fields left to `None` are kept as they are.
+    #[derive(Clone, Debug, Default)]
+    pub struct TodoChanges {
+        pub title: Option<String>,
+        pub notes: Option<String>,
+        /// `Some(None)` removes the due date.
+        pub due_at: Option<Option<DateTime<Utc>>>,
+        pub priority: Option<Priority>,
+        /// `Some(None)` takes the todo out of its project.
+        pub project_id: Option<Option<u32>>,
+    }
+
+    /// Changes some details of a todo of `user`, keeping track of them in its history.
+    ///
+    /// The project is expected to be authorized already.
+    pub async fn edit_details(
+        conn: &mut SqliteConnection,
+        user: &User,
+        id: u32,
+        changes: TodoChanges,
+    ) -> Result<(), ServerFnError> {
+        use crate::history::ssr as history;
+
+        let todo = sqlx::query_as::<_, SqlTodo>(
+            "SELECT * FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(todo_not_found)?;
+
+        let TodoChanges {
+            title,
+            notes,
+            due_at,
+            priority,
+            project_id,
+        } = changes;
+        let title = title.map(|title| title.trim().to_string());
+        if title.as_ref().is_some_and(String::is_empty) {
+            return Err(missing_title());
+        }
+        if let (Some(start_at), Some(Some(due_at))) = (todo.start_at, due_at) {
+            if start_at > due_at {
+                return Err(starts_after_due());
+            }
+        }
+
+        if title.is_none() && notes.is_none() && due_at.is_none() && priority.is_none() && project_id.is_none() {
+            return Ok(());
+        }
+
+        let mut query = sqlx::QueryBuilder::<sqlx::Sqlite>::new("UPDATE todos SET ");
+        let mut columns = query.separated(", ");
+        if let Some(title) = title.clone() {
+            columns.push("title = ").push_bind_unseparated(title);
+        }
+        if let Some(notes) = notes.clone() {
+            columns.push("notes = ").push_bind_unseparated(notes);
+        }
+        if let Some(due_at) = due_at {
+            columns
+                .push("due_at = ")
+                .push_bind_unseparated(due_at.map(|due_at| due_at.naive_utc()));
+        }
+        if let Some(priority) = priority {
+            columns.push("priority = ").push_bind_unseparated(priority.level());
+        }
+        if let Some(project_id) = project_id {
+            columns.push("project_id = ").push_bind_unseparated(project_id);
+        }
+        query
+            .push(" WHERE id = ")
+            .push_bind(id)
+            .push(" AND user_id = ")
+            .push_bind(user.id);
+        ensure_affected(query.build().execute(&mut *conn).await?)?;
+
+        if let Some(title) = title {
+            history::record_edit(conn, id, user.id, "title", Some(todo.title), Some(title)).await?;
+        }
+        // Notes can be long, so only the fact they changed is kept
+        if notes.is_some_and(|notes| notes != todo.notes) {
+            history::record_edited(conn, id, user.id, "notes").await?;
+        }
+        if let Some(due_at) = due_at {
+            history::record_edit(
+                conn,
+                id,
+                user.id,
+                "due date",
+                history::date_value(todo.due_at),
+                history::date_value(due_at),
+            )
+            .await?;
+        }
+        if let Some(priority) = priority {
+            history::record_priority(conn, id, user.id, Priority::from_level(todo.priority), priority)
+                .await?;
+        }
+        if let Some(project_id) = project_id {
+            history::record_move(conn, id, user.id, todo.project_id, project_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Changes the priority of a todo of `user`.
+    pub async fn set_priority(
+        conn: &mut SqliteConnection,
+        user: &User,
+        id: u32,
+        priority: Priority,
+    ) -> Result<(), ServerFnError> {
+        use crate::history::ssr as history;
+
+        let previous = sqlx::query_scalar::<_, u8>(
+            "SELECT priority FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(todo_not_found)?;
+
+        sqlx::query("UPDATE todos SET priority = ? WHERE id = ?")
+            .bind(priority.level())
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        history::record_priority(conn, id, user.id, Priority::from_level(previous), priority).await?;
+
+        Ok(())
+    }
+
+    /// Moves a todo of `user` into a project, or out of any project when `project_id` is `None`.
+    ///
+    /// The project is expected to be authorized already.
+    pub async fn set_project(
+        conn: &mut SqliteConnection,
+        user: &User,
+        id: u32,
+        project_id: Option<u32>,
+    ) -> Result<(), ServerFnError> {
+        use crate::history::ssr as history;
+
+        let previous = sqlx::query_scalar::<_, Option<u32>>(
+            "SELECT project_id FROM todos WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(todo_not_found)?;
+
+        sqlx::query("UPDATE todos SET project_id = ? WHERE id = ?")
+            .bind(project_id)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        history::record_move(conn, id, user.id, previous, project_id).await?;
+
+        Ok(())
+    }
+
+    /// Moves a top-level todo of `user` right after `after_id`, or right before `before_id`.
+    ///
+    /// Todos added at the same time can end up with the same rank, in which case the ranks of
+    /// the whole list are spread out again before moving.
+    pub async fn move_between(
+        user: &User,
+        id: u32,
+        after_id: Option<u32>,
+        before_id: Option<u32>,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        let mut tx = pool.begin().await?;
+
+        let mut rank = None;
+        for attempt in 0..2 {
+            if attempt > 0 {
+                respace_ranks(user, &mut tx).await?;
+            }
+            let (after, before) = neighbour_ranks(user, id, after_id, before_id, &mut tx).await?;
+            rank = rank::between(after.as_deref(), before.as_deref());
+            if rank.is_some() {
+                break;
+            }
+        }
+        let rank = rank.ok_or_else(|| ServerFnError::new("Could not move the todo, try again."))?;
+
+        ensure_affected(
+            sqlx::query("UPDATE todos SET rank = ? WHERE id = ? AND user_id = ? AND parent_id IS NULL")
+                .bind(rank)
+                .bind(id)
+                .bind(user.id)
+                .execute(&mut *tx)
+                .await?,
+        )?;
+
+        Ok(tx.commit().await?)
+    }
+
+    /// Ranks of the todos `id` is moved between, see [`move_between`].
+    async fn neighbour_ranks(
+        user: &User,
+        id: u32,
+        after_id: Option<u32>,
+        before_id: Option<u32>,
+        conn: &mut SqliteConnection,
+    ) -> Result<(Option<String>, Option<String>), ServerFnError> {
+        let rank_of = |other: u32| {
+            sqlx::query_scalar::<_, Option<String>>(
+                "SELECT rank FROM todos WHERE id = ? AND id != ? AND user_id = ? AND parent_id IS NULL AND deleted_at IS NULL",
+            )
+            .bind(other)
+            .bind(id)
+            .bind(user.id)
+        };
+        // The other neighbour is the todo next to the given one, skipping the moved todo
+        let neighbour = |other: u32, after: bool| {
+            sqlx::query_scalar::<_, Option<String>>(if after {
+                "SELECT t.rank FROM todos t, todos o WHERE o.id = ? AND t.id != ? AND t.user_id = ?
+                AND t.parent_id IS NULL AND t.deleted_at IS NULL AND (t.rank, t.id) > (o.rank, o.id) ORDER BY t.rank, t.id LIMIT 1"
+            } else {
+                "SELECT t.rank FROM todos t, todos o WHERE o.id = ? AND t.id != ? AND t.user_id = ?
+                AND t.parent_id IS NULL AND t.deleted_at IS NULL AND (t.rank, t.id) < (o.rank, o.id) ORDER BY t.rank DESC, t.id DESC LIMIT 1"
+            })
+            .bind(other)
+            .bind(id)
+            .bind(user.id)
+        };
+
+        match (after_id, before_id) {
+            (Some(after_id), _) => {
+                let after = rank_of(after_id).fetch_optional(&mut *conn).await?.ok_or_else(todo_not_found)?;
+                let before = neighbour(after_id, true).fetch_optional(&mut *conn).await?.flatten();
+                Ok((after, before))
+            }
+            (None, Some(before_id)) => {
+                let before = rank_of(before_id).fetch_optional(&mut *conn).await?.ok_or_else(todo_not_found)?;
+                let after = neighbour(before_id, false).fetch_optional(&mut *conn).await?.flatten();
+                Ok((after, before))
+            }
+            (None, None) => Err(ServerFnError::new("A todo needs a place to move to.")),
+        }
+    }
+
+    /// Gives every top-level todo of `user` a distinct rank, keeping their order.
+    async fn respace_ranks(user: &User, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+        let ids = sqlx::query_scalar::<_, u32>(
+            "SELECT id FROM todos WHERE user_id = ? AND parent_id IS NULL ORDER BY rank, id",
+        )
+        .bind(user.id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for (id, rank) in ids.iter().zip(rank::spread(ids.len())) {
+            sqlx::query("UPDATE todos SET rank = ? WHERE id = ?")
+                .bind(rank)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads a page of the top-level todos of `user`, see [`super::get_todos`].
+    ///
+    /// Every todo is owned by `user`, so the whole page is loaded with a single query,
+    /// plus one for the tags of its todos.
+    /// Pages are keyed by the last todo ID of the previous page, which keeps them stable while
+    /// todos are added, moved or removed.
+    pub async fn load_page(
+        user: &User,
+        project: Option<u32>,
+        filter: &TodoFilter,
+        sort: Option<TodoSort>,
+        after: Option<u32>,
+        limit: Option<u32>,
+        pool: &SqlitePool,
+    ) -> Result<TodoPage, ServerFnError> {
+        use super::{MAX_TODO_PAGE_SIZE, TODO_PAGE_SIZE};
+        use crate::tags::{ssr::{json_ids, load_tags}, TagMatch};
+
+        let (due_from, due_to) = filter
+            .due
+            .map(|filter| due_window(filter, user, Utc::now()))
+            .unwrap_or_default();
+        let pending_only = filter.due == Some(DueFilter::Overdue);
+        let limit = limit.unwrap_or(TODO_PAGE_SIZE).clamp(1, MAX_TODO_PAGE_SIZE);
+        let sort = sort.unwrap_or(user.todo_sort);
+        let columns = sort_columns(sort, "t");
+        let cursor_columns = sort_columns(sort, "c");
+
+        let mut tags = filter.tags.clone();
+        tags.sort_unstable();
+        tags.dedup();
+        // Number of the given tags a todo needs to be listed
+        let required_tags = match filter.tag_match {
+            TagMatch::Any => 1,
+            TagMatch::All => tags.len(),
+        };
+        let tags = (!tags.is_empty()).then(|| json_ids(tags));
+
+        // Pages continue after a todo of the user, even one trashed since
+        if let Some(after) = after {
+            sqlx::query("SELECT id FROM todos WHERE id = ? AND user_id = ?")
+                .bind(after)
+                .bind(user.id)
+                .fetch_optional(pool)
+                .await?
+                .ok_or_else(|| ServerFnError::new("Invalid page cursor, reload the todos."))?;
+        }
+
+        // One extra row tells whether there is a next page
+        let mut todos = sqlx::query_as::<_, SqlTodo>(&format!(
+            "{SELECT_TODOS} WHERE t.user_id = ? AND t.parent_id IS NULL AND t.deleted_at IS NULL
+            AND (? IS NULL OR t.project_id = ?)
+            AND (? IS NULL OR t.due_at >= ?) AND (? IS NULL OR t.due_at < ?)
+            AND (? = false OR t.completed = false)
+            AND (? IS NULL OR (
+                SELECT COUNT(*) FROM todo_tags tt
+                WHERE tt.todo_id = t.id AND tt.tag_id IN (SELECT value FROM json_each(?))
+            ) >= ?)
+            AND (? IS NULL OR ({columns}) > (SELECT {cursor_columns} FROM todos c WHERE c.id = ? AND c.user_id = ?))
+            ORDER BY {columns} LIMIT ?"
+        ))
+        .bind(user.id)
+        .bind(project)
+        .bind(project)
+        .bind(due_from.map(|from| from.naive_utc()))
+        .bind(due_from.map(|from| from.naive_utc()))
+        .bind(due_to.map(|to| to.naive_utc()))
+        .bind(due_to.map(|to| to.naive_utc()))
+        .bind(pending_only)
+        .bind(&tags)
+        .bind(&tags)
+        .bind(required_tags as u32)
+        .bind(after)
+        .bind(after)
+        .bind(user.id)
+        .bind(limit + 1)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|todo| todo.into_todo(user))
+        .collect::<Vec<_>>();
+
+        let next_cursor = if todos.len() > limit as usize {
+            todos.truncate(limit as usize);
+            todos.last().map(|todo| todo.id)
+        } else {
+            None
+        };
+        load_tags(&mut todos, pool).await?;
+
+        Ok(TodoPage {
+            sort,
+            todos,
+            next_cursor,
+        })
     }
 
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlTodo {
+        pub id: u32,
+        pub user_id: i64,
+        pub title: String,
+        pub notes: String,
+        pub created_at: DateTime<Utc>,
+        pub completed: bool,
+        pub completed_at: Option<DateTime<Utc>>,
+        pub start_at: Option<DateTime<Utc>>,
+        pub due_at: Option<DateTime<Utc>>,
+        pub rrule: Option<String>,
+        pub project_id: Option<u32>,
+        pub parent_id: Option<u32>,
+        pub position: i64,
+        pub rank: Option<String>,
+        pub priority: u8,
+        pub auto_complete: bool,
+        #[sqlx(default)]
+        pub subtasks: u32,
+        #[sqlx(default)]
+        pub subtasks_completed: u32,
+    }
+
+    /// Todos along with their subtask counts, aliased as `t`.
+    pub const SELECT_TODOS: &str = "SELECT t.*,
+        (SELECT COUNT(*) FROM todos c WHERE c.parent_id = t.id AND c.deleted_at IS NULL) AS subtasks,
+        (SELECT COUNT(*) FROM todos c
+            WHERE c.parent_id = t.id AND c.deleted_at IS NULL AND c.completed) AS subtasks_completed
+        FROM todos t";
+
     impl SqlTodo {
-        pub async fn into_todo(self, pool: &SqlitePool) -> Todo {
+        /// Builds the todo from its row, `owner` being the already loaded user it belongs to.
+        pub fn into_todo(self, owner: &User) -> Todo {
+            debug_assert_eq!(self.user_id, owner.id);
             Todo {
                 id: self.id,
-                user: User::get(self.user_id, pool).await,
+                user: Some(owner.clone()),
                 title: self.title,
+                notes: Notes::new(self.notes),
                 created_at: self.created_at,
                 completed: self.completed,
+                completed_at: self.completed_at,
+                start_at: self.start_at,
+                due_at: self.due_at,
+                recurrence: self.rrule.and_then(|rule| rule.parse().ok()),
+                project_id: self.project_id,
+                parent_id: self.parent_id,
+                priority: Priority::from_level(self.priority),
+                auto_complete: self.auto_complete,
+                subtasks: self.subtasks,
+                subtasks_completed: self.subtasks_completed,
+                tags: Vec::new(),
             }
         }
     }
 }
 
+/// Lists the top-level todos of the current user, one page at a time.
+///
+/// Todos are sorted by `sort`, or else by the last sort picked by the user.
 #[server(GetTodos, "/api")]
-pub async fn get_todos() -> Result<Vec<Todo>, ServerFnError> {
-    use self::ssr::{pool, SqlTodo};
-    use futures::future::join_all;
+pub async fn get_todos(
+    project: Option<u32>,
+    due: Option<DueFilter>,
+    tags: Option<Vec<u32>>,
+    tag_match: Option<TagMatch>,
+    sort: Option<TodoSort>,
+    after: Option<u32>,
+    limit: Option<u32>,
+) -> Result<TodoPage, ServerFnError> {
+    use self::ssr::{load_page, pool};
 
-    let user = get_user().await?;
+    let user = get_user().await?.unwrap_or_default();
     let pool = pool()?;
-
-    let id = match user {
-        Some(user) => user.id,
-        None => -1,
+    let filter = TodoFilter {
+        due,
+        tags: tags.unwrap_or_default(),
+        tag_match: tag_match.unwrap_or_default(),
     };
 
-    Ok(join_all(
-        sqlx::query_as::<_, SqlTodo>("SELECT * FROM todos WHERE user_id = ?")
-            .bind(id)
-            .fetch_all(&pool)
-            .await?
-            .iter()
-            .map(|todo: &SqlTodo| todo.clone().into_todo(&pool)),
-    )
-    .await)
+    load_page(&user, project, &filter, sort, after, limit, &pool).await
 }
 
 #[server(AddTodo, "/api")]
-pub async fn add_todo(title: String) -> Result<(), ServerFnError> {
+pub async fn add_todo(
+    title: String,
+    start_at: Option<String>,
+    due_at: Option<String>,
+    rrule: Option<String>,
+    project_id: Option<u32>,
+    priority: Option<Priority>,
+    smart: Option<String>,
+) -> Result<(), ServerFnError> {
     use self::ssr::*;
+    use crate::{history::{ssr as history, TodoEventKind}, projects::ssr::{authorize_project, find_project}, quick_add, tags::ssr::tag_by_names};
 
     let user = get_user().await?;
     let pool = pool()?;
 
     if let Some(user) = user {
+        // Fields typed in the title win over the ones picked in the form
+        let quick_add = match smart {
+            Some(_) => quick_add::parse(&title, user.to_local(Utc::now())),
+            None => QuickAdd::plain(&title),
+        };
+        let priority = quick_add.priority.or(priority).unwrap_or_default();
+        let recurrence = match quick_add.recurrence {
+            Some(recurrence) => Some(recurrence),
+            None => parse_rrule(rrule)?,
+        };
+        let start_at = parse_datetime(start_at, &user)?;
+        let mut due_at = match quick_add.due {
+            Some(due) => Some(
+                user.from_local(due)
+                    .ok_or_else(|| ServerFnError::new(format!("Invalid date: {due}")))?,
+            ),
+            None => parse_datetime(due_at, &user)?,
+        };
+        if let (Some(start_at), Some(due_at)) = (start_at, due_at) {
+            if start_at > due_at {
+                return Err(starts_after_due());
+            }
+        }
+        let project_id = match &quick_add.project {
+            Some(name) => Some(find_project(name, &user, &pool).await?),
+            None => {
+                if let Some(project_id) = project_id {
+                    authorize_project(project_id, &user, &pool).await?;
+                }
+                project_id
+            }
+        };
+
+        // A recurrence rule needs a first occurrence to expand from
+        if recurrence.is_some() && due_at.is_none() {
+            due_at = Some(Utc::now());
+        }
+
         // Fake API delay
         std::thread::sleep(std::time::Duration::from_millis(1250));
 
-        Ok(sqlx::query(
-            "INSERT INTO todos (title, user_id, completed) VALUES (?, ?, false)",
+        let mut tx = pool.begin().await?;
+        let rank = next_rank(&user, &mut *tx).await?;
+
+        let id = sqlx::query_scalar::<_, u32>(
+            "INSERT INTO todos (title, user_id, completed, start_at, due_at, rrule, project_id, rank, priority) VALUES (?, ?, false, ?, ?, ?, ?, ?, ?) RETURNING id",
         )
-        .bind(title)
+        .bind(quick_add.title)
         .bind(user.id)
-        .execute(&pool)
-        .await
-        .map(|_| ())?)
+        .bind(start_at.map(|start_at| start_at.naive_utc()))
+        .bind(due_at.map(|due_at| due_at.naive_utc()))
+        .bind(recurrence.map(|recurrence| recurrence.to_string()))
+        .bind(project_id)
+        .bind(rank)
+        .bind(priority.level())
+        .fetch_one(&mut *tx)
+        .await?;
+        history::record(&mut tx, id, user.id, TodoEventKind::Created).await?;
+        tag_by_names(id, &quick_add.tags, &user, &mut tx).await?;
+
+        Ok(tx.commit().await?)
     } else {
         Err(ServerFnError::new("User needs to be loggind in."))
     }
@@ -106,39 +1040,209 @@ pub async fn add_todo(title: String) -> Result<(), ServerFnError> {
 pub async fn update_todo(id: u32, completed: bool) -> Result<(), ServerFnError> {
     use self::ssr::*;
 
-    let user = get_user().await?;
+    let user = require_user()?;
     let pool = pool()?;
+    let mut tx = pool.begin().await?;
 
-    if let Some(_) = user {
-        Ok(sqlx::query(
-            "UPDATE todos SET completed = $2 WHERE id = $1",
-        )
-        .bind(id)
-        .bind(completed)
+    set_completed(&mut tx, &user, id, completed).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Moves a todo into a project, or out of any project when `project_id` is `None`.
+#[server(SetTodoProject, "/api")]
+pub async fn set_todo_project(
+    id: u32,
+    project_id: Option<u32>,
+) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::projects::ssr::authorize_project;
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    if let Some(project_id) = project_id {
+        authorize_project(project_id, &user, &pool).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    set_project(&mut tx, &user, id, project_id).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Changes some details of a todo, fields left out are kept as they are.
+///
+/// A blank `due_at` removes the due date, and a blank `project_id` takes the todo out of its project.
+#[server(name = EditTodo, prefix = "/api", input = server_fn::codec::Json)]
+pub async fn edit_todo(
+    id: u32,
+    title: Option<String>,
+    notes: Option<String>,
+    due_at: Option<String>,
+    priority: Option<Priority>,
+    project_id: Option<String>,
+) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::{projects::ssr::authorize_project, scheduler::SchedulerHandle};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let due_at = due_at.map(|due_at| parse_datetime(Some(due_at), &user)).transpose()?;
+    let project_id = project_id
+        .map(|project_id| match project_id.trim() {
+            "" => Ok(None),
+            project_id => project_id
+                .parse::<u32>()
+                .map(Some)
+                .map_err(|_| ServerFnError::new(format!("Invalid project: {project_id}"))),
+        })
+        .transpose()?;
+    if let Some(Some(project_id)) = project_id {
+        authorize_project(project_id, &user, &pool).await?;
+    }
+    let changes = TodoChanges {
+        title,
+        notes,
+        due_at,
+        priority,
+        project_id,
+    };
+    let due_changed = changes.due_at.is_some();
+
+    let mut tx = pool.begin().await?;
+    edit_details(&mut tx, &user, id, changes).await?;
+    tx.commit().await?;
+
+    // Reminders relative to the due date may now fire earlier
+    if due_changed {
+        if let Some(scheduler) = use_context::<SchedulerHandle>() {
+            scheduler.wake();
+        }
+    }
+
+    Ok(())
+}
+
+#[server(SetTodoPriority, "/api")]
+pub async fn set_todo_priority(id: u32, priority: Priority) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let mut tx = pool.begin().await?;
+
+    set_priority(&mut tx, &user, id, priority).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Picks the order of the todo list of the current user.
+#[server(SetTodoSort, "/api")]
+pub async fn set_todo_sort(sort: TodoSort) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    sqlx::query("UPDATE users SET todo_sort = ? WHERE id = ?")
+        .bind(sort.as_str())
+        .bind(user.id)
         .execute(&pool)
-        .await
-        .map(|_| ())?)
-    } else {
-        Err(ServerFnError::new("User needs to be loggind in."))
+        .await?;
+
+    // The session caches the user, drop it so the new sort is picked up
+    auth()?.cache_clear_user(user.id);
+
+    Ok(())
+}
+
+/// Moves a top-level todo right after `after_id`, or right before `before_id`.
+///
+/// Only the moved todo is updated, its new rank is picked between its new neighbours.
+#[server(MoveTodo, "/api")]
+pub async fn move_todo(
+    id: u32,
+    after_id: Option<u32>,
+    before_id: Option<u32>,
+) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    move_between(&user, id, after_id, before_id, &pool).await
+}
+
+#[server(GetOccurrences, "/api")]
+pub async fn get_occurrences(
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<Occurrence>, ServerFnError> {
+    use self::ssr::*;
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    // The range is given in the user's time zone
+    if end < start || (end - start).num_days() > 366 {
+        return Err(ServerFnError::new(
+            "Date range must be ordered and at most a year long.",
+        ));
     }
+    let start = start.and_hms_opt(0, 0, 0).unwrap();
+    let end = end.and_hms_opt(23, 59, 59).unwrap();
+
+    let todos = sqlx::query_as::<_, SqlTodo>(
+        "SELECT * FROM todos WHERE user_id = ? AND rrule IS NOT NULL AND due_at IS NOT NULL
+        AND completed = false AND deleted_at IS NULL",
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?;
+
+    let mut occurrences = todos
+        .into_iter()
+        .filter_map(|todo| {
+            let recurrence = todo.rrule.as_ref()?.parse::<Recurrence>().ok()?;
+            let dtstart = user.to_local(todo.due_at?);
+            let occurrences = recurrence
+                .occurrences(dtstart, start, end)
+                .into_iter()
+                .filter_map(|at| user.from_local(at))
+                .map(|at| Occurrence {
+                    todo_id: todo.id,
+                    title: todo.title.clone(),
+                    at,
+                })
+                .collect::<Vec<_>>();
+            Some(occurrences)
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    occurrences.sort_by_key(|occurrence| occurrence.at);
+
+    Ok(occurrences)
 }
 
+/// Moves a todo to the trash, see [`crate::trash`].
 #[server(DeleteTodo, "/api")]
-pub async fn delete_todo(id: u16) -> Result<(), ServerFnError> {
+pub async fn delete_todo(id: u32) -> Result<(), ServerFnError> {
     use self::ssr::*;
+    use crate::trash::ssr::trash_todo;
 
-    let user = get_user().await?;
+    let user = require_user()?;
     let pool = pool()?;
+    let mut tx = pool.begin().await?;
 
-    if let Some(_) = user {
-        Ok(sqlx::query("DELETE FROM todos WHERE id = $1")
-            .bind(id)
-            .execute(&pool)
-            .await
-            .map(|_| ())?)
-    } else {
-        Err(ServerFnError::new("User needs to be loggind in."))
-    }
+    trash_todo(&mut tx, &user, id, Utc::now()).await?;
+    tx.commit().await?;
+
+    Ok(())
 }
 
 #[component]
@@ -146,6 +1250,7 @@ pub fn TodoApp() -> impl IntoView {
     let login = create_server_action::<Login>();
     let signup = create_server_action::<Signup>();
     let logout = create_server_action::<Logout>();
+    let verify_two_factor = create_server_action::<VerifyTwoFactor>();
 
     let user = create_resource(
         move || {
@@ -153,17 +1258,29 @@ pub fn TodoApp() -> impl IntoView {
                 login.version().get(),
                 signup.version().get(),
                 logout.version().get(),
+                verify_two_factor.version().get(),
             )
         },
         move |_| get_user(),
     );
     provide_meta_context();
+    provide_context::<UserResource>(user);
+    NotificationActions::provide();
 
     view! {
         <Title text="Todo App"/>
         <Link rel="shortcut icon" type_="image/ico" href="/favicon.ico"/>
         <Stylesheet id="leptos" href="/pkg/kreqo-habits.css"/>
-        <Html lang="en" class="h-full bg-base-200"/>
+        <Html
+            lang="en"
+            class="h-full bg-base-200"
+            attr:data-theme=move || {
+                user.get()
+                    .and_then(Result::ok)
+                    .flatten()
+                    .and_then(|user| user.theme.data_theme())
+            }
+        />
         <Body class="h-full flex flex-col"/>
         <Router>
             <header class="navbar bg-base-100 px-6">
@@ -171,7 +1288,20 @@ pub fn TodoApp() -> impl IntoView {
                     <A href="/" class="btn btn-ghost">
                         <h1 class="text-2xl font-bold text-primary">"My Tasks"</h1>
                     </A>
+                    <A href="/projects" class="btn btn-ghost text-lg">
+                        "Projects"
+                    </A>
+                    <A href="/habits" class="btn btn-ghost text-lg">
+                        "Habits"
+                    </A>
+                    <A href="/tags" class="btn btn-ghost text-lg">
+                        "Tags"
+                    </A>
+                    <A href="/stats" class="btn btn-ghost text-lg">
+                        "Stats"
+                    </A>
                 </div>
+                <SearchBox/>
                 <div class="flex-none">
                     <Transition fallback=move || {
                         view! { <span class="loading loading-spinner"></span> }
@@ -200,6 +1330,7 @@ pub fn TodoApp() -> impl IntoView {
                                     Ok(None) => login_section(),
                                     Ok(Some(user)) => {
                                         view! {
+                                            <NotificationBell user=user.clone()/>
                                             <div class="dropdown relative">
                                                 <div
                                                     tabindex="0"
@@ -213,7 +1344,14 @@ pub fn TodoApp() -> impl IntoView {
                                                     class="dropdown-content z-[1] menu relative right-0 mt-1 p-2 w-52 bg-base-200 border border-neutral rounded-xl"
                                                 >
                                                     <li>
-                                                        <a class="btn btn-ghost text-lg">"Settings"</a>
+                                                        <A href="/trash" class="btn btn-ghost text-lg">
+                                                            "Trash"
+                                                        </A>
+                                                    </li>
+                                                    <li>
+                                                        <A href="/settings" class="btn btn-ghost text-lg">
+                                                            "Settings"
+                                                        </A>
                                                     </li>
                                                     <li>
                                                         <a
@@ -240,9 +1378,22 @@ pub fn TodoApp() -> impl IntoView {
             </header>
             <main class="flex-1">
                 <Routes>
-                    <Route path="" view=Todos/>
+                    <Route path="" view=move || view! { <Todos/> }/>
+                    <Route path="projects" view=Projects/>
+                    <Route path="projects/:id" view=ProjectView/>
+                    <Route path="habits" view=Habits/>
+                    <Route path="tags" view=Tags/>
+                    <Route path="search" view=Search/>
+                    <Route path="notifications" view=Notifications/>
+                    <Route path="trash" view=Trash/>
+                    <Route path="stats" view=Stats/>
+                    <Route path="settings" view=Settings/>
                     <Route path="signup" view=move || view! { <Signup action=signup/> }/>
                     <Route path="login" view=move || view! { <Login action=login/> }/>
+                    <Route
+                        path="login/2fa"
+                        view=move || view! { <TwoFactorLogin action=verify_two_factor/> }
+                    />
                 </Routes>
             </main>
         </Router>
@@ -250,28 +1401,221 @@ pub fn TodoApp() -> impl IntoView {
 }
 
 #[component]
-pub fn Todos() -> impl IntoView {
+pub fn Todos(
+    /// Only show todos of this project, and add new ones to it.
+    #[prop(optional)]
+    project: Option<u32>,
+) -> impl IntoView {
     let add_todo = create_server_multi_action::<AddTodo>();
     let delete_todo = create_server_action::<DeleteTodo>();
+    let restore_todo = create_server_action::<RestoreTodo>();
+    let set_project = create_server_action::<SetTodoProject>();
+    let move_todo = create_server_action::<MoveTodo>();
+    let tag_todo = create_server_action::<TagTodo>();
+    let set_priority = create_server_action::<SetTodoPriority>();
+    let set_sort = create_server_action::<SetTodoSort>();
+    let bulk_edit = create_server_action::<BulkEditTodos>();
     let submissions = add_todo.submissions();
+    // Index of the todo being dragged around in the current page
+    let (dragged, set_dragged) = create_signal(None::<usize>);
+    // Todos picked for a bulk edit, and the index of the last one clicked to select ranges from
+    let (selecting, set_selecting) = create_signal(false);
+    let selected = create_rw_signal(HashSet::<u32>::new());
+    let anchor = store_value(None::<usize>);
+
+    let query = use_query_map();
+    let filter = move || query.with(TodoFilter::from_query);
+    let after = move || query.with(|query| query.get("after").and_then(|after| after.parse().ok()));
 
     // List of todos is loaded from the server in reaction to changes
     let todos = create_resource(
-        move || (add_todo.version().get(), delete_todo.version().get()),
-        move |_| get_todos(),
+        move || {
+            (
+                add_todo.version().get(),
+                delete_todo.version().get(),
+                restore_todo.version().get(),
+                set_project.version().get(),
+                move_todo.version().get(),
+                tag_todo.version().get(),
+                set_priority.version().get(),
+                set_sort.version().get(),
+                bulk_edit.version().get(),
+                filter(),
+                after(),
+            )
+        },
+        move |(_, _, _, _, _, _, _, _, _, filter, after)| async move {
+            let tags = Some(filter.tags).filter(|tags| !tags.is_empty());
+            get_todos(project, filter.due, tags, Some(filter.tag_match), None, after, None).await
+        },
     );
+    let sort = move || todos.get().and_then(Result::ok).map(|page| page.sort).unwrap_or_default();
+    let projects = create_resource(|| (), move |_| get_projects());
+    let tags = create_resource(|| (), move |_| get_tags());
+
+    let page_ids = Signal::derive(move || {
+        todos
+            .get()
+            .and_then(Result::ok)
+            .map(|page| page.todos.iter().map(|todo| todo.id).collect::<Vec<u32>>())
+            .unwrap_or_default()
+    });
+    // Shift-clicking a todo selects every todo since the last one clicked along with it
+    let select = move |index: usize, shift: bool| {
+        let ids = page_ids.get_untracked();
+        let Some(id) = ids.get(index) else {
+            return;
+        };
+        let checked = !selected.with_untracked(|selected| selected.contains(id));
+        let from = anchor
+            .get_value()
+            .filter(|from| shift && *from < ids.len())
+            .unwrap_or(index);
+        selected.update(|selected| {
+            for id in &ids[from.min(index)..=from.max(index)] {
+                if checked {
+                    selected.insert(*id);
+                } else {
+                    selected.remove(id);
+                }
+            }
+        });
+        anchor.set_value(Some(index));
+    };
+    // Selections only make sense for the todos they were made on
+    create_effect(move |_| {
+        let _ = (filter(), after(), bulk_edit.version().get(), selecting.get());
+        selected.set(HashSet::new());
+        anchor.set_value(None);
+    });
+
+    // Preview of the fields the server will find in the title being typed, when asked to
+    let user = use_context::<UserResource>();
+    let (title, set_title) = create_signal(String::new());
+    let (smart, set_smart) = create_signal(false);
+    let quick_add = move || {
+        let user = user
+            .and_then(|user| user.get())
+            .and_then(Result::ok)
+            .flatten()
+            .unwrap_or_default();
+        match smart.get() {
+            true => quick_add::parse(&title.get(), user.to_local(Utc::now())),
+            false => QuickAdd::plain(&title.get()),
+        }
+    };
 
     view! {
         <Container>
             <MultiActionForm action=add_todo class="flex items-center gap-4 mb-4">
                 <label class="input input-bordered flex items-center flex-1 text-xl gap-4">
                     <span class="text-primary">"Todo Title"</span>
-                    <input type="text" name="title"/>
+                    <input
+                        type="text"
+                        name="title"
+                        placeholder="Pay rent every month on the 1st #finance !high @Home"
+                        on:input=move |ev| set_title.set(event_target_value(&ev))
+                    />
                 </label>
+                <label
+                    class="flex items-center gap-2 text-lg"
+                    title="Find dates, repeats, #tags, !priority and @project in the title. Words in \"quotes\" are kept as they are."
+                >
+                    <input
+                        type="checkbox"
+                        name="smart"
+                        class="checkbox checkbox-accent"
+                        on:change=move |ev| set_smart.set(event_target_checked(&ev))
+                    />
+                    "Smart add"
+                </label>
+                <label class="input input-bordered flex items-center text-xl gap-4">
+                    <span class="text-primary">"Start"</span>
+                    <input type="datetime-local" name="start_at"/>
+                </label>
+                <label class="input input-bordered flex items-center text-xl gap-4">
+                    <span class="text-primary">"Due"</span>
+                    <input type="datetime-local" name="due_at"/>
+                </label>
+                {project.map(|project| view! { <input type="hidden" name="project_id" value=project/> })}
+                <select name="rrule" class="select select-bordered text-xl">
+                    <option value="">"Once"</option>
+                    <option value="FREQ=DAILY">"Daily"</option>
+                    <option value="FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR">"Weekdays"</option>
+                    <option value="FREQ=WEEKLY">"Weekly"</option>
+                    <option value="FREQ=MONTHLY">"Monthly"</option>
+                    <option value="FREQ=YEARLY">"Yearly"</option>
+                </select>
+                <select name="priority" class="select select-bordered text-xl">
+                    {Priority::ALL
+                        .into_iter()
+                        .map(|priority| {
+                            view! { <option value=priority.as_str()>{priority.label()}</option> }
+                        })
+                        .collect_view()}
+                </select>
                 <button type="submit" class="btn btn-primary text-lg">
                     "Add Todo"
                 </button>
             </MultiActionForm>
+            {move || {
+                let quick_add = quick_add();
+                (!quick_add.is_plain()).then(|| view! { <QuickAddPreview quick_add/> })
+            }}
+
+            <div class="flex items-start gap-4">
+                <div class="flex-1">
+                    <DueFilterTabs filter/>
+                </div>
+                <Transition>
+                    <select
+                        class="select select-bordered text-lg"
+                        prop:value=move || sort().as_str()
+                        on:change=move |ev| {
+                            if let Some(sort) = TodoSort::parse(&event_target_value(&ev)) {
+                                set_sort.dispatch(SetTodoSort { sort });
+                            }
+                        }
+                    >
+                        {TodoSort::ALL
+                            .into_iter()
+                            .map(|option| {
+                                view! {
+                                    <option value=option.as_str() selected=move || sort() == option>
+                                        "Sort: "
+                                        {option.label()}
+                                    </option>
+                                }
+                            })
+                            .collect_view()}
+                    </select>
+                </Transition>
+                <button
+                    class="btn btn-ghost text-lg"
+                    class:btn-active=selecting
+                    on:click=move |_| set_selecting.update(|selecting| *selecting = !*selecting)
+                >
+                    "Select"
+                </button>
+            </div>
+            <Transition>
+                {move || {
+                    tags.get()
+                        .and_then(Result::ok)
+                        .filter(|tags| !tags.is_empty())
+                        .map(|tags| view! { <TagFilterBar filter tags/> })
+                }}
+
+            </Transition>
+            <Show when=move || selecting.get()>
+                <BulkBar
+                    ids=page_ids
+                    selected
+                    bulk_edit
+                    projects=Signal::derive(move || projects.get().and_then(Result::ok).unwrap_or_default())
+                    tags=Signal::derive(move || tags.get().and_then(Result::ok).unwrap_or_default())
+                />
+            </Show>
             <Transition fallback=move || view! { <p>"Loading..."</p> }>
                 <ErrorBoundary fallback=|errors| {
                     view! { <ErrorTemplate errors=errors/> }
@@ -288,20 +1632,111 @@ pub fn Todos() -> impl IntoView {
                                             }
                                                 .into_view()
                                         }
-                                        Ok(todos) => {
-                                            if todos.is_empty() {
+                                        Ok(page) => {
+                                            let projects = projects
+                                                .get()
+                                                .and_then(Result::ok)
+                                                .unwrap_or_default();
+                                            let all_tags = tags
+                                                .get()
+                                                .and_then(Result::ok)
+                                                .unwrap_or_default();
+                                            if page.todos.is_empty() {
                                                 view! { <p>"No tasks were found."</p> }.into_view()
                                             } else {
-                                                todos
-                                                    .into_iter()
-                                                    .map(move |todo| {
-                                                        view! {
-                                                            <li>
-                                                                <Todo todo delete_todo/>
-                                                            </li>
-                                                        }
-                                                    })
-                                                    .collect_view()
+                                                // Moving todos around only makes sense in the manual order
+                                                let manual = page.sort == TodoSort::Manual;
+                                                let ids: Vec<u32> = page
+                                                    .todos
+                                                    .iter()
+                                                    .map(|todo| todo.id)
+                                                    .collect();
+                                                view! {
+                                                    {page
+                                                        .todos
+                                                        .into_iter()
+                                                        .enumerate()
+                                                        .map(move |(index, todo)| {
+                                                            let projects = projects.clone();
+                                                            let all_tags = all_tags.clone();
+                                                            let ids = ids.clone();
+                                                            let up = index
+                                                                .checked_sub(1)
+                                                                .map(|previous| ids[previous])
+                                                                .filter(|_| manual);
+                                                            let down = ids
+                                                                .get(index + 1)
+                                                                .copied()
+                                                                .filter(|_| manual);
+                                                            let id = ids[index];
+                                                            let on_drop = move |ev: ev::DragEvent| {
+                                                                ev.prevent_default();
+                                                                let Some(from) = dragged.get_untracked() else {
+                                                                    return;
+                                                                };
+                                                                set_dragged.set(None);
+                                                                if from == index {
+                                                                    return;
+                                                                }
+                                                                let (after_id, before_id) = match from < index {
+                                                                    true => (Some(ids[index]), None),
+                                                                    false => (None, Some(ids[index])),
+                                                                };
+                                                                move_todo
+                                                                    .dispatch(MoveTodo {
+                                                                        id: ids[from],
+                                                                        after_id,
+                                                                        before_id,
+                                                                    });
+                                                            };
+                                                            view! {
+                                                                <li
+                                                                    draggable=manual.to_string()
+                                                                    class:opacity-50=move || dragged.get() == Some(index)
+                                                                    on:dragstart=move |_| set_dragged.set(Some(index))
+                                                                    on:dragend=move |_| set_dragged.set(None)
+                                                                    on:dragover=|ev| ev.prevent_default()
+                                                                    on:drop=on_drop
+                                                                >
+                                                                    <div class="flex items-start gap-2">
+                                                                        <Show when=move || selecting.get()>
+                                                                            <div class="h-12 flex items-center">
+                                                                                <input
+                                                                                    type="checkbox"
+                                                                                    class="checkbox checkbox-primary"
+                                                                                    prop:checked=move || {
+                                                                                        selected.with(|selected| selected.contains(&id))
+                                                                                    }
+                                                                                    on:click=move |ev| select(index, ev.shift_key())
+                                                                                />
+                                                                            </div>
+                                                                        </Show>
+                                                                        <div class="flex-1">
+                                                                            <Todo
+                                                                                todo
+                                                                                projects
+                                                                                delete_todo
+                                                                                set_project
+                                                                                move_todo
+                                                                                tag_todo
+                                                                                set_priority
+                                                                                all_tags
+                                                                                up
+                                                                                down
+                                                                            />
+                                                                        </div>
+                                                                    </div>
+                                                                </li>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                    <PageLinks
+                                                        filter
+                                                        first=after().is_some()
+                                                        next=page.next_cursor
+                                                    />
+                                                }
+                                                    .into_view()
                                             }
                                         }
                                     })
@@ -333,17 +1768,184 @@ pub fn Todos() -> impl IntoView {
 
                 </ErrorBoundary>
             </Transition>
+            <UndoToast delete_todo restore_todo/>
         </Container>
     }
 }
 
+/// Links to the first and next page of todos, keeping the filters.
+#[component]
+pub fn PageLinks<F>(filter: F, first: bool, next: Option<u32>) -> impl IntoView
+where
+    F: Fn() -> TodoFilter + Copy + 'static,
+{
+    let location = use_location();
+    let href = move |after: Option<u32>| filter().href(&location.pathname.get(), after);
+
+    view! {
+        <li class="flex justify-center gap-2">
+            <Show when=move || first>
+                <A href=move || href(None) class="btn btn-ghost">
+                    "First page"
+                </A>
+            </Show>
+            {next
+                .map(|next| {
+                    view! {
+                        <A href=move || href(Some(next)) class="btn btn-ghost">
+                            "Next page"
+                        </A>
+                    }
+                })}
+
+        </li>
+    }
+}
+
+#[component]
+pub fn DueFilterTabs<F>(filter: F) -> impl IntoView
+where
+    F: Fn() -> TodoFilter + Copy + 'static,
+{
+    let location = use_location();
+    let tab = move |due: Option<DueFilter>, label: &'static str| {
+        let href = move || TodoFilter { due, ..filter() }.href(&location.pathname.get(), None);
+        view! {
+            <A href class=move || if filter().due == due { "tab tab-active" } else { "tab" }>
+                {label}
+            </A>
+        }
+    };
+
+    view! {
+        <div role="tablist" class="tabs tabs-boxed mb-4 text-lg">
+            {tab(None, "All")}
+            {DueFilter::ALL
+                .into_iter()
+                .map(|due| tab(Some(due), due.label()))
+                .collect_view()}
+        </div>
+    }
+}
+
+/// Tag chips narrowing the todo list down, each toggling its tag in the filter.
+#[component]
+pub fn TagFilterBar<F>(filter: F, tags: Vec<Tag>) -> impl IntoView
+where
+    F: Fn() -> TodoFilter + Copy + 'static,
+{
+    let location = use_location();
+    let chip = move |tag: Tag| {
+        let selected = move || filter().tags.contains(&tag.id);
+        let href = move || {
+            let mut filter = filter();
+            match filter.tags.iter().position(|id| *id == tag.id) {
+                Some(index) => {
+                    filter.tags.remove(index);
+                }
+                None => filter.tags.push(tag.id),
+            }
+            filter.href(&location.pathname.get(), None)
+        };
+        let style = tag.style();
+        view! {
+            <A
+                href
+                class=move || if selected() { "badge badge-lg" } else { "badge badge-lg badge-outline" }
+            >
+                <span style=style>{tag.name}</span>
+            </A>
+        }
+    };
+    let match_link = move |tag_match: TagMatch| {
+        let href = move || TodoFilter { tag_match, ..filter() }.href(&location.pathname.get(), None);
+        view! {
+            <A
+                href
+                class=move || if filter().tag_match == tag_match { "tab tab-active" } else { "tab" }
+            >
+                {tag_match.label()}
+            </A>
+        }
+    };
+
+    view! {
+        <div class="flex flex-wrap items-center gap-2 mb-4">
+            {tags.into_iter().map(chip).collect_view()}
+            <div role="tablist" class="tabs tabs-boxed tabs-sm ml-auto">
+                {match_link(TagMatch::Any)}
+                {match_link(TagMatch::All)}
+            </div>
+        </div>
+    }
+}
+
+/// Chips showing what was understood in the title of a new todo.
+#[component]
+pub fn QuickAddPreview(quick_add: QuickAdd) -> impl IntoView {
+    view! {
+        <div class="flex flex-wrap items-center gap-2 mb-4">
+            <span class="font-bold">{quick_add.title}</span>
+            {quick_add
+                .due
+                .map(|due| {
+                    view! {
+                        <span class="badge badge-outline">
+                            "Due " {due.format("%a %b %-d, %H:%M").to_string()}
+                        </span>
+                    }
+                })}
+            {quick_add
+                .recurrence
+                .map(|recurrence| {
+                    view! { <span class="badge badge-outline">{recurrence.describe()}</span> }
+                })}
+            {quick_add
+                .priority
+                .map(|priority| {
+                    view! {
+                        <span class=format!("badge badge-outline {}", priority.class())>
+                            {priority.label()}
+                        </span>
+                    }
+                })}
+            {quick_add
+                .project
+                .map(|project| {
+                    view! { <span class="badge badge-outline badge-primary">"@" {project}</span> }
+                })}
+            {quick_add
+                .tags
+                .into_iter()
+                .map(|tag| view! { <span class="badge badge-outline">"#" {tag}</span> })
+                .collect_view()}
+        </div>
+    }
+}
+
 #[component]
 pub fn PendingTodo(input: RwSignal<Option<AddTodo>>) -> impl IntoView {
+    let user = use_context::<UserResource>();
+    let title = move || {
+        let data = input.get()?;
+        let user = user
+            .and_then(|user| user.get())
+            .and_then(Result::ok)
+            .flatten()
+            .unwrap_or_default();
+        Some(match data.smart {
+            Some(_) => quick_add::parse(&data.title, user.to_local(Utc::now())).title,
+            None => QuickAdd::plain(&data.title).title,
+        })
+    };
+
     view! {
         <div class="flex gap-2 animate-pulse">
             <div class="h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
                 <input type="checkbox" class="checkbox checkbox-accent" disabled/>
-                <span class="text-xl">{input.get().map(|data| data.title)}</span>
+                <span class="text-xl">
+                    {title}
+                </span>
                 <span class="flex-1 text-right text-xl">"Loading..."</span>
             </div>
         </div>
@@ -351,31 +1953,266 @@ pub fn PendingTodo(input: RwSignal<Option<AddTodo>>) -> impl IntoView {
 }
 
 #[component]
-pub fn Todo(todo: Todo, delete_todo: Action<DeleteTodo, Result<(), ServerFnError>>) -> impl IntoView {
+pub fn Todo(
+    todo: Todo,
+    projects: Vec<Project>,
+    delete_todo: Action<DeleteTodo, Result<(), ServerFnError>>,
+    set_project: Action<SetTodoProject, Result<(), ServerFnError>>,
+    move_todo: Action<MoveTodo, Result<(), ServerFnError>>,
+    tag_todo: Action<TagTodo, Result<(), ServerFnError>>,
+    set_priority: Action<SetTodoPriority, Result<(), ServerFnError>>,
+    /// Every tag of the user, to pick new tags from.
+    all_tags: Vec<Tag>,
+    /// Todo this one moves in front of with the up button.
+    up: Option<u32>,
+    /// Todo this one moves behind with the down button.
+    down: Option<u32>,
+) -> impl IntoView {
     let (completed, set_completed) = create_signal(todo.completed);
+    let (progress, set_progress) = create_signal(todo.progress());
+    let (expanded, set_expanded) = create_signal(false);
+    let notes = create_rw_signal(todo.notes.clone());
+    let (notes_expanded, set_notes_expanded) = create_signal(false);
+    // Edits show up right away, and are rolled back if the server rejects them
+    let (title, set_title) = create_signal(todo.title.clone());
+    let (due_at, set_due_at) = create_signal(todo.due_at);
+    let (editing_title, set_editing_title) = create_signal(false);
+    let (editing_due, set_editing_due) = create_signal(false);
+    let now = Utc::now();
+    let overdue = move || !completed.get() && due_at.get().is_some_and(|due_at| due_at < now);
+    let owner = store_value(todo.user.clone().unwrap_or_default());
+    let format_local = move |datetime: DateTime<Utc>| {
+        owner.with_value(|owner| owner.format_datetime(datetime))
+    };
+
+    let save_title = move |value: String| {
+        set_editing_title.set(false);
+        let value = value.trim().to_string();
+        let previous = title.get_untracked();
+        if value.is_empty() || value == previous {
+            return;
+        }
+        set_title.set(value.clone());
+        spawn_local(async move {
+            if edit_todo(todo.id, Some(value), None, None, None, None).await.is_err() {
+                set_title.set(previous);
+            }
+        });
+    };
+    // Due dates are edited as the value of a `datetime-local` input, blank meaning none
+    let save_due = move |value: String| {
+        set_editing_due.set(false);
+        let previous = due_at.get_untracked();
+        let next = NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M")
+            .ok()
+            .and_then(|local| owner.with_value(|owner| owner.from_local(local)));
+        if (next.is_none() && !value.trim().is_empty()) || next == previous {
+            return;
+        }
+        set_due_at.set(next);
+        spawn_local(async move {
+            if edit_todo(todo.id, None, None, Some(value), None, None).await.is_err() {
+                set_due_at.set(previous);
+            }
+        });
+    };
+    let project_options = projects
+        .into_iter()
+        .filter(|project| !project.archived || Some(project.id) == todo.project_id)
+        .map(|project| {
+            view! {
+                <option value=project.id selected=Some(project.id) == todo.project_id>
+                    {project.name}
+                </option>
+            }
+        })
+        .collect_view();
 
     view! {
         <div class="flex gap-2">
-            <div class="h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
+            <div
+                class="h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl border border-transparent"
+                class:border-error=overdue
+            >
                 <input
                     type="checkbox"
                     class="checkbox checkbox-accent"
-                    checked=completed
+                    prop:checked=completed
                     on:change=move |ev| {
                         let checked = event_target_checked(&ev);
                         set_completed.set(checked);
                         spawn_local(async move {
-                            update_todo(todo.id, checked).await.unwrap();
+                            if update_todo(todo.id, checked).await.is_err() {
+                                set_completed.set(!checked);
+                            }
                         });
                     }
                 />
 
-                <span class="text-xl">{todo.title}</span>
+                <select
+                    class=format!("select select-sm select-ghost {}", todo.priority.class())
+                    on:change=move |ev| {
+                        if let Some(priority) = Priority::parse(&event_target_value(&ev)) {
+                            set_priority.dispatch(SetTodoPriority { id: todo.id, priority });
+                        }
+                    }
+                >
+                    {Priority::ALL
+                        .into_iter()
+                        .map(|priority| {
+                            view! {
+                                <option value=priority.as_str() selected=priority == todo.priority>
+                                    {priority.label()}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                {move || match editing_title.get() {
+                    true => {
+                        view! {
+                            <InlineEdit
+                                input_type="text"
+                                value=title.get_untracked()
+                                class="text-xl"
+                                on_save=save_title
+                                on_cancel=move |_| set_editing_title.set(false)
+                            />
+                        }
+                            .into_view()
+                    }
+                    false => {
+                        view! {
+                            <span
+                                class="text-xl cursor-text"
+                                title="Click to edit"
+                                on:click=move |_| set_editing_title.set(true)
+                            >
+                                {title}
+                            </span>
+                        }
+                            .into_view()
+                    }
+                }}
+
+                <SubtasksToggle progress expanded set_expanded/>
+                <NotesToggle notes expanded=notes_expanded set_expanded=set_notes_expanded/>
+                <TodoTags todo_id=todo.id tags=todo.tags all_tags tag_todo/>
+                {todo
+                    .recurrence
+                    .map(|recurrence| {
+                        view! {
+                            <span class="badge badge-secondary badge-outline">
+                                {recurrence.describe()}
+                            </span>
+                        }
+                    })}
+                {todo
+                    .start_at
+                    .map(|start_at| {
+                        view! {
+                            <span>
+                                "Starts " <span class="text-primary">{format_local(start_at)}</span>
+                            </span>
+                        }
+                    })}
+                {move || match (editing_due.get(), due_at.get()) {
+                    (true, due_at) => {
+                        let value = due_at
+                            .map(|due_at| {
+                                owner
+                                    .with_value(|owner| {
+                                        owner.to_local(due_at).format("%Y-%m-%dT%H:%M").to_string()
+                                    })
+                            })
+                            .unwrap_or_default();
+                        view! {
+                            <InlineEdit
+                                input_type="datetime-local"
+                                value
+                                class=""
+                                on_save=save_due
+                                on_cancel=move |_| set_editing_due.set(false)
+                            />
+                        }
+                            .into_view()
+                    }
+                    (false, Some(due_at)) => {
+                        view! {
+                            <span
+                                class="cursor-text"
+                                class:text-error=overdue
+                                title="Click to edit"
+                                on:click=move |_| set_editing_due.set(true)
+                            >
+                                {move || if overdue() { "Overdue since " } else { "Due " }}
+                                <span class:text-primary=move || !overdue()>
+                                    {format_local(due_at)}
+                                </span>
+                            </span>
+                        }
+                            .into_view()
+                    }
+                    (false, None) => {
+                        view! {
+                            <button
+                                class="btn btn-ghost btn-xs opacity-50"
+                                on:click=move |_| set_editing_due.set(true)
+                            >
+                                "Set due date"
+                            </button>
+                        }
+                            .into_view()
+                    }
+                }}
+
+                {move || due_at.get().map(|_| view! { <ReminderButton todo_id=todo.id/> })}
                 <span class="flex-1 text-right">
-                    "Created at " <span class="text-primary">{todo.created_at}</span> " by "
+                    "Created at " <span class="text-primary">{format_local(todo.created_at)}</span> " by "
                     <span class="text-primary">{todo.user.unwrap_or_default().username}</span>
                 </span>
+                <select
+                    class="select select-sm select-ghost"
+                    on:change=move |ev| {
+                        set_project
+                            .dispatch(SetTodoProject {
+                                id: todo.id,
+                                project_id: event_target_value(&ev).parse().ok(),
+                            });
+                    }
+                >
+                    <option value="" selected=todo.project_id.is_none()>
+                        "No project"
+                    </option>
+                    {project_options}
+                </select>
             </div>
+            {up
+                .map(|up| {
+                    view! {
+                        <ActionIcon
+                            action=move_todo
+                            icon=i::LuArrowUp
+                            class="btn-ghost bg-base-100 rounded-xl"
+                        >
+                            <input type="hidden" name="id" value=todo.id/>
+                            <input type="hidden" name="before_id" value=up/>
+                        </ActionIcon>
+                    }
+                })}
+            {down
+                .map(|down| {
+                    view! {
+                        <ActionIcon
+                            action=move_todo
+                            icon=i::LuArrowDown
+                            class="btn-ghost bg-base-100 rounded-xl"
+                        >
+                            <input type="hidden" name="id" value=todo.id/>
+                            <input type="hidden" name="after_id" value=down/>
+                        </ActionIcon>
+                    }
+                })}
             <ActionIcon
                 action=delete_todo
                 icon=i::LuTrash2
@@ -384,6 +2221,21 @@ pub fn Todo(todo: Todo, delete_todo: Action<DeleteTodo, Result<(), ServerFnError
                 <input type="hidden" name="id" value=todo.id/>
             </ActionIcon>
         </div>
+        {move || {
+            notes_expanded
+                .get()
+                .then(|| {
+                    view! {
+                        <TodoNotes todo_id=todo.id notes/>
+                        <TodoHistory todo_id=todo.id user=owner.get_value()/>
+                    }
+                })
+        }}
+        {move || {
+            expanded
+                .get()
+                .then(|| view! { <Subtasks parent_id=todo.id set_completed set_progress/> })
+        }}
     }
 }
 
@@ -444,3 +2296,372 @@ pub fn Signup(
         </CenteredCard>
     }
 }
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::ssr::*;
+    use crate::{auth::User, testing, trash::ssr::trash_todo};
+    use chrono::Utc;
+    use leptos::ServerFnError;
+    use sqlx::SqlitePool;
+
+    type Row = (String, String, bool, Option<u32>, Option<String>, u8, Option<String>);
+
+    async fn row(id: u32, pool: &SqlitePool) -> Row {
+        sqlx::query_as(
+            "SELECT title, notes, completed, project_id, rank, priority, deleted_at FROM todos WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    fn assert_not_found(result: Result<(), ServerFnError>) {
+        assert_eq!(result.unwrap_err().to_string(), todo_not_found().to_string());
+    }
+
+    /// Two users, the todos of the first one and the pool they live in.
+    async fn setup() -> (SqlitePool, User, User, u32, u32) {
+        let pool = testing::pool().await;
+        let alice = testing::user("alice", &pool).await;
+        let bob = testing::user("bob", &pool).await;
+        let first = testing::todo("Water the plants", &alice, &pool).await;
+        let second = testing::todo("Pay rent", &alice, &pool).await;
+        (pool, alice, bob, first, second)
+    }
+
+    #[tokio::test]
+    async fn authorize_todo_only_lets_the_owner_through() {
+        let (pool, alice, bob, id, _) = setup().await;
+
+        assert!(authorize_todo(id, &alice, &pool).await.is_ok());
+        assert_not_found(authorize_todo(id, &bob, &pool).await);
+        assert_not_found(authorize_todo(9999, &alice, &pool).await);
+    }
+
+    #[tokio::test]
+    async fn ensure_affected_fails_when_no_row_matched() {
+        let (pool, alice, bob, id, _) = setup().await;
+        let rename = |user: &User| {
+            sqlx::query("UPDATE todos SET title = 'Renamed' WHERE id = ? AND user_id = ?")
+                .bind(id)
+                .bind(user.id)
+        };
+
+        assert_not_found(ensure_affected(rename(&bob).execute(&pool).await.unwrap()));
+        assert!(ensure_affected(rename(&alice).execute(&pool).await.unwrap()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn other_users_cannot_toggle_a_todo() {
+        let (pool, _, bob, id, _) = setup().await;
+        let before = row(id, &pool).await;
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert_not_found(set_completed(&mut conn, &bob, id, true).await);
+        drop(conn);
+
+        assert_eq!(row(id, &pool).await, before);
+    }
+
+    #[tokio::test]
+    async fn other_users_cannot_update_a_todo() {
+        let (pool, _, bob, id, _) = setup().await;
+        let before = row(id, &pool).await;
+
+        let mut conn = pool.acquire().await.unwrap();
+        let changes = TodoChanges {
+            title: Some("Hijacked".into()),
+            notes: Some("Hijacked".into()),
+            priority: Some(super::Priority::High),
+            ..Default::default()
+        };
+        assert_not_found(edit_details(&mut conn, &bob, id, changes).await);
+        assert_not_found(set_priority(&mut conn, &bob, id, super::Priority::Low).await);
+        assert_not_found(set_project(&mut conn, &bob, id, None).await);
+        drop(conn);
+
+        assert_eq!(row(id, &pool).await, before);
+    }
+
+    #[tokio::test]
+    async fn other_users_cannot_delete_a_todo() {
+        let (pool, _, bob, id, _) = setup().await;
+        let before = row(id, &pool).await;
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert_not_found(trash_todo(&mut conn, &bob, id, Utc::now()).await);
+        drop(conn);
+
+        assert_eq!(row(id, &pool).await, before);
+    }
+
+    #[tokio::test]
+    async fn other_users_cannot_move_a_todo() {
+        let (pool, _, bob, first, second) = setup().await;
+        let own = testing::todo("Walk the dog", &bob, &pool).await;
+        let before = (row(first, &pool).await, row(second, &pool).await);
+
+        assert_not_found(move_between(&bob, first, Some(second), None, &pool).await);
+        assert_not_found(move_between(&bob, first, Some(own), None, &pool).await);
+        // Nor use a foreign todo as a neighbour of their own
+        assert_not_found(move_between(&bob, own, Some(first), None, &pool).await);
+        assert_not_found(move_between(&bob, own, None, Some(second), &pool).await);
+
+        assert_eq!((row(first, &pool).await, row(second, &pool).await), before);
+    }
+
+    #[tokio::test]
+    async fn owners_can_still_change_their_todos() {
+        let (pool, alice, _, first, second) = setup().await;
+
+        let mut conn = pool.acquire().await.unwrap();
+        set_completed(&mut conn, &alice, first, true).await.unwrap();
+        drop(conn);
+        move_between(&alice, first, Some(second), None, &pool).await.unwrap();
+
+        let (_, _, completed, _, first_rank, ..) = row(first, &pool).await;
+        let (.., second_rank, _, _) = row(second, &pool).await;
+        assert!(completed);
+        assert!(first_rank > second_rank);
+    }
+
+    #[tokio::test]
+    async fn relative_reminders_follow_recurring_todos() {
+        let (pool, alice, _, id, _) = setup().await;
+        sqlx::query("UPDATE todos SET due_at = '2024-05-01 09:00:00', rrule = 'FREQ=DAILY' WHERE id = ?")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO reminders (user_id, todo_id, remind_at, offset_minutes, message) VALUES
+            (?1, ?2, NULL, 15, 'Soon'), (?1, ?2, '2024-04-30 18:00:00', NULL, 'Once')",
+        )
+        .bind(alice.id)
+        .bind(id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        set_completed(&mut conn, &alice, id, true).await.unwrap();
+        drop(conn);
+
+        let reminders = sqlx::query_as::<_, (Option<i64>, Option<String>, Option<String>)>(
+            "SELECT r.offset_minutes, r.message, s.fire_at FROM reminders r
+            JOIN reminder_schedule s ON s.id = r.id
+            WHERE r.todo_id = (SELECT MAX(id) FROM todos) AND r.todo_id != ?",
+        )
+        .bind(id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            reminders,
+            [(Some(15), Some("Soon".into()), Some("2024-05-02 08:45:00".into()))]
+        );
+    }
+
+    /// Statements run by pools logging them at the trace level, which only the tests counting
+    /// them use.
+    static STATEMENTS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    struct StatementCounter;
+
+    impl log::Log for StatementCounter {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "sqlx::query" && metadata.level() == log::Level::Trace
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                STATEMENTS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// A database whose statements are counted in [`STATEMENTS`].
+    async fn counted_pool() -> SqlitePool {
+        use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions};
+        use std::str::FromStr;
+
+        static COUNTER: StatementCounter = StatementCounter;
+        if log::set_logger(&COUNTER).is_ok() {
+            log::set_max_level(log::LevelFilter::Trace);
+        }
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .log_statements(log::LevelFilter::Trace);
+        testing::pool_with(options).await
+    }
+
+    /// Adds `count` todos of `user`, each with two subtasks and a tag.
+    async fn seed_todos(count: usize, user: &User, pool: &SqlitePool) {
+        let tag = sqlx::query_scalar::<_, u32>(
+            "INSERT INTO tags (user_id, name, color) VALUES (?, 'home', 'red') RETURNING id",
+        )
+        .bind(user.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        for i in 0..count {
+            let id = testing::todo(&format!("Todo {i}"), user, pool).await;
+            sqlx::query(
+                "INSERT INTO todos (title, user_id, completed, parent_id, position)
+                VALUES ('First step', ?1, true, ?2, 0), ('Second step', ?1, false, ?2, 1)",
+            )
+            .bind(user.id)
+            .bind(id)
+            .execute(pool)
+            .await
+            .unwrap();
+            sqlx::query("INSERT INTO todo_tags (todo_id, tag_id) VALUES (?, ?)")
+                .bind(id)
+                .bind(tag)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_a_page_in_a_constant_number_of_statements() {
+        let mut statements = Vec::new();
+
+        for count in [5, 50] {
+            let pool = counted_pool().await;
+            let user = testing::user("alice", &pool).await;
+            seed_todos(count, &user, &pool).await;
+
+            let before = STATEMENTS.load(std::sync::atomic::Ordering::SeqCst);
+            let page = load_page(&user, None, &Default::default(), None, None, Some(500), &pool)
+                .await
+                .unwrap();
+            statements.push(STATEMENTS.load(std::sync::atomic::Ordering::SeqCst) - before);
+
+            assert_eq!(page.todos.len(), count);
+            assert!(page
+                .todos
+                .iter()
+                .all(|todo| (todo.subtasks, todo.subtasks_completed, todo.tags.len()) == (2, 1, 1)));
+        }
+
+        assert_eq!(statements, [2, 2]);
+    }
+
+    #[tokio::test]
+    async fn pages_neither_repeat_nor_skip_todos() {
+        let (pool, alice, bob, ..) = setup().await;
+        seed_todos(23, &alice, &pool).await;
+        testing::todo("Not on the list", &bob, &pool).await;
+        // Equal ranks and due dates are told apart by the ID
+        sqlx::query("UPDATE todos SET rank = 'm', due_at = '2024-05-01 09:00:00' WHERE id % 3 = 0")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE todos SET priority = 2 WHERE id % 4 = 0")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        for sort in super::TodoSort::ALL {
+            let everything = load_page(&alice, None, &Default::default(), Some(sort), None, Some(500), &pool)
+                .await
+                .unwrap();
+            assert_eq!(everything.todos.len(), 25);
+            assert_eq!(everything.next_cursor, None);
+
+            let mut paged = Vec::new();
+            let mut after = None;
+            loop {
+                let page = load_page(&alice, None, &Default::default(), Some(sort), after, Some(4), &pool)
+                    .await
+                    .unwrap();
+                assert!(page.todos.len() <= 4);
+                paged.extend(page.todos.iter().map(|todo| todo.id));
+                match page.next_cursor {
+                    Some(cursor) => {
+                        assert_eq!(Some(cursor), page.todos.last().map(|todo| todo.id));
+                        after = Some(cursor);
+                    }
+                    None => break,
+                }
+            }
+
+            let expected: Vec<_> = everything.todos.iter().map(|todo| todo.id).collect();
+            assert_eq!(paged, expected, "{sort:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_only_continue_after_todos_of_the_user() {
+        let (pool, alice, bob, first, _) = setup().await;
+        let foreign = testing::todo("Not on the list", &bob, &pool).await;
+        let filter = Default::default();
+        let page = |after| load_page(&alice, None, &filter, None, Some(after), Some(10), &pool);
+
+        assert_eq!(page(first).await.unwrap().todos.len(), 1);
+        assert!(page(foreign).await.is_err());
+        assert!(page(9999).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn moves_between_todos_sharing_a_rank() {
+        let (pool, alice, _, first, second) = setup().await;
+        let third = testing::todo("Call mum", &alice, &pool).await;
+        // As when two todos are added at the same time
+        sqlx::query("UPDATE todos SET rank = 'W' WHERE id IN (?, ?)")
+            .bind(first)
+            .bind(second)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        move_between(&alice, third, Some(first), None, &pool).await.unwrap();
+
+        let order = sqlx::query_scalar::<_, u32>(
+            "SELECT id FROM todos WHERE user_id = ? ORDER BY rank, id",
+        )
+        .bind(alice.id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(order, [first, third, second]);
+        let ranks = sqlx::query_scalar::<_, String>("SELECT DISTINCT rank FROM todos")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(ranks.len(), 3);
+    }
+
+    #[test]
+    fn parses_priorities_and_sorts_in_any_case() {
+        use super::{Priority, TodoSort};
+
+        for value in ["high", "High", "HIGH"] {
+            assert_eq!(Priority::parse(value), Some(Priority::High));
+            assert_eq!(
+                serde_json::from_str::<Priority>(&format!("{value:?}")).unwrap(),
+                Priority::High
+            );
+        }
+        assert!(serde_json::from_str::<Priority>(r#""highest""#).is_err());
+        // Form values round trip
+        for priority in Priority::ALL {
+            assert_eq!(
+                serde_json::from_value::<Priority>(priority.as_str().into()).unwrap(),
+                priority
+            );
+        }
+
+        for value in ["due", "Due", "DUE"] {
+            assert_eq!(TodoSort::parse(value), Some(TodoSort::Due));
+        }
+        assert_eq!(TodoSort::parse("soonest"), None);
+    }
+}
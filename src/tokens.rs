@@ -0,0 +1,309 @@
+use chrono::{DateTime, Utc};
+use icondata as i;
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+/// What a personal access token is allowed to do through the REST API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub enum TokenScope {
+    #[default]
+    Read,
+    /// Reading as well as changing things.
+    Write,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(TokenScope::Read),
+            "write" => Some(TokenScope::Write),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "Read only",
+            TokenScope::Write => "Read and write",
+        }
+    }
+}
+
+/// A personal access token, without the token itself which is only shown once.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub id: u32,
+    pub name: String,
+    /// Start of the token, to tell tokens apart.
+    pub prefix: String,
+    pub scope: TokenScope,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Most tokens a user can have at once.
+pub const MAX_ACCESS_TOKENS: u32 = 20;
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{AccessToken, TokenScope};
+    use crate::auth::User;
+    use chrono::{DateTime, Utc};
+    use rand::{distributions::Alphanumeric, Rng};
+    use sha2::{Digest, Sha256};
+    use sqlx::SqlitePool;
+
+    /// Start of every token, so leaked tokens are easy to search for.
+    const TOKEN_PREFIX: &str = "kqt_";
+
+    /// Generates a new random token.
+    pub fn generate_token() -> String {
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(40)
+            .map(char::from)
+            .collect();
+        format!("{TOKEN_PREFIX}{random}")
+    }
+
+    /// Hashes a token to store or look it up.
+    ///
+    /// Tokens are long and random, so unlike passwords a fast unsalted hash is enough
+    /// and lets tokens be found by their hash.
+    pub fn hash_token(token: &str) -> String {
+        Sha256::digest(token.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Finds the user a token belongs to, along with its scope, and records its use.
+    pub async fn authenticate(
+        token: &str,
+        pool: &SqlitePool,
+    ) -> Result<Option<(User, TokenScope)>, sqlx::Error> {
+        let found = sqlx::query_as::<_, (i64, String)>(
+            "UPDATE access_tokens SET last_used_at = ? WHERE token_hash = ?
+            RETURNING user_id, scope",
+        )
+        .bind(Utc::now().naive_utc())
+        .bind(hash_token(token))
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(match found {
+            Some((user_id, scope)) => User::get(user_id, pool)
+                .await
+                .map(|user| (user, TokenScope::parse(&scope).unwrap_or_default())),
+            None => None,
+        })
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlAccessToken {
+        pub id: u32,
+        pub name: String,
+        pub prefix: String,
+        pub scope: String,
+        pub created_at: DateTime<Utc>,
+        pub last_used_at: Option<DateTime<Utc>>,
+    }
+
+    impl SqlAccessToken {
+        pub fn into_access_token(self) -> AccessToken {
+            AccessToken {
+                id: self.id,
+                name: self.name,
+                prefix: self.prefix,
+                scope: TokenScope::parse(&self.scope).unwrap_or_default(),
+                created_at: self.created_at,
+                last_used_at: self.last_used_at,
+            }
+        }
+    }
+}
+
+#[server(GetAccessTokens, "/api")]
+pub async fn get_access_tokens() -> Result<Vec<AccessToken>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(sqlx::query_as::<_, SqlAccessToken>(
+        "SELECT id, name, prefix, scope, created_at, last_used_at FROM access_tokens
+        WHERE user_id = ? ORDER BY id",
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlAccessToken::into_access_token)
+    .collect())
+}
+
+/// Creates a personal access token, returning the token itself.
+/// Only its hash is kept, so it can't be shown again.
+#[server(CreateAccessToken, "/api")]
+pub async fn create_access_token(name: String, scope: TokenScope) -> Result<String, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ServerFnError::new("A token needs a name."));
+    }
+    let count = sqlx::query_scalar::<_, u32>("SELECT COUNT(*) FROM access_tokens WHERE user_id = ?")
+        .bind(user.id)
+        .fetch_one(&pool)
+        .await?;
+    if count >= MAX_ACCESS_TOKENS {
+        return Err(ServerFnError::new(format!(
+            "At most {MAX_ACCESS_TOKENS} tokens can be created, revoke one first."
+        )));
+    }
+
+    let token = generate_token();
+    sqlx::query(
+        "INSERT INTO access_tokens (user_id, name, token_hash, prefix, scope) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(user.id)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(&token[..8])
+    .bind(scope.as_str())
+    .execute(&pool)
+    .await?;
+
+    Ok(token)
+}
+
+/// Revokes a personal access token, which stops working right away.
+#[server(RevokeAccessToken, "/api")]
+pub async fn revoke_access_token(id: u32) -> Result<(), ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let result = sqlx::query("DELETE FROM access_tokens WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    match result.rows_affected() {
+        0 => Err(ServerFnError::new("Token not found.")),
+        _ => Ok(()),
+    }
+}
+
+/// Settings section listing the personal access tokens of the user, to create and revoke them.
+#[component]
+pub fn AccessTokens(user: crate::auth::User) -> impl IntoView {
+    use crate::ui::ActionIcon;
+
+    let create_token = create_server_action::<CreateAccessToken>();
+    let revoke_token = create_server_action::<RevokeAccessToken>();
+    let tokens = create_resource(
+        move || (create_token.version().get(), revoke_token.version().get()),
+        move |_| get_access_tokens(),
+    );
+    let user = store_value(user);
+
+    let created = move || match create_token.value().get() {
+        Some(Ok(token)) => Some(view! {
+            <div role="alert" class="alert alert-success mb-4 flex-col items-start">
+                <span>"Copy your new token now, it won't be shown again:"</span>
+                <code class="select-all break-all">{token}</code>
+            </div>
+        }
+            .into_view()),
+        Some(Err(e)) => Some(view! { <p class="text-error mb-4">{e.to_string()}</p> }.into_view()),
+        None => None,
+    };
+
+    view! {
+        <section class="p-4 bg-base-100 rounded-xl mb-4">
+            <h3 class="text-xl font-bold">"Access tokens"</h3>
+            <p class="opacity-75 mb-4">
+                "Tokens let scripts use the REST API at " <code>"/api/v1"</code>
+                ", described by " <a href="/api/v1/openapi.json" class="link">"its OpenAPI document"</a>
+                ". Send them as " <code>"Authorization: Bearer <token>"</code> "."
+            </p>
+            {created}
+            <ActionForm action=create_token class="flex flex-wrap items-center gap-4 mb-4">
+                <input type="text" name="name" placeholder="Token name" class="input input-bordered flex-1"/>
+                <select name="scope" class="select select-bordered">
+                    {[TokenScope::Read, TokenScope::Write]
+                        .into_iter()
+                        .map(|scope| view! { <option value=format!("{scope:?}")>{scope.label()}</option> })
+                        .collect_view()}
+                </select>
+                <button type="submit" class="btn btn-primary">"Create token"</button>
+            </ActionForm>
+            <Transition fallback=move || view! { <span class="loading loading-spinner"></span> }>
+                {move || {
+                    tokens
+                        .get()
+                        .map(|tokens| match tokens {
+                            Err(e) => view! { <p class="text-error">{e.to_string()}</p> }.into_view(),
+                            Ok(tokens) if tokens.is_empty() => {
+                                view! { <p class="opacity-50">"No tokens yet."</p> }.into_view()
+                            }
+                            Ok(tokens) => {
+                                view! {
+                                    <ul class="space-y-2">
+                                        {tokens
+                                            .into_iter()
+                                            .map(|token| {
+                                                let used = user.with_value(|user| {
+                                                    token
+                                                        .last_used_at
+                                                        .map(|used| user.format_datetime(used))
+                                                        .unwrap_or_else(|| "never".to_string())
+                                                });
+                                                view! {
+                                                    <li class="flex items-center gap-4 px-3 bg-base-200 rounded-xl">
+                                                        <div class="flex-1">
+                                                            <p class="text-lg">{token.name}</p>
+                                                            <p class="text-sm opacity-75">
+                                                                <code>{token.prefix} "…"</code> " · "
+                                                                {token.scope.label()} " · last used " {used}
+                                                            </p>
+                                                        </div>
+                                                        <ActionIcon
+                                                            action=revoke_token
+                                                            icon=i::LuTrash2
+                                                            class="btn-ghost text-error"
+                                                        >
+                                                            <input type="hidden" name="id" value=token.id/>
+                                                        </ActionIcon>
+                                                    </li>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </ul>
+                                }
+                                    .into_view()
+                            }
+                        })
+                }}
+
+            </Transition>
+        </section>
+    }
+}
@@ -0,0 +1,527 @@
+use crate::{auth::{User, UserResource}, error_template::ErrorTemplate, todo::DeleteTodo, ui::{ActionIcon, Container}};
+use chrono::{DateTime, Utc};
+use icondata as i;
+use leptos::*;
+use leptos_icons::Icon;
+use serde::{Deserialize, Serialize};
+
+/// A todo moved to the trash, see [`crate::todo::delete_todo`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashedTodo {
+    pub id: u32,
+    pub title: String,
+    /// Title of the todo it is a subtask of.
+    pub parent_title: Option<String>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Days todos stay in the trash when `TRASH_RETENTION_DAYS` is not set.
+pub const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+
+/// How long the Undo toast stays up after a deletion.
+const UNDO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(8);
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{TrashedTodo, DEFAULT_TRASH_RETENTION_DAYS};
+    use crate::{
+        auth::User,
+        history::{ssr as history, TodoEventKind},
+        subtasks::ssr::sync_completion,
+        todo::ssr::todo_not_found,
+    };
+    use chrono::{DateTime, Duration, Utc};
+    use leptos::ServerFnError;
+    use sqlx::{SqliteConnection, SqlitePool};
+    use tokio::task::JoinHandle;
+
+    /// Moves a todo of `user` to the trash.
+    ///
+    /// Its subtasks go along with it, trashed at the same time so [`restore_todo`] brings them back.
+    pub async fn trash_todo(
+        conn: &mut SqliteConnection,
+        user: &User,
+        id: u32,
+        now: DateTime<Utc>,
+    ) -> Result<(), ServerFnError> {
+        let parent_id = sqlx::query_scalar::<_, Option<u32>>(
+            "UPDATE todos SET deleted_at = ? WHERE id = ? AND user_id = ? AND deleted_at IS NULL
+            RETURNING parent_id",
+        )
+        .bind(now.naive_utc())
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(todo_not_found)?;
+        history::record(&mut *conn, id, user.id, TodoEventKind::Deleted).await?;
+
+        // Subtasks already in the trash keep the time they were deleted at
+        sqlx::query(
+            "WITH RECURSIVE subtree (id) AS (
+                SELECT id FROM todos WHERE parent_id = ? AND deleted_at IS NULL
+                UNION ALL
+                SELECT t.id FROM todos t JOIN subtree s ON t.parent_id = s.id WHERE t.deleted_at IS NULL
+            )
+            UPDATE todos SET deleted_at = ? WHERE id IN (SELECT id FROM subtree)",
+        )
+        .bind(id)
+        .bind(now.naive_utc())
+        .execute(&mut *conn)
+        .await?;
+
+        sync_completion(conn, parent_id).await?;
+
+        Ok(())
+    }
+
+    /// Takes a todo of `user` out of the trash, along with the subtasks trashed with it.
+    ///
+    /// Subtasks of a todo still in the trash only come back with it.
+    pub async fn restore_todo(conn: &mut SqliteConnection, user: &User, id: u32) -> Result<(), ServerFnError> {
+        sqlx::query(
+            "WITH RECURSIVE subtree (id) AS (
+                SELECT c.id FROM todos c JOIN todos t ON t.id = c.parent_id
+                WHERE t.id = ? AND t.user_id = ? AND c.deleted_at = t.deleted_at
+                UNION ALL
+                SELECT c.id FROM todos c JOIN subtree s ON c.parent_id = s.id
+                JOIN todos t ON t.id = s.id WHERE c.deleted_at = t.deleted_at
+            )
+            UPDATE todos SET deleted_at = NULL WHERE id IN (SELECT id FROM subtree)",
+        )
+        .bind(id)
+        .bind(user.id)
+        .execute(&mut *conn)
+        .await?;
+
+        let parent_id = sqlx::query_scalar::<_, Option<u32>>(
+            "UPDATE todos SET deleted_at = NULL WHERE id = ? AND user_id = ? AND deleted_at IS NOT NULL
+            AND NOT EXISTS (SELECT 1 FROM todos p WHERE p.id = todos.parent_id AND p.deleted_at IS NOT NULL)
+            RETURNING parent_id",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(todo_not_found)?;
+        history::record(&mut *conn, id, user.id, TodoEventKind::Restored).await?;
+
+        // A restored open subtask reopens a parent completed automatically
+        sync_completion(conn, parent_id).await?;
+
+        Ok(())
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlTrashedTodo {
+        pub id: u32,
+        pub title: String,
+        pub parent_title: Option<String>,
+        pub deleted_at: DateTime<Utc>,
+    }
+
+    impl SqlTrashedTodo {
+        pub fn into_trashed_todo(self) -> TrashedTodo {
+            TrashedTodo {
+                id: self.id,
+                title: self.title,
+                parent_title: self.parent_title,
+                deleted_at: self.deleted_at,
+            }
+        }
+    }
+
+    /// Background worker purging todos which have been in the trash for longer than the retention period.
+    pub struct TrashCleaner {
+        pool: SqlitePool,
+        retention: Duration,
+        interval: std::time::Duration,
+    }
+
+    impl TrashCleaner {
+        pub fn new(pool: SqlitePool) -> Self {
+            Self {
+                pool,
+                retention: Duration::days(DEFAULT_TRASH_RETENTION_DAYS),
+                interval: std::time::Duration::from_secs(60 * 60),
+            }
+        }
+
+        /// How long todos stay in the trash.
+        pub fn with_retention(mut self, retention: Duration) -> Self {
+            self.retention = retention;
+            self
+        }
+
+        pub fn spawn(self) -> JoinHandle<()> {
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(self.interval);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = self.purge_expired(Utc::now()).await {
+                        log::error!("Failed to empty the trash: {e}");
+                    }
+                }
+            })
+        }
+
+        /// Purges todos deleted before `now` minus the retention period, returning how many were purged.
+        /// Their subtasks go along with them.
+        pub async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+            Ok(sqlx::query("DELETE FROM todos WHERE deleted_at IS NOT NULL AND deleted_at <= ?")
+                .bind((now - self.retention).naive_utc())
+                .execute(&self.pool)
+                .await?
+                .rows_affected())
+        }
+    }
+}
+
+/// Lists the todos in the trash of the current user, last deleted first.
+///
+/// Subtasks deleted along with their parent are left out, they come back with it.
+#[server(GetTrash, "/api")]
+pub async fn get_trash() -> Result<Vec<TrashedTodo>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    Ok(sqlx::query_as::<_, SqlTrashedTodo>(
+        "SELECT t.id, t.title, p.title AS parent_title, t.deleted_at FROM todos t
+        LEFT JOIN todos p ON p.id = t.parent_id
+        WHERE t.user_id = ? AND t.deleted_at IS NOT NULL AND (p.id IS NULL OR p.deleted_at IS NULL)
+        ORDER BY t.deleted_at DESC, t.id DESC",
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(SqlTrashedTodo::into_trashed_todo)
+    .collect())
+}
+
+/// Takes a todo out of the trash, back where it was.
+#[server(RestoreTodo, "/api")]
+pub async fn restore_todo(id: u32) -> Result<(), ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+    let mut tx = pool.begin().await?;
+
+    self::ssr::restore_todo(&mut tx, &user, id).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Deletes one todo in the trash for good, or empties the whole trash when `id` is `None`.
+#[server(PurgeTrash, "/api")]
+pub async fn purge_trash(id: Option<u32>) -> Result<(), ServerFnError> {
+    use crate::todo::ssr::{pool, require_user, todo_not_found};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let result = sqlx::query(
+        "DELETE FROM todos WHERE user_id = ? AND deleted_at IS NOT NULL AND (? IS NULL OR id = ?)",
+    )
+    .bind(user.id)
+    .bind(id)
+    .bind(id)
+    .execute(&pool)
+    .await?;
+
+    match (id, result.rows_affected()) {
+        (Some(_), 0) => Err(todo_not_found()),
+        _ => Ok(()),
+    }
+}
+
+/// Asks before deleting something for good.
+fn confirm(message: &str) -> bool {
+    window().confirm_with_message(message).unwrap_or(false)
+}
+
+#[component]
+pub fn Trash() -> impl IntoView {
+    let restore_todo = create_server_action::<RestoreTodo>();
+    let purge_trash = create_server_action::<PurgeTrash>();
+
+    let trash = create_resource(
+        move || (restore_todo.version().get(), purge_trash.version().get()),
+        move |_| get_trash(),
+    );
+    let user = expect_context::<UserResource>();
+
+    view! {
+        <Container>
+            <div class="flex items-center gap-4 mb-4">
+                <h2 class="flex-1 text-2xl font-bold">"Trash"</h2>
+                <button
+                    class="btn btn-ghost text-lg text-error"
+                    on:click=move |_| {
+                        if confirm("Delete every todo in the trash for good?") {
+                            purge_trash.dispatch(PurgeTrash { id: None });
+                        }
+                    }
+                >
+                    "Empty trash"
+                </button>
+            </div>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|errors| {
+                    view! { <ErrorTemplate errors=errors/> }
+                }>
+                    {move || {
+                        let user = user.get().and_then(Result::ok).flatten().unwrap_or_default();
+                        trash
+                            .get()
+                            .map(move |trash| match trash {
+                                Err(e) => {
+                                    view! {
+                                        <pre class="error">"Server Error: " {e.to_string()}</pre>
+                                    }
+                                        .into_view()
+                                }
+                                Ok(trash) => {
+                                    if trash.is_empty() {
+                                        view! { <p>"The trash is empty."</p> }.into_view()
+                                    } else {
+                                        view! {
+                                            <ul class="overflow-auto space-y-2">
+                                                {trash
+                                                    .into_iter()
+                                                    .map(|todo| {
+                                                        view! {
+                                                            <li>
+                                                                <TrashRow
+                                                                    todo
+                                                                    user=user.clone()
+                                                                    restore_todo
+                                                                    purge_trash
+                                                                />
+                                                            </li>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </ul>
+                                        }
+                                            .into_view()
+                                    }
+                                }
+                            })
+                            .unwrap_or_default()
+                    }}
+
+                </ErrorBoundary>
+            </Transition>
+        </Container>
+    }
+}
+
+#[component]
+pub fn TrashRow(
+    todo: TrashedTodo,
+    user: User,
+    restore_todo: Action<RestoreTodo, Result<(), ServerFnError>>,
+    purge_trash: Action<PurgeTrash, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let deleted_at = user.format_datetime(todo.deleted_at);
+
+    view! {
+        <div class="flex gap-2">
+            <div class="min-h-12 flex flex-1 items-center gap-4 px-3 bg-base-100 rounded-xl">
+                <div class="flex-1">
+                    <p class="text-xl">{todo.title}</p>
+                    {todo
+                        .parent_title
+                        .map(|parent| view! { <p class="text-sm opacity-75">"Subtask of " {parent}</p> })}
+                </div>
+                <span>"Deleted at " <span class="text-primary">{deleted_at}</span></span>
+            </div>
+            <ActionIcon
+                action=restore_todo
+                icon=i::LuArchiveRestore
+                class="btn-ghost bg-base-100 text-accent rounded-xl"
+            >
+                <input type="hidden" name="id" value=todo.id/>
+            </ActionIcon>
+            <button
+                class="btn btn-square btn-ghost bg-base-100 text-error rounded-xl"
+                title="Delete for good"
+                on:click=move |_| {
+                    if confirm("Delete this todo for good?") {
+                        purge_trash.dispatch(PurgeTrash { id: Some(todo.id) });
+                    }
+                }
+            >
+                <Icon icon=i::LuTrash2 class="text-2xl"/>
+            </button>
+        </div>
+    }
+}
+
+/// Toast offering to restore the todo just deleted by `delete_todo`, for a few seconds.
+#[component]
+pub fn UndoToast(
+    delete_todo: Action<DeleteTodo, Result<(), ServerFnError>>,
+    restore_todo: Action<RestoreTodo, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let (deleted, set_deleted) = create_signal(None::<u32>);
+    // The input of an action is cleared once it is done, so keep the ID around until then
+    let pending = store_value(None::<u32>);
+
+    create_effect(move |_| {
+        if let Some(input) = delete_todo.input().get() {
+            pending.set_value(Some(input.id));
+        }
+    });
+    create_effect(move |_| {
+        if let Some(Ok(())) = delete_todo.value().get() {
+            set_deleted.set(pending.get_value());
+        }
+    });
+    create_effect(move |_| {
+        if deleted.get().is_some() {
+            if let Ok(handle) = set_timeout_with_handle(move || set_deleted.set(None), UNDO_TIMEOUT) {
+                on_cleanup(move || handle.clear());
+            }
+        }
+    });
+
+    move || {
+        deleted.get().map(|id| {
+            view! {
+                <div class="toast toast-end z-10">
+                    <div class="alert shadow-lg">
+                        <span>"Todo moved to the trash."</span>
+                        <button
+                            class="btn btn-sm btn-primary"
+                            on:click=move |_| {
+                                set_deleted.set(None);
+                                restore_todo.dispatch(RestoreTodo { id });
+                            }
+                        >
+                            "Undo"
+                        </button>
+                    </div>
+                </div>
+            }
+        })
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::ssr::{restore_todo, trash_todo, TrashCleaner};
+    use crate::{auth::User, testing, todo::ssr::authorize_todo};
+    use chrono::{Duration, Utc};
+    use sqlx::SqlitePool;
+
+    /// A trip with a packing subtask, itself with a subtask, for alice.
+    async fn setup() -> (SqlitePool, User, [u32; 3]) {
+        let pool = testing::pool().await;
+        let alice = testing::user("alice", &pool).await;
+        let trip = testing::todo("Plan the trip", &alice, &pool).await;
+        let packing = testing::subtask("Pack", trip, &alice, &pool).await;
+        let socks = testing::subtask("Socks", packing, &alice, &pool).await;
+        (pool, alice, [trip, packing, socks])
+    }
+
+    async fn trashed(ids: &[u32], pool: &SqlitePool) -> Vec<bool> {
+        let mut trashed = Vec::new();
+        for id in ids {
+            trashed.push(
+                sqlx::query_scalar::<_, bool>("SELECT deleted_at IS NOT NULL FROM todos WHERE id = ?")
+                    .bind(id)
+                    .fetch_one(pool)
+                    .await
+                    .unwrap(),
+            );
+        }
+        trashed
+    }
+
+    #[tokio::test]
+    async fn trashing_takes_subtasks_along() {
+        let (pool, alice, todos @ [trip, packing, socks]) = setup().await;
+        sqlx::query("INSERT INTO reminders (user_id, todo_id, remind_at) VALUES (?, ?, ?)")
+        .bind(alice.id)
+        .bind(socks)
+        .bind((Utc::now() + Duration::hours(1)).naive_utc())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        trash_todo(&mut conn, &alice, trip, Utc::now()).await.unwrap();
+        drop(conn);
+
+        assert_eq!(trashed(&todos, &pool).await, [true, true, true]);
+        for id in [packing, socks] {
+            assert!(authorize_todo(id, &alice, &pool).await.is_err());
+        }
+        let scheduled = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM reminder_schedule")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(scheduled, 0);
+    }
+
+    #[tokio::test]
+    async fn restoring_brings_back_subtasks_trashed_along() {
+        let (pool, alice, todos @ [trip, packing, socks]) = setup().await;
+        let shoes = testing::subtask("Shoes", packing, &alice, &pool).await;
+        let now = Utc::now();
+
+        let mut conn = pool.acquire().await.unwrap();
+        trash_todo(&mut conn, &alice, shoes, now - Duration::minutes(5)).await.unwrap();
+        trash_todo(&mut conn, &alice, trip, now).await.unwrap();
+
+        // Subtasks only come back with their parent
+        assert!(restore_todo(&mut conn, &alice, socks).await.is_err());
+        restore_todo(&mut conn, &alice, trip).await.unwrap();
+        drop(conn);
+
+        assert_eq!(trashed(&todos, &pool).await, [false, false, false]);
+        assert_eq!(trashed(&[shoes], &pool).await, [true]);
+        for id in [trip, packing, socks] {
+            authorize_todo(id, &alice, &pool).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn restoring_needs_the_owner() {
+        let (pool, alice, [trip, ..]) = setup().await;
+        let bob = testing::user("bob", &pool).await;
+
+        let mut conn = pool.acquire().await.unwrap();
+        trash_todo(&mut conn, &alice, trip, Utc::now()).await.unwrap();
+        assert!(restore_todo(&mut conn, &bob, trip).await.is_err());
+        drop(conn);
+
+        assert_eq!(trashed(&[trip], &pool).await, [true]);
+    }
+
+    #[tokio::test]
+    async fn purging_takes_subtasks_along() {
+        let (pool, alice, [trip, ..]) = setup().await;
+        let now = Utc::now();
+
+        let mut conn = pool.acquire().await.unwrap();
+        trash_todo(&mut conn, &alice, trip, now - Duration::days(2)).await.unwrap();
+        drop(conn);
+
+        let cleaner = TrashCleaner::new(pool.clone()).with_retention(Duration::days(1));
+        // Subtasks are deleted by the foreign key, not counted
+        assert_eq!(cleaner.purge_expired(now).await.unwrap(), 1);
+        let left = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM todos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+}
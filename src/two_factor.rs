@@ -0,0 +1,746 @@
+//! Optional two-factor authentication with RFC 6238 time-based one-time passwords.
+//!
+//! Once enabled, logging in takes a code from an authenticator app, or one of the
+//! recovery codes handed out when 2FA was turned on, after the password.
+
+use crate::{
+    ui::{CenteredCard, Form, FormInput},
+    validation::error_message,
+};
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+/// Whether the current user has 2FA on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    /// Recovery codes not used yet.
+    pub recovery_codes_left: u32,
+}
+
+/// What the user needs to add their account to an authenticator app.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwoFactorSetup {
+    /// Base32 secret, for apps which can't scan the QR code.
+    pub secret: String,
+    /// QR code of the `otpauth://` URI, as an SVG document.
+    pub qr_svg: String,
+}
+
+/// Number of recovery codes handed out when 2FA is turned on.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use chrono::{DateTime, Duration, Utc};
+    use hmac::{Hmac, Mac};
+    use leptos::ServerFnError;
+    use rand::{seq::SliceRandom, Rng};
+    use serde::{Deserialize, Serialize};
+    use sha1::Sha1;
+    use sqlx::SqlitePool;
+
+    /// Seconds each code is valid for.
+    const STEP: i64 = 30;
+    const DIGITS: u32 = 6;
+    /// Steps before and after the current one also accepted, for clocks running late or early.
+    const SKEW: i64 = 1;
+    /// Name of the app shown in authenticator apps.
+    const ISSUER: &str = "Kreqo Habits";
+    /// Time left to enter the code after the password.
+    const PENDING_LOGIN_TIMEOUT: Duration = Duration::minutes(5);
+    /// Session key of a login waiting for its code.
+    const PENDING_LOGIN_KEY: &str = "two_factor_pending";
+
+    const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    /// Characters of recovery codes, leaving out the ones easy to mix up.
+    const RECOVERY_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+    /// Encodes bytes as unpadded RFC 4648 base32, as authenticator apps expect secrets.
+    pub fn base32_encode(bytes: &[u8]) -> String {
+        let mut encoded = String::new();
+        for chunk in bytes.chunks(5) {
+            let mut buffer = [0u8; 5];
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            let bits = buffer.iter().fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+            let chars = (chunk.len() * 8).div_ceil(5);
+            for i in 0..chars {
+                let index = (bits >> (35 - i * 5)) & 0x1f;
+                encoded.push(BASE32_ALPHABET[index as usize] as char);
+            }
+        }
+        encoded
+    }
+
+    /// Decodes unpadded base32, ignoring case, spaces and padding.
+    pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut bits = 0u32;
+        let mut count = 0;
+        for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+            let value = BASE32_ALPHABET
+                .iter()
+                .position(|a| *a as char == c.to_ascii_uppercase())?;
+            bits = bits << 5 | value as u32;
+            count += 5;
+            if count >= 8 {
+                count -= 8;
+                bytes.push((bits >> count) as u8);
+                bits &= (1 << count) - 1;
+            }
+        }
+        Some(bytes)
+    }
+
+    /// Time-based one-time password generator of RFC 6238, with SHA-1, 6 digits and
+    /// 30 second steps like authenticator apps use.
+    pub struct Totp {
+        secret: Vec<u8>,
+    }
+
+    impl Totp {
+        pub fn new(secret: Vec<u8>) -> Self {
+            Self { secret }
+        }
+
+        /// Generates a new random secret.
+        pub fn generate() -> Self {
+            Self::new(rand::thread_rng().gen::<[u8; 20]>().to_vec())
+        }
+
+        pub fn from_base32(secret: &str) -> Option<Self> {
+            base32_decode(secret).map(Self::new)
+        }
+
+        pub fn secret_base32(&self) -> String {
+            base32_encode(&self.secret)
+        }
+
+        /// The time step `time` falls in.
+        pub fn step_at(time: DateTime<Utc>) -> i64 {
+            time.timestamp().div_euclid(STEP)
+        }
+
+        /// The code of a time step, as the HOTP of RFC 4226 with the step as counter.
+        pub fn code(&self, step: i64) -> String {
+            let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret)
+                .expect("HMAC takes keys of any length");
+            mac.update(&step.to_be_bytes());
+            let hash = mac.finalize().into_bytes();
+
+            let offset = (hash[hash.len() - 1] & 0xf) as usize;
+            let binary = u32::from_be_bytes([
+                hash[offset] & 0x7f,
+                hash[offset + 1],
+                hash[offset + 2],
+                hash[offset + 3],
+            ]);
+            format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+        }
+
+        /// The step `code` is valid for around `now`, if it is.
+        pub fn verify(&self, code: &str, now: DateTime<Utc>) -> Option<i64> {
+            let current = Self::step_at(now);
+            (current - SKEW..=current + SKEW).find(|step| self.code(*step) == code)
+        }
+
+        /// URI of the account for authenticator apps, usually scanned from a QR code.
+        pub fn otpauth_uri(&self, username: &str) -> String {
+            let encode = |value: &str| {
+                value
+                    .bytes()
+                    .map(|byte| match byte {
+                        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                            (byte as char).to_string()
+                        }
+                        _ => format!("%{byte:02X}"),
+                    })
+                    .collect::<String>()
+            };
+            format!(
+                "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={DIGITS}&period={STEP}",
+                encode(ISSUER),
+                encode(username),
+                self.secret_base32(),
+                encode(ISSUER),
+            )
+        }
+    }
+
+    /// Renders `data` as a QR code in an SVG document.
+    pub fn qr_svg(data: &str) -> Result<String, ServerFnError> {
+        use qrcode::{render::svg, QrCode};
+
+        let code = QrCode::new(data.as_bytes())?;
+        Ok(code
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .dark_color(svg::Color("#000000"))
+            .light_color(svg::Color("#ffffff"))
+            .build())
+    }
+
+    /// Generates a new recovery code, like `k7m2p-x9qrt`.
+    pub fn generate_recovery_code() -> String {
+        let mut rng = rand::thread_rng();
+        let mut part = || -> String {
+            (0..5)
+                .map(|_| *RECOVERY_ALPHABET.choose(&mut rng).unwrap() as char)
+                .collect()
+        };
+        format!("{}-{}", part(), part())
+    }
+
+    /// Recovery codes are hashed the same way whatever their case and separators.
+    fn normalize_recovery_code(code: &str) -> String {
+        code.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+
+    pub fn hash_recovery_code(code: &str) -> String {
+        crate::tokens::ssr::hash_token(&normalize_recovery_code(code))
+    }
+
+    #[derive(sqlx::FromRow, Clone)]
+    pub struct SqlTwoFactor {
+        pub totp_secret: Option<String>,
+        pub totp_pending_secret: Option<String>,
+        pub totp_last_step: Option<i64>,
+    }
+
+    pub async fn load(user_id: i64, pool: &SqlitePool) -> Result<SqlTwoFactor, sqlx::Error> {
+        sqlx::query_as::<_, SqlTwoFactor>(
+            "SELECT totp_secret, totp_pending_secret, totp_last_step FROM users WHERE id = ?",
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Checks a code from the authenticator app or an unused recovery code of a user
+    /// with 2FA on, using it up so it can't be replayed.
+    pub async fn check_code(
+        user_id: i64,
+        code: &str,
+        now: DateTime<Utc>,
+        pool: &SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let Some(totp) = load(user_id, pool)
+            .await?
+            .totp_secret
+            .and_then(|secret| Totp::from_base32(&secret))
+        else {
+            return Ok(false);
+        };
+
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        if code.len() == DIGITS as usize && code.chars().all(|c| c.is_ascii_digit()) {
+            let Some(step) = totp.verify(&code, now) else {
+                return Ok(false);
+            };
+            // Only accept steps after the last one used
+            let accepted = sqlx::query(
+                "UPDATE users SET totp_last_step = ?
+                WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+            )
+            .bind(step)
+            .bind(user_id)
+            .bind(step)
+            .execute(pool)
+            .await?
+            .rows_affected();
+            return Ok(accepted > 0);
+        }
+
+        let used = sqlx::query(
+            "UPDATE recovery_codes SET used_at = ?
+            WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+        )
+        .bind(now.naive_utc())
+        .bind(user_id)
+        .bind(hash_recovery_code(&code))
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(used > 0)
+    }
+
+    /// A login which got the right password, waiting for its code.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct PendingLogin {
+        pub user_id: i64,
+        pub username: String,
+        pub remember: bool,
+        pub expires_at: DateTime<Utc>,
+    }
+
+    /// Holds off logging `user_id` in until a code is entered, through the session.
+    pub fn start_pending_login(user_id: i64, username: String, remember: bool, now: DateTime<Utc>) {
+        if let Some(auth) = leptos::use_context::<crate::auth::ssr::AuthSession>() {
+            auth.session.set(
+                PENDING_LOGIN_KEY,
+                PendingLogin {
+                    user_id,
+                    username,
+                    remember,
+                    expires_at: now + PENDING_LOGIN_TIMEOUT,
+                },
+            );
+        }
+    }
+
+    /// The login waiting for a code in this session, unless it expired.
+    pub fn pending_login(now: DateTime<Utc>) -> Option<PendingLogin> {
+        let auth = leptos::use_context::<crate::auth::ssr::AuthSession>()?;
+        auth.session
+            .get::<PendingLogin>(PENDING_LOGIN_KEY)
+            .filter(|pending| pending.expires_at > now)
+    }
+
+    pub fn end_pending_login() {
+        if let Some(auth) = leptos::use_context::<crate::auth::ssr::AuthSession>() {
+            auth.session.remove(PENDING_LOGIN_KEY);
+        }
+    }
+}
+
+#[server(GetTwoFactorStatus, "/api")]
+pub async fn get_two_factor_status() -> Result<TwoFactorStatus, ServerFnError> {
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let enabled = ssr::load(user.id, &pool).await?.totp_secret.is_some();
+    let recovery_codes_left = sqlx::query_scalar::<_, u32>(
+        "SELECT COUNT(*) FROM recovery_codes WHERE user_id = ? AND used_at IS NULL",
+    )
+    .bind(user.id)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok(TwoFactorStatus {
+        enabled,
+        recovery_codes_left,
+    })
+}
+
+/// Starts turning 2FA on with a new secret, which is only kept once a first code confirms it.
+#[server(StartTwoFactorSetup, "/api")]
+pub async fn start_two_factor_setup() -> Result<TwoFactorSetup, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    if load(user.id, &pool).await?.totp_secret.is_some() {
+        return Err(ServerFnError::new("Two-factor authentication is already on."));
+    }
+
+    let totp = Totp::generate();
+    let secret = totp.secret_base32();
+    sqlx::query("UPDATE users SET totp_pending_secret = ? WHERE id = ?")
+        .bind(&secret)
+        .bind(user.id)
+        .execute(&pool)
+        .await?;
+
+    Ok(TwoFactorSetup {
+        qr_svg: qr_svg(&totp.otpauth_uri(&user.username))?,
+        secret,
+    })
+}
+
+/// Turns 2FA on once `code` shows the authenticator app has the new secret.
+/// Returns the recovery codes, which can't be shown again.
+#[server(EnableTwoFactor, "/api")]
+pub async fn enable_two_factor(code: String) -> Result<Vec<String>, ServerFnError> {
+    use self::ssr::*;
+    use crate::todo::ssr::{pool, require_user};
+    use chrono::Utc;
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    let totp = load(user.id, &pool)
+        .await?
+        .totp_pending_secret
+        .and_then(|secret| Totp::from_base32(&secret))
+        .ok_or_else(|| ServerFnError::new("Start setting up two-factor authentication first."))?;
+    let step = totp
+        .verify(code.trim(), Utc::now())
+        .ok_or_else(|| ServerFnError::new("This code is not the right one."))?;
+
+    let codes: Vec<_> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE users SET totp_secret = totp_pending_secret, totp_pending_secret = NULL, totp_last_step = ?
+        WHERE id = ?",
+    )
+    .bind(step)
+    .bind(user.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+    for code in &codes {
+        sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES (?, ?)")
+            .bind(user.id)
+            .bind(hash_recovery_code(code))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(codes)
+}
+
+/// Turns 2FA off, which takes both the password and a code.
+#[server(DisableTwoFactor, "/api")]
+pub async fn disable_two_factor(password: String, code: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::{
+        auth::ssr::verify_password,
+        todo::ssr::{pool, require_user},
+    };
+    use chrono::Utc;
+
+    let user = require_user()?;
+    let pool = pool()?;
+
+    verify_password(&user, &password, &pool).await?;
+    if !check_code(user.id, &code, Utc::now(), &pool).await? {
+        return Err(ServerFnError::new("This code is not the right one."));
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE users SET totp_secret = NULL, totp_pending_secret = NULL, totp_last_step = NULL
+        WHERE id = ?",
+    )
+    .bind(user.id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Second step of logging in with 2FA on, after [`crate::auth::login`] checked the password.
+#[server(VerifyTwoFactor, "/api")]
+pub async fn verify_two_factor(code: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use crate::{
+        auth::ssr::client_ip,
+        throttle,
+        todo::ssr::{auth, pool},
+    };
+    use chrono::Utc;
+
+    let pool = pool()?;
+    let auth = auth()?;
+    let now = Utc::now();
+
+    let pending = pending_login(now)
+        .ok_or_else(|| ServerFnError::new("Your login expired, enter your password again."))?;
+
+    // Codes are short, so guessing them is throttled like passwords
+    let ip = client_ip().await;
+    let keys = throttle::keys(&pending.username, ip);
+    if let Some(wait) = throttle::locked_for(&keys, now, &pool).await? {
+        return Err(ServerFnError::new(format!(
+            "Too many failed attempts, try again in {} seconds.",
+            wait.num_seconds() + 1
+        )));
+    }
+
+    if !check_code(pending.user_id, &code, now, &pool).await? {
+        throttle::record_failure(&keys, &pending.username, ip, now, &pool).await?;
+        return Err(ServerFnError::new("This code is not the right one."));
+    }
+
+    end_pending_login();
+    throttle::record_success(&pending.username, &pool).await?;
+    auth.login_user(pending.user_id);
+    auth.remember_user(pending.remember);
+    leptos_axum::redirect("/");
+
+    Ok(())
+}
+
+/// Second step of logging in, asking for a code.
+#[component]
+pub fn TwoFactorLogin(action: Action<VerifyTwoFactor, Result<(), ServerFnError>>) -> impl IntoView {
+    view! {
+        <CenteredCard>
+            <Form action title="Two-Factor Authentication" submit="Verify">
+                <p class="opacity-75">
+                    "Enter the code from your authenticator app, or one of your recovery codes."
+                </p>
+                <FormInput
+                    input_type="text"
+                    name="code"
+                    label="Code"
+                    placeholder="123456"
+                    maxlength=16
+                />
+            </Form>
+        </CenteredCard>
+    }
+}
+
+/// Settings section to turn 2FA on and off.
+#[component]
+pub fn TwoFactorSettings() -> impl IntoView {
+    let start_setup = create_server_action::<StartTwoFactorSetup>();
+    let enable = create_server_action::<EnableTwoFactor>();
+    let disable = create_server_action::<DisableTwoFactor>();
+    let status = create_resource(
+        move || (enable.version().get(), disable.version().get()),
+        move |_| get_two_factor_status(),
+    );
+
+    let error = move |e: ServerFnError| view! { <p class="text-error mb-2">{error_message(&e)}</p> };
+
+    // Shown right after turning 2FA on, the only time they can be seen
+    let recovery_codes = move || match enable.value().get() {
+        Some(Ok(codes)) => Some(
+            view! {
+                <div role="alert" class="alert alert-success mb-4 flex-col items-start">
+                    <span>
+                        "Two-factor authentication is on. Keep these recovery codes somewhere safe, "
+                        "each one logs you in once without your app:"
+                    </span>
+                    <ul class="grid grid-cols-2 gap-x-8 font-mono select-all">
+                        {codes.into_iter().map(|code| view! { <li>{code}</li> }).collect_view()}
+                    </ul>
+                </div>
+            }
+                .into_view(),
+        ),
+        Some(Err(e)) => Some(error(e).into_view()),
+        None => None,
+    };
+
+    let setup = move || match start_setup.value().get() {
+        Some(Ok(setup)) => Some(
+            view! {
+                <div class="flex flex-wrap items-center gap-6 mb-4">
+                    <div class="bg-white p-2 rounded-xl" inner_html=setup.qr_svg></div>
+                    <div class="flex-1 space-y-2">
+                        <p>"Scan this code with your authenticator app, or enter this secret:"</p>
+                        <code class="select-all break-all">{setup.secret}</code>
+                        <ActionForm action=enable class="flex items-center gap-4">
+                            <input
+                                type="text"
+                                name="code"
+                                placeholder="Code from the app"
+                                maxlength=6
+                                autocomplete="one-time-code"
+                                class="input input-bordered flex-1"
+                            />
+                            <button type="submit" class="btn btn-primary">"Turn on"</button>
+                        </ActionForm>
+                    </div>
+                </div>
+            }
+                .into_view(),
+        ),
+        Some(Err(e)) => Some(error(e).into_view()),
+        None => None,
+    };
+
+    view! {
+        <section class="p-4 bg-base-100 rounded-xl mb-4">
+            <h3 class="text-xl font-bold">"Two-factor authentication"</h3>
+            <p class="opacity-75 mb-4">
+                "Logging in also takes a code from an authenticator app on your phone."
+            </p>
+            {recovery_codes}
+            <Transition fallback=move || view! { <span class="loading loading-spinner"></span> }>
+                {move || {
+                    status
+                        .get()
+                        .map(|status| match status {
+                            Err(e) => error(e).into_view(),
+                            Ok(status) if status.enabled => {
+                                view! {
+                                    <p class="mb-2">
+                                        "On, with " {status.recovery_codes_left}
+                                        " recovery codes left."
+                                    </p>
+                                    <ActionForm
+                                        action=disable
+                                        class="flex flex-wrap items-center gap-4 mb-2"
+                                    >
+                                        <input
+                                            type="password"
+                                            name="password"
+                                            placeholder="Password"
+                                            class="input input-bordered flex-1"
+                                        />
+                                        <input
+                                            type="text"
+                                            name="code"
+                                            placeholder="Code or recovery code"
+                                            autocomplete="one-time-code"
+                                            class="input input-bordered flex-1"
+                                        />
+                                        <button type="submit" class="btn btn-error">
+                                            "Turn off"
+                                        </button>
+                                    </ActionForm>
+                                    {move || disable.value().get().and_then(Result::err).map(error)}
+                                }
+                                    .into_view()
+                            }
+                            Ok(_) => {
+                                view! {
+                                    {setup}
+                                    <ActionForm action=start_setup>
+                                        <button type="submit" class="btn btn-primary">
+                                            "Set up"
+                                        </button>
+                                    </ActionForm>
+                                }
+                                    .into_view()
+                            }
+                        })
+                }}
+
+            </Transition>
+        </section>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::ssr::*;
+    use crate::testing;
+    use chrono::{DateTime, Utc};
+    use sqlx::SqlitePool;
+
+    /// Secret of the SHA-1 test vectors of RFC 6238.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(timestamp, 0).unwrap()
+    }
+
+    /// A user with 2FA on using the RFC secret, and `recovery` as their only recovery code.
+    async fn setup(recovery: &str) -> (SqlitePool, i64) {
+        let pool = testing::pool().await;
+        let user = testing::user("alice", &pool).await;
+        sqlx::query("UPDATE users SET totp_secret = ? WHERE id = ?")
+            .bind(base32_encode(RFC_SECRET))
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES (?, ?)")
+            .bind(user.id)
+            .bind(hash_recovery_code(recovery))
+            .execute(&pool)
+            .await
+            .unwrap();
+        (pool, user.id)
+    }
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        // Appendix B, down to the last 6 of the 8 digits
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        let totp = Totp::new(RFC_SECRET.to_vec());
+        for (timestamp, code) in vectors {
+            assert_eq!(totp.code(Totp::step_at(at(timestamp))), code, "{timestamp}");
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_either_way() {
+        let totp = Totp::new(RFC_SECRET.to_vec());
+        // The first and last second of step 100
+        for now in [at(3000), at(3029)] {
+            assert_eq!(totp.verify(&totp.code(99), now), Some(99));
+            assert_eq!(totp.verify(&totp.code(100), now), Some(100));
+            assert_eq!(totp.verify(&totp.code(101), now), Some(101));
+            assert_eq!(totp.verify(&totp.code(98), now), None);
+            assert_eq!(totp.verify(&totp.code(102), now), None);
+        }
+        assert_eq!(totp.verify(&totp.code(101), at(2999)), None);
+        assert_eq!(totp.verify(&totp.code(99), at(3030)), None);
+    }
+
+    #[test]
+    fn round_trips_base32() {
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_encode(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+
+        for length in 0..=21 {
+            let bytes: Vec<u8> = (0..length).map(|i| (i * 37 + 11) as u8).collect();
+            assert_eq!(base32_decode(&base32_encode(&bytes)), Some(bytes));
+        }
+        assert_eq!(base32_decode("mzxw 6ytb oi=="), Some(b"foobar".to_vec()));
+        assert_eq!(base32_decode("MZXW1"), None);
+
+        let totp = Totp::generate();
+        let decoded = Totp::from_base32(&totp.secret_base32()).unwrap();
+        assert_eq!(decoded.code(1), totp.code(1));
+    }
+
+    #[tokio::test]
+    async fn rejects_replayed_steps() {
+        let (pool, user_id) = setup("abcde-fghjk").await;
+        let totp = Totp::new(RFC_SECRET.to_vec());
+        let now = at(3000);
+
+        assert!(check_code(user_id, &totp.code(100), now, &pool).await.unwrap());
+        assert!(!check_code(user_id, &totp.code(100), now, &pool).await.unwrap());
+        // Nor any earlier step still in the window
+        assert!(!check_code(user_id, &totp.code(99), now, &pool).await.unwrap());
+        assert!(check_code(user_id, &totp.code(101), now, &pool).await.unwrap());
+        assert!(!check_code(user_id, "000000", at(6000), &pool).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn uses_recovery_codes_once() {
+        let (pool, user_id) = setup("abcde-fghjk").await;
+        let now = at(3000);
+
+        assert!(!check_code(user_id, "abcde-zzzzz", now, &pool).await.unwrap());
+        // Whatever the case and separators
+        assert!(check_code(user_id, "ABCDE FGHJK", now, &pool).await.unwrap());
+        assert!(!check_code(user_id, "abcde-fghjk", now, &pool).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn needs_two_factor_on() {
+        let pool = testing::pool().await;
+        let user = testing::user("bob", &pool).await;
+        let totp = Totp::new(RFC_SECRET.to_vec());
+
+        assert!(!check_code(user.id, &totp.code(100), at(3000), &pool).await.unwrap());
+    }
+}
@@ -1,9 +1,12 @@
+use crate::validation::{error_message, FieldErrors};
 use leptos::{
-    component,
+    component, create_node_ref, ev, event_target_value, html, provide_context,
+    request_animation_frame,
     server_fn::{
         client::Client, codec::PostUrl, error::NoCustomError, request::ClientReq, ServerFn,
     },
-    view, Action, AttributeValue, Children, IntoView, Serializable, ServerFnError,
+    use_context, view, Action, AttributeValue, Callable, Callback, Children, IntoView,
+    Serializable, ServerFnError, Signal, SignalGet,
 };
 use leptos_icons::Icon;
 use leptos_router::ActionForm;
@@ -26,10 +29,10 @@ pub fn CenteredCard(children: Children) -> impl IntoView {
 }
 
 #[component]
-pub fn ActionIcon<I, O, 'a>(
+pub fn ActionIcon<I, O>(
     action: Action<I, Result<O, ServerFnError>>,
     icon: icondata::Icon,
-    class: &'a str,
+    #[prop(into)] class: String,
     children: Children,
 ) -> impl IntoView
 where
@@ -42,8 +45,6 @@ where
         <I as ServerFn>::Error,
     >>::FormData: From<web_sys::FormData>,
 {
-    let class = class.to_string();
-
     view! {
         <ActionForm action>
             {children()} <button type="submit" class=format!("btn btn-square {class}")>
@@ -53,6 +54,46 @@ where
     }
 }
 
+/// Input editing a value in place, focused as soon as it shows up.
+/// Enter saves the new value, while Escape or clicking away cancels.
+#[component]
+pub fn InlineEdit(
+    #[prop(into)] input_type: String,
+    value: String,
+    #[prop(into)] class: String,
+    #[prop(into)] on_save: Callback<String>,
+    #[prop(into)] on_cancel: Callback<()>,
+) -> impl IntoView {
+    let input = create_node_ref::<html::Input>();
+    input.on_load(|input| {
+        request_animation_frame(move || {
+            let _ = input.focus();
+        });
+    });
+
+    view! {
+        <input
+            type=input_type
+            node_ref=input
+            value=value
+            class=format!("input input-sm input-bordered {class}")
+            on:keydown=move |ev: ev::KeyboardEvent| match ev.key().as_str() {
+                "Enter" => {
+                    ev.prevent_default();
+                    on_save.call(event_target_value(&ev));
+                }
+                "Escape" => on_cancel.call(()),
+                _ => {}
+            }
+            on:blur=move |_| on_cancel.call(())
+        />
+    }
+}
+
+/// Error of the last submission of a [`Form`], for its inputs to show their own.
+#[derive(Clone, Copy)]
+struct FormError(Signal<Option<ServerFnError>>);
+
 #[component]
 pub fn Form<I, O, 'a>(
     action: Action<I, Result<O, ServerFnError>>,
@@ -72,20 +113,29 @@ where
 {
     let title = title.to_string();
     let submit = submit.to_string();
+    let error = Signal::derive(move || action.value().get().and_then(Result::err));
+    provide_context(FormError(error));
+
+    // Errors about a field are shown beside it instead
+    let form_error = move || {
+        error
+            .get()
+            .filter(|e| FieldErrors::from_error(e).is_none())
+            .map(|e| view! { <p class="text-error mt-2">{error_message(&e)}</p> })
+    };
 
     view! {
         <ActionForm action class="w-full flex flex-col items-center">
-            <FormTitle text=&title/>
+            <FormTitle text=title/>
+            {form_error}
             <div class="w-full flex flex-col mt-4 gap-4 mb-6">{children()}</div>
-            <FormSubmit msg=&submit/>
+            <FormSubmit msg=submit/>
         </ActionForm>
     }
 }
 
 #[component]
-pub fn FormTitle<'a>(text: &'a str) -> impl IntoView {
-    let text = text.to_string();
-
+pub fn FormTitle(#[prop(into)] text: String) -> impl IntoView {
     view! { <h1 class="text-primary text-2xl font-bold">{text}</h1> }
 }
 
@@ -103,6 +153,14 @@ pub fn FormInput<'a>(
     let name = name.to_string();
     let label = label.to_string();
     let placeholder = placeholder.to_string();
+    let error = use_context::<FormError>();
+    let field = name.clone();
+    let field_error = Signal::derive(move || {
+        error
+            .and_then(|FormError(error)| error.get())
+            .and_then(|e| FieldErrors::from_error(&e))
+            .and_then(|errors| errors.get(&field).map(str::to_string))
+    });
 
     view! {
         <div>
@@ -117,8 +175,23 @@ pub fn FormInput<'a>(
                     placeholder=placeholder
                     value=default_value
                     maxlength=maxlength
-                    class="input input-accent w-full text-lg"
+                    class=move || match field_error.get() {
+                        Some(_) => "input input-error w-full text-lg",
+                        None => "input input-accent w-full text-lg",
+                    }
                 />
+                {move || {
+                    field_error
+                        .get()
+                        .map(|message| {
+                            view! {
+                                <div class="label">
+                                    <span class="label-text-alt text-error">{message}</span>
+                                </div>
+                            }
+                        })
+                }}
+
             </label>
         </div>
     }
@@ -140,9 +213,7 @@ pub fn FormCheckbox<'a>(label: &'a str, id: &'a str) -> impl IntoView {
 }
 
 #[component]
-pub fn FormSubmit<'a>(msg: &'a str) -> impl IntoView {
-    let msg = msg.to_string();
-
+pub fn FormSubmit(#[prop(into)] msg: String) -> impl IntoView {
     view! {
         <button type="submit" class="btn btn-primary btn-wide text-lg">
             {msg}
@@ -0,0 +1,226 @@
+use leptos::ServerFnError;
+
+/// Start of the message of a [`ServerFnError`] carrying [`FieldErrors`].
+const FIELD_ERRORS_PREFIX: &str = "Invalid fields:\n";
+
+/// Problems with the fields of a submitted form, shown beside each field.
+///
+/// Server functions can only fail with a message, so these travel encoded in it,
+/// one `field: message` per line, and are decoded by [`FieldErrors::from_error`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FieldErrors(Vec<(String, String)>);
+
+impl FieldErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an error to `field`, keeping only the first one of each field.
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        if self.get(field).is_none() {
+            self.0.push((field.to_string(), message.into()));
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, message)| message.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Fails with these errors, if there are any.
+    pub fn into_result(self) -> Result<(), ServerFnError> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(self.into()),
+        }
+    }
+
+    /// The field errors a server function failed with, if it did because of them.
+    pub fn from_error(error: &ServerFnError) -> Option<Self> {
+        let ServerFnError::ServerError(message) = error else {
+            return None;
+        };
+        let fields = message.strip_prefix(FIELD_ERRORS_PREFIX)?;
+
+        Some(Self(
+            fields
+                .lines()
+                .filter_map(|line| line.split_once(": "))
+                .map(|(field, message)| (field.to_string(), message.to_string()))
+                .collect(),
+        ))
+    }
+}
+
+impl From<FieldErrors> for ServerFnError {
+    fn from(errors: FieldErrors) -> Self {
+        let fields: Vec<_> = errors
+            .0
+            .iter()
+            .map(|(field, message)| format!("{field}: {message}"))
+            .collect();
+        ServerFnError::ServerError(format!("{FIELD_ERRORS_PREFIX}{}", fields.join("\n")))
+    }
+}
+
+/// Message of an error for the user, field errors being listed one after the other.
+pub fn error_message(error: &ServerFnError) -> String {
+    match (FieldErrors::from_error(error), error) {
+        (Some(errors), _) => errors
+            .0
+            .into_iter()
+            .map(|(_, message)| message)
+            .collect::<Vec<_>>()
+            .join(" "),
+        (None, ServerFnError::ServerError(message)) => message.clone(),
+        (None, error) => error.to_string(),
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::FieldErrors;
+    use sqlx::SqlitePool;
+
+    /// Passwords refused whatever the policy, bundled so no lookup leaves the server.
+    const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+    /// Rules usernames and passwords must follow, see `main.rs` for how they are configured.
+    #[derive(Clone, Debug)]
+    pub struct ValidationPolicy {
+        username_min_length: usize,
+        username_max_length: usize,
+        password_min_length: usize,
+    }
+
+    impl Default for ValidationPolicy {
+        fn default() -> Self {
+            Self {
+                username_min_length: 3,
+                username_max_length: 32,
+                password_min_length: 8,
+            }
+        }
+    }
+
+    /// bcrypt ignores everything past this many bytes.
+    const PASSWORD_MAX_BYTES: usize = 72;
+
+    /// The policy the server was started with.
+    pub fn policy() -> ValidationPolicy {
+        leptos::use_context::<ValidationPolicy>().unwrap_or_default()
+    }
+
+    impl ValidationPolicy {
+        pub fn with_username_min_length(mut self, min: usize) -> Self {
+            self.username_min_length = min.max(1);
+            self.username_max_length = self.username_max_length.max(self.username_min_length);
+            self
+        }
+
+        pub fn with_username_max_length(mut self, max: usize) -> Self {
+            self.username_max_length = max.max(self.username_min_length);
+            self
+        }
+
+        pub fn with_password_min_length(mut self, min: usize) -> Self {
+            self.password_min_length = min.clamp(1, PASSWORD_MAX_BYTES);
+            self
+        }
+
+        /// Checks the shape of a username: letters, digits, `_`, `-` and `.`,
+        /// starting with a letter or digit.
+        pub fn check_username(&self, username: &str, errors: &mut FieldErrors) {
+            let length = username.chars().count();
+            if length < self.username_min_length || length > self.username_max_length {
+                errors.add(
+                    "username",
+                    format!(
+                        "Usernames need {} to {} characters.",
+                        self.username_min_length, self.username_max_length
+                    ),
+                );
+            } else if !username.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+                errors.add("username", "Usernames start with a letter or a digit.");
+            } else if !username
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            {
+                errors.add(
+                    "username",
+                    "Usernames only use letters, digits, \"_\", \"-\" and \".\".",
+                );
+            }
+        }
+
+        /// Checks a new password of `username`, under the `field` name of its form.
+        pub fn check_password(
+            &self,
+            field: &str,
+            password: &str,
+            username: &str,
+            errors: &mut FieldErrors,
+        ) {
+            if password.chars().count() < self.password_min_length {
+                errors.add(
+                    field,
+                    format!("Passwords need at least {} characters.", self.password_min_length),
+                );
+            } else if password.len() > PASSWORD_MAX_BYTES {
+                errors.add(
+                    field,
+                    format!("Passwords can't be longer than {PASSWORD_MAX_BYTES} bytes."),
+                );
+            } else if password.eq_ignore_ascii_case(username) {
+                errors.add(field, "The password can't be the username.");
+            } else if is_common_password(password) {
+                errors.add(field, "This password is too common, pick another one.");
+            }
+        }
+    }
+
+    fn is_common_password(password: &str) -> bool {
+        let password = password.to_lowercase();
+        COMMON_PASSWORDS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .any(|common| common.to_lowercase() == password)
+    }
+
+    /// Checks no other user than `user_id` has `username`.
+    pub async fn check_username_free(
+        username: &str,
+        user_id: Option<i64>,
+        pool: &SqlitePool,
+        errors: &mut FieldErrors,
+    ) -> Result<(), sqlx::Error> {
+        let taken = sqlx::query("SELECT id FROM users WHERE username = ? AND id IS NOT ?")
+            .bind(username)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+        if taken.is_some() {
+            errors.add("username", "This username is already taken.");
+        }
+        Ok(())
+    }
+
+    /// Field errors for a username taken between the check and the write.
+    pub fn username_taken(e: sqlx::Error) -> leptos::ServerFnError {
+        match &e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                let mut errors = FieldErrors::new();
+                errors.add("username", "This username is already taken.");
+                errors.into()
+            }
+            _ => e.into(),
+        }
+    }
+}